@@ -0,0 +1,15 @@
+#![no_main]
+
+use std::convert::TryFrom;
+
+use ircd::structs::IrcMessage;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(line) = std::str::from_utf8(data) {
+        if let Ok(irc_message) = IrcMessage::try_from(line) {
+            let _ = irc_message.to_command();
+            let _ = irc_message.source();
+        }
+    }
+});
@@ -0,0 +1,28 @@
+#![no_main]
+
+use std::convert::TryFrom;
+
+use ircd::framing::MAX_MESSAGE_LEN;
+use ircd::structs::{IrcMessage, Trailing};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // longer lines get their text cut when they're written back out
+    if data.len() > MAX_MESSAGE_LEN - 2 {
+        return;
+    }
+    let line = match std::str::from_utf8(data) {
+        Ok(line) => line,
+        Err(_) => return,
+    };
+
+    // anything that parses and can be written back out parses the same way again
+    if let Ok(irc_message) = IrcMessage::try_from(line) {
+        for &trailing in &[Trailing::IfNeeded, Trailing::Always] {
+            if let Ok(serialized) = irc_message.to_line(trailing) {
+                let parsed = IrcMessage::try_from(serialized.strip_suffix("\r\n").unwrap());
+                assert_eq!(parsed, Ok(irc_message.clone()));
+            }
+        }
+    }
+});
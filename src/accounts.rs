@@ -0,0 +1,185 @@
+//! Account storage used to check SASL credentials.
+
+use std::fs::read_to_string;
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use serde::Deserialize;
+
+use crate::casemap::casefold;
+use crate::sasl::{PlainCredentials, Step};
+use crate::scram::ScramCredentials;
+
+/// Where the server looks up accounts. Implementations must be safe to share between every
+/// connection.
+pub trait AccountStore: Send + Sync {
+    /// Checks a password, returning the account's canonical name if it matches.
+    fn verify_password(&self, account: &str, password: &str) -> Option<String>;
+
+    /// Finds the account a TLS client certificate fingerprint is registered to.
+    fn find_by_certfp(&self, certfp: &str) -> Option<String>;
+
+    /// Looks up an account's SCRAM-SHA-256 credentials, along with its canonical name. Stores
+    /// that don't keep them can't be used with SCRAM.
+    fn scram_credentials(&self, _account: &str) -> Option<(String, ScramCredentials)> {
+        None
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Account {
+    pub name: String,
+    /// An Argon2 hash in PHC string format, as produced by `hash_password`.
+    pub password: Option<String>,
+    /// SCRAM-SHA-256 credentials in the RFC 5803 format, as produced by `ScramCredentials`.
+    pub scram_sha_256: Option<String>,
+    /// Lowercase hex SHA-256 fingerprints of client certificates that may log in as the account.
+    #[serde(default)]
+    pub certfps: Vec<String>,
+}
+
+/// Accounts read from a TOML file made up of `[[accounts]]` tables.
+///
+/// Examples
+///
+/// ```
+/// use ircd::accounts::{hash_password, AccountStore, TomlAccountStore};
+///
+/// let toml = format!(r#"
+///     [[accounts]]
+///     name = "Cardinal"
+///     password = "{}"
+/// "#, hash_password("hunter2"));
+/// let store = TomlAccountStore::from_toml(&toml).unwrap();
+///
+/// assert_eq!(store.verify_password("cardinal", "hunter2"), Some("Cardinal".to_owned()));
+/// assert_eq!(store.verify_password("cardinal", "hunter3"), None);
+/// ```
+#[derive(Deserialize, Default)]
+pub struct TomlAccountStore {
+    #[serde(default)]
+    accounts: Vec<Account>,
+}
+
+impl TomlAccountStore {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let toml = read_to_string(path).map_err(|_| format!("Error opening file: {}", path))?;
+        Self::from_toml(&toml)
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, String> {
+        let store: Self = toml::from_str(toml)
+            .map_err(|e| format!("Error deserializing accounts file: {}", e))?;
+
+        for account in &store.accounts {
+            if let Some(credentials) = &account.scram_sha_256 {
+                if ScramCredentials::parse(credentials).is_none() {
+                    return Err(format!(
+                        "Invalid SCRAM-SHA-256 credentials for account: {}",
+                        account.name
+                    ));
+                }
+            }
+        }
+
+        Ok(store)
+    }
+
+    fn find(&self, account: &str) -> Option<&Account> {
+        let account = casefold(account);
+        self.accounts
+            .iter()
+            .find(|candidate| casefold(&candidate.name) == account)
+    }
+}
+
+impl AccountStore for TomlAccountStore {
+    fn verify_password(&self, account: &str, password: &str) -> Option<String> {
+        let account = self.find(account)?;
+        let hash = account.password.as_deref()?;
+        if verify_password(password, hash) {
+            Some(account.name.clone())
+        } else {
+            None
+        }
+    }
+
+    fn find_by_certfp(&self, certfp: &str) -> Option<String> {
+        let certfp = certfp.to_ascii_lowercase();
+        self.accounts
+            .iter()
+            .find(|account| account.certfps.contains(&certfp))
+            .map(|account| account.name.clone())
+    }
+
+    fn scram_credentials(&self, account: &str) -> Option<(String, ScramCredentials)> {
+        let account = self.find(account)?;
+        let credentials = ScramCredentials::parse(account.scram_sha_256.as_deref()?)?;
+        Some((account.name.clone(), credentials))
+    }
+}
+
+/// A password a command is waiting on. Checking a hash is slow on purpose, so the connection
+/// checks it once the state has been unlocked, and then lets the command finish.
+#[derive(Debug)]
+pub enum PasswordCheck {
+    Sasl(PlainCredentials),
+    /// OPER, against the hash from the oper block `name`.
+    Oper {
+        name: String,
+        hash: String,
+        password: String,
+    },
+}
+
+/// The outcome of a `PasswordCheck`.
+#[derive(Debug)]
+pub enum Verified {
+    Sasl(Step),
+    Oper { name: String, matched: bool },
+}
+
+impl PasswordCheck {
+    /// Checks the password. SASL fails without an account store, which a rehash can take away
+    /// while the check is waiting.
+    pub fn verify(self, accounts: Option<&dyn AccountStore>) -> Verified {
+        match self {
+            PasswordCheck::Sasl(credentials) => Verified::Sasl(
+                accounts.map_or(Step::Failure, |accounts| credentials.verify(accounts)),
+            ),
+            PasswordCheck::Oper {
+                name,
+                hash,
+                password,
+            } => Verified::Oper {
+                matched: verify_password(&password, &hash),
+                name,
+            },
+        }
+    }
+}
+
+/// Hashes a password with Argon2 and a random salt, for storing in configuration.
+pub fn hash_password(password: &str) -> String {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("hashing with default parameters does not fail")
+        .to_string()
+}
+
+/// Checks a password against a hash produced by `hash_password`. A malformed hash never matches.
+pub fn verify_password(password: &str, hash: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Whether a hash is something `verify_password` could ever accept.
+pub fn is_valid_hash(hash: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| hash.algorithm.as_str().starts_with("argon2"))
+}
@@ -0,0 +1,404 @@
+//! IRCv3 capability negotiation.
+//!
+//! See <https://ircv3.net/specs/extensions/capability-negotiation>.
+
+use std::collections::BTreeSet;
+
+use crate::client::{Client, ClientId};
+use crate::framing::MAX_MESSAGE_LEN;
+use crate::message::Message;
+use crate::reply::Reply;
+use crate::sasl::Mechanism;
+use crate::server::{Server, State};
+
+/// Every capability the server knows how to offer. Features check a client's `CapSet` for these
+/// before deciding what to send it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Capability {
+    CapNotify,
+    Sasl,
+}
+
+impl Capability {
+    pub const ALL: &'static [Capability] = &[Capability::CapNotify, Capability::Sasl];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::CapNotify => "cap-notify",
+            Capability::Sasl => "sasl",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Capability::ALL
+            .iter()
+            .copied()
+            .find(|cap| cap.name() == name)
+    }
+}
+
+/// The capabilities enabled for a single client.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CapSet(BTreeSet<Capability>);
+
+impl CapSet {
+    pub fn contains(&self, cap: Capability) -> bool {
+        self.0.contains(&cap)
+    }
+
+    pub fn insert(&mut self, cap: Capability) {
+        self.0.insert(cap);
+    }
+
+    pub fn remove(&mut self, cap: Capability) {
+        self.0.remove(&cap);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Capability> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+impl Server {
+    /// The capabilities currently offered to clients. SASL needs an account store.
+    pub fn offered_caps(&self) -> Vec<Capability> {
+        Capability::ALL
+            .iter()
+            .copied()
+            .filter(|cap| match cap {
+                Capability::CapNotify => true,
+                Capability::Sasl => self.accounts().is_some(),
+            })
+            .collect()
+    }
+
+    /// The value advertised alongside a capability to clients that asked for `CAP LS 302`.
+    pub fn cap_value(&self, cap: Capability) -> Option<String> {
+        match cap {
+            Capability::CapNotify => None,
+            Capability::Sasl => Some(Mechanism::list()),
+        }
+    }
+
+    /// How a capability is listed in LS and NEW, with its value only for 302 clients.
+    fn advertised_cap(&self, cap: Capability, cap_version: u16) -> String {
+        match self.cap_value(cap) {
+            Some(value) if cap_version >= 302 => format!("{}={}", cap.name(), value),
+            _ => cap.name().to_owned(),
+        }
+    }
+
+    pub(crate) fn handle_cap(
+        &self,
+        state: &mut State,
+        id: ClientId,
+        subcommand: &str,
+        argument: Option<&str>,
+    ) -> Vec<Reply> {
+        match subcommand.to_ascii_uppercase().as_str() {
+            "LS" => self.cap_ls(state, id, argument),
+            "LIST" => {
+                let client = state.client(id);
+                let caps: Vec<&str> = client.caps.iter().map(Capability::name).collect();
+                self.send_cap(client, "LIST", &caps.join(" "));
+                vec![]
+            }
+            "REQ" => self.cap_req(state, id, argument.unwrap_or("")),
+            "END" => {
+                let client = state.client_mut(id);
+                if client.registered || !client.cap_negotiating {
+                    return vec![];
+                }
+                client.cap_negotiating = false;
+
+                // registration can't wait for an unfinished SASL exchange
+                let mut replies = vec![];
+                if client.sasl.take().is_some() {
+                    replies.push(Reply::ERR_SASLABORTED);
+                }
+                replies.extend(self.try_register(client));
+                replies
+            }
+            _ => vec![Reply::ERR_INVALIDCAPCMD {
+                subcommand: subcommand.to_owned(),
+            }],
+        }
+    }
+
+    fn cap_ls(&self, state: &mut State, id: ClientId, version: Option<&str>) -> Vec<Reply> {
+        let client = state.client_mut(id);
+        if !client.registered {
+            client.cap_negotiating = true;
+        }
+
+        // the version only ever goes up, and 302 implies cap-notify
+        let version = version.and_then(|v| v.parse().ok()).unwrap_or(301);
+        if version > client.cap_version {
+            client.cap_version = version;
+        }
+        if client.cap_version >= 302 {
+            client.caps.insert(Capability::CapNotify);
+        }
+
+        let caps: Vec<String> = self
+            .offered_caps()
+            .into_iter()
+            .map(|cap| self.advertised_cap(cap, client.cap_version))
+            .collect();
+
+        let nick = client.display_nick();
+        // lines are split so that `:<server> CAP <nick> LS * :<caps>` stays within the limit
+        let overhead = format!(":{} CAP {} LS * :\r\n", self.config().irc.hostname, nick).len();
+        let mut lines: Vec<String> = vec![];
+        let mut current = String::new();
+        for cap in caps {
+            // only 302 clients understand multi-line replies
+            if !current.is_empty()
+                && client.cap_version >= 302
+                && overhead + current.len() + 1 + cap.len() > MAX_MESSAGE_LEN
+            {
+                lines.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(&cap);
+        }
+
+        for line in &lines {
+            self.send_cap_continued(client, "LS", line);
+        }
+        self.send_cap(client, "LS", &current);
+
+        vec![]
+    }
+
+    /// Enables and disables the requested capabilities all at once, or not at all.
+    fn cap_req(&self, state: &mut State, id: ClientId, requested: &str) -> Vec<Reply> {
+        let client = state.client_mut(id);
+        if !client.registered {
+            client.cap_negotiating = true;
+        }
+
+        let offered = self.offered_caps();
+        let mut changes = vec![];
+        for token in requested.split(' ').filter(|token| !token.is_empty()) {
+            let (enable, name) = match token.strip_prefix('-') {
+                Some(name) => (false, name),
+                None => (true, token),
+            };
+            match Capability::from_name(name).filter(|cap| offered.contains(cap)) {
+                // 302 clients can't turn cap-notify off
+                Some(Capability::CapNotify) if !enable && client.cap_version >= 302 => {
+                    changes.clear();
+                    break;
+                }
+                Some(cap) => changes.push((enable, cap)),
+                None => {
+                    changes.clear();
+                    break;
+                }
+            }
+        }
+
+        if changes.is_empty() {
+            self.send_cap(client, "NAK", requested);
+            return vec![];
+        }
+
+        for (enable, cap) in changes {
+            if enable {
+                client.caps.insert(cap);
+            } else {
+                client.caps.remove(cap);
+            }
+        }
+        self.send_cap(client, "ACK", requested);
+
+        vec![]
+    }
+
+    /// Tells every client with cap-notify that capabilities have been added or removed. Removed
+    /// capabilities are disabled for everyone.
+    pub fn notify_cap_change(&self, state: &mut State, new: &[Capability], del: &[Capability]) {
+        for client in state.clients.values_mut() {
+            let notify = client.caps.contains(Capability::CapNotify);
+            for cap in del {
+                client.caps.remove(*cap);
+            }
+            if !notify {
+                continue;
+            }
+
+            if !new.is_empty() {
+                let caps: Vec<String> = new
+                    .iter()
+                    .map(|cap| self.advertised_cap(*cap, client.cap_version))
+                    .collect();
+                self.send_cap(client, "NEW", &caps.join(" "));
+            }
+            if !del.is_empty() {
+                let names: Vec<&str> = del.iter().map(|cap| cap.name()).collect();
+                self.send_cap(client, "DEL", &names.join(" "));
+            }
+        }
+    }
+
+    fn send_cap(&self, client: &Client, subcommand: &str, caps: &str) {
+        client.send_message(
+            Message::new("CAP")
+                .source(&self.config().irc.hostname)
+                .param(client.display_nick())
+                .param(subcommand)
+                .trailing(caps),
+        );
+    }
+
+    /// Sends a line of a multi-line reply, which is marked with `*` to say that more will follow.
+    fn send_cap_continued(&self, client: &Client, subcommand: &str, caps: &str) {
+        client.send_message(
+            Message::new("CAP")
+                .source(&self.config().irc.hostname)
+                .param(client.display_nick())
+                .param(subcommand)
+                .param("*")
+                .trailing(caps),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{connect, received, register, send, server, server_with};
+
+    #[test]
+    fn negotiation_holds_registration() {
+        let server = server();
+        let (id, mut receiver) = connect(&server);
+
+        send(
+            &server,
+            id,
+            &["CAP LS 302", "NICK Cardinal", "USER cardinal 0 * :Cardinal"],
+        );
+        assert_eq!(
+            received(&mut receiver),
+            vec![":irc.example.com CAP * LS :cap-notify sasl=SCRAM-SHA-256,PLAIN,EXTERNAL\r\n"]
+        );
+        assert!(!server.state().client(id).registered);
+
+        send(&server, id, &["CAP REQ :cap-notify", "CAP END"]);
+        let lines = received(&mut receiver);
+        assert_eq!(
+            lines[0],
+            ":irc.example.com CAP Cardinal ACK :cap-notify\r\n"
+        );
+        assert!(lines[1].starts_with(":irc.example.com 001 Cardinal "));
+        assert!(server.state().client(id).registered);
+    }
+
+    #[test]
+    fn ls_split_over_lines() {
+        // a hostname this long leaves room for only one of the caps on each line
+        let hostname = "a".repeat(460);
+        let server = server_with(|config| config.irc.hostname = hostname.clone());
+        let (id, mut receiver) = connect(&server);
+
+        send(&server, id, &["CAP LS 302"]);
+        let lines = received(&mut receiver);
+        assert_eq!(
+            lines,
+            vec![
+                format!(":{} CAP * LS * :cap-notify\r\n", hostname),
+                format!(
+                    ":{} CAP * LS :sasl=SCRAM-SHA-256,PLAIN,EXTERNAL\r\n",
+                    hostname
+                ),
+            ]
+        );
+        assert!(lines.iter().all(|line| line.len() <= MAX_MESSAGE_LEN));
+    }
+
+    #[test]
+    fn req_is_atomic() {
+        let server = server();
+        let (id, mut receiver) = connect(&server);
+
+        send(&server, id, &["CAP LS", "CAP REQ :cap-notify unknown-cap"]);
+        assert_eq!(
+            received(&mut receiver)[1],
+            ":irc.example.com CAP * NAK :cap-notify unknown-cap\r\n"
+        );
+        assert!(!server
+            .state()
+            .client(id)
+            .caps
+            .contains(Capability::CapNotify));
+
+        send(
+            &server,
+            id,
+            &["CAP REQ cap-notify", "CAP LIST", "CAP REQ -cap-notify"],
+        );
+        assert_eq!(
+            received(&mut receiver),
+            vec![
+                ":irc.example.com CAP * ACK :cap-notify\r\n",
+                ":irc.example.com CAP * LIST :cap-notify\r\n",
+                ":irc.example.com CAP * ACK :-cap-notify\r\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn cap_notify_cannot_be_disabled_with_302() {
+        let server = server();
+        let (id, mut receiver) = connect(&server);
+
+        send(&server, id, &["CAP LS 302", "CAP REQ -cap-notify"]);
+        assert_eq!(
+            received(&mut receiver)[1],
+            ":irc.example.com CAP * NAK :-cap-notify\r\n"
+        );
+    }
+
+    #[test]
+    fn cap_after_registration_and_invalid_subcommand() {
+        let server = server();
+        let (id, mut receiver) = register(&server, "Cardinal");
+
+        send(&server, id, &["CAP LS 302", "CAP FOO"]);
+        assert_eq!(
+            received(&mut receiver),
+            vec![
+                ":irc.example.com CAP Cardinal LS :cap-notify sasl=SCRAM-SHA-256,PLAIN,EXTERNAL\r\n",
+                ":irc.example.com 410 Cardinal FOO :Invalid CAP command\r\n",
+            ]
+        );
+        assert!(!server.state().client(id).cap_negotiating);
+    }
+
+    #[test]
+    fn notify_cap_change() {
+        let server = server();
+        let (id, mut receiver) = register(&server, "Cardinal");
+        let (_, mut other_receiver) = register(&server, "Other");
+        send(&server, id, &["CAP LS 302"]);
+        received(&mut receiver);
+
+        let mut state = server.state();
+        server.notify_cap_change(&mut state, &[Capability::CapNotify], &[]);
+        server.notify_cap_change(&mut state, &[], &[Capability::CapNotify]);
+        drop(state);
+
+        assert_eq!(
+            received(&mut receiver),
+            vec![
+                ":irc.example.com CAP Cardinal NEW :cap-notify\r\n",
+                ":irc.example.com CAP Cardinal DEL :cap-notify\r\n",
+            ]
+        );
+        assert!(received(&mut other_receiver).is_empty());
+    }
+}
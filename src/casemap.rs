@@ -0,0 +1,67 @@
+//! Case-insensitive comparison of nicks and channel names, per the advertised CASEMAPPING.
+
+/// Folds a name using the `rfc1459` casemapping, where `[]\~` are the uppercase forms of `{}|^`.
+///
+/// Examples
+///
+/// ```
+/// use ircd::casemap::casefold;
+///
+/// assert_eq!(casefold("Cardinal[away]"), "cardinal{away}");
+/// assert_eq!(casefold("#Foo\\Bar~"), "#foo|bar^");
+/// ```
+pub fn casefold(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '[' => '{',
+            ']' => '}',
+            '\\' => '|',
+            '~' => '^',
+            c => c.to_ascii_lowercase(),
+        })
+        .collect()
+}
+
+/// Matches a `nick!user@host` against a mask such as a ban, where `*` matches any run of
+/// characters and `?` matches exactly one. Both are casefolded first.
+///
+/// Examples
+///
+/// ```
+/// use ircd::casemap::matches_mask;
+///
+/// assert!(matches_mask("*!*@192.0.2.*", "Cardinal!cardinal@192.0.2.1"));
+/// assert!(matches_mask("cardinal[?]!*@*", "Cardinal{a}!cardinal@localhost"));
+/// assert!(!matches_mask("*!*@192.0.2.?", "Cardinal!cardinal@192.0.2.10"));
+/// ```
+pub fn matches_mask(mask: &str, subject: &str) -> bool {
+    let mask: Vec<char> = casefold(mask).chars().collect();
+    let subject: Vec<char> = casefold(subject).chars().collect();
+
+    // where to resume after the last `*`, should what follows it stop matching
+    let mut backtrack = None;
+    let (mut m, mut s) = (0, 0);
+    while s < subject.len() {
+        match mask.get(m) {
+            Some('*') => {
+                backtrack = Some((m + 1, s));
+                m += 1;
+            }
+            Some(&c) if c == '?' || c == subject[s] => {
+                m += 1;
+                s += 1;
+            }
+            _ => match backtrack {
+                // let the `*` swallow one more character
+                Some((after_star, from)) => {
+                    m = after_star;
+                    s = from + 1;
+                    backtrack = Some((after_star, from + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    mask[m..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,81 @@
+use std::collections::HashSet;
+
+use crate::client::ClientId;
+use crate::isupport::CHANTYPES;
+
+pub struct Topic {
+    pub text: String,
+    pub set_by: String,
+    /// Seconds since the Unix epoch.
+    pub set_at: u64,
+}
+
+pub struct Channel {
+    pub name: String,
+    pub members: HashSet<ClientId>,
+    pub operators: HashSet<ClientId>,
+    pub topic: Option<Topic>,
+}
+
+impl Channel {
+    pub fn new(name: &str) -> Self {
+        Channel {
+            name: name.to_owned(),
+            members: HashSet::new(),
+            operators: HashSet::new(),
+            topic: None,
+        }
+    }
+
+    /// Adds a member, making them an operator if they are the first to join.
+    pub fn join(&mut self, id: ClientId) {
+        if self.members.is_empty() {
+            self.operators.insert(id);
+        }
+        self.members.insert(id);
+    }
+
+    pub fn part(&mut self, id: ClientId) {
+        self.members.remove(&id);
+        self.operators.remove(&id);
+    }
+
+    /// The prefix shown before a member's nick in RPL_NAMREPLY.
+    pub fn member_prefix(&self, id: ClientId) -> &'static str {
+        if self.operators.contains(&id) {
+            "@"
+        } else {
+            ""
+        }
+    }
+}
+
+/// Checks a channel name against RFC 2812, with the allowed prefixes taken from CHANTYPES and the
+/// length limit from configuration.
+///
+/// ```text
+/// channel    = ( "#" / "+" / ( "!" channelid ) / "&" ) chanstring
+/// chanstring = %x01-07 / %x08-09 / %x0B-0C / %x0E-1F / %x21-2B / %x2D-39 / %x3B-FF
+/// ```
+///
+/// Examples
+///
+/// ```
+/// use ircd::channel::is_valid_channel_name;
+///
+/// assert!(is_valid_channel_name("#rust", 50));
+/// assert!(!is_valid_channel_name("rust", 50));
+/// assert!(!is_valid_channel_name("#a,b", 50));
+/// assert!(!is_valid_channel_name("#rust", 4));
+/// ```
+pub fn is_valid_channel_name(name: &str, channellen: usize) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if CHANTYPES.contains(c) => (),
+        _ => return false,
+    }
+
+    name.len() > 1
+        && name.len() <= channellen
+        && chars.all(|c| !matches!(c, '\0' | '\x07' | '\r' | '\n' | ' ' | ',' | ':'))
+}
@@ -0,0 +1,193 @@
+use std::net::SocketAddr;
+
+use bytes::Bytes;
+use log::warn;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::accounts::PasswordCheck;
+use crate::caps::CapSet;
+use crate::message::{Message, MessageBuilder};
+use crate::reply::Reply;
+use crate::sasl;
+
+pub type ClientId = u64;
+
+/// A single connection, as seen by the rest of the server.
+///
+/// A client starts out unregistered. PASS, NICK and USER may arrive in any order, and the client
+/// becomes registered once both a nick and a user have been given, and any capability
+/// negotiation has ended.
+pub struct Client {
+    pub id: ClientId,
+    /// The address the client connected from, which Unix domain socket connections don't have.
+    pub addr: Option<SocketAddr>,
+    pub host: String,
+    pub password: Option<String>,
+    pub nick: Option<String>,
+    pub user: Option<String>,
+    pub realname: Option<String>,
+    pub registered: bool,
+    /// The highest CAP LS version the client has asked for, or 0 if it never sent CAP LS.
+    pub cap_version: u16,
+    pub caps: CapSet,
+    /// Set while capability negotiation holds registration open, until CAP END.
+    pub cap_negotiating: bool,
+    /// The account the client has logged in to with SASL.
+    pub account: Option<String>,
+    /// Whether the client is connected over TLS.
+    pub secure: bool,
+    /// The SHA-256 fingerprint of the client's TLS certificate, as lowercase hex.
+    pub certfp: Option<String>,
+    /// The name of the oper block the client authenticated against with OPER.
+    pub oper: Option<String>,
+    /// The `[[classes]]` block of the listener the client connected to.
+    pub class: Option<String>,
+    /// Set when the client is to be disconnected once the command being handled is done, with
+    /// the reason why.
+    pub closing: Option<String>,
+    /// Set when the command being handled can only finish once a password has been checked,
+    /// which the connection does without holding the state lock.
+    pub password_check: Option<PasswordCheck>,
+    pub sasl: Option<sasl::Session>,
+    /// How many times the client has failed to authenticate with SASL.
+    pub sasl_failures: usize,
+    sender: UnboundedSender<Bytes>,
+}
+
+impl Client {
+    pub fn new(id: ClientId, addr: Option<SocketAddr>, sender: UnboundedSender<Bytes>) -> Self {
+        Client {
+            id,
+            addr,
+            host: addr.map_or_else(|| "localhost".to_owned(), |addr| addr.ip().to_string()),
+            password: None,
+            nick: None,
+            user: None,
+            realname: None,
+            registered: false,
+            cap_version: 0,
+            caps: CapSet::default(),
+            cap_negotiating: false,
+            account: None,
+            secure: false,
+            certfp: None,
+            oper: None,
+            class: None,
+            closing: None,
+            password_check: None,
+            sasl: None,
+            sasl_failures: 0,
+            sender,
+        }
+    }
+
+    /// Queues a line to be written to the client's socket. Passing a `&Message` shares its line
+    /// rather than copying it.
+    ///
+    /// A send only fails once the connection's writer has gone away, in which case the client is
+    /// already being torn down and the line can be dropped.
+    pub fn send(&self, line: impl Into<Bytes>) {
+        let _ = self.sender.send(line.into());
+    }
+
+    /// The nick used as the target of numeric replies, or `*` before one has been set.
+    pub fn display_nick(&self) -> &str {
+        self.nick.as_deref().unwrap_or("*")
+    }
+
+    /// The `nick!user@host` prefix used for messages originating from this client.
+    pub fn prefix(&self) -> String {
+        format!(
+            "{}!{}@{}",
+            self.display_nick(),
+            self.user.as_deref().unwrap_or("*"),
+            self.host
+        )
+    }
+
+    /// Builds and queues a message. One that can't be serialized is logged and dropped rather
+    /// than sent malformed.
+    pub fn send_message(&self, message: MessageBuilder) {
+        match message.build() {
+            Ok(message) => self.send(&message),
+            Err(e) => warn!("Not sending {:?} to client {}: {}", message, self.id, e),
+        }
+    }
+
+    /// Sends an ERROR, which tells the client the server is about to close the connection.
+    pub fn send_error(&self, message: &str) {
+        self.send_message(Message::new("ERROR").trailing(message));
+    }
+
+    /// Sends a numeric reply from `server_name`, addressed to the client's current nick.
+    pub fn reply(&self, server_name: &str, reply: Reply) {
+        match reply.to_message(server_name, self.display_nick()) {
+            Ok(message) => self.send(&message),
+            Err(e) => warn!("Not sending {:?} to client {}: {}", reply, self.id, e),
+        }
+    }
+
+    /// Whether registration can complete, i.e. both NICK and USER have been received and
+    /// capability negotiation isn't in progress.
+    pub fn can_register(&self) -> bool {
+        !self.registered && !self.cap_negotiating && self.nick.is_some() && self.user.is_some()
+    }
+}
+
+/// Checks a nick against the RFC 2812 grammar, with the length limit taken from configuration.
+///
+/// ```text
+/// nickname = ( letter / special ) *8( letter / digit / special / "-" )
+/// special  = %x5B-60 / %x7B-7D ; "[", "]", "\", "`", "_", "^", "{", "|", "}"
+/// ```
+///
+/// Examples
+///
+/// ```
+/// use ircd::client::is_valid_nick;
+///
+/// assert!(is_valid_nick("Cardinal", 30));
+/// assert!(is_valid_nick("[away]`-1", 30));
+/// assert!(!is_valid_nick("1Cardinal", 30));
+/// assert!(!is_valid_nick("Cardinal", 4));
+/// ```
+pub fn is_valid_nick(nick: &str, nicklen: usize) -> bool {
+    fn is_special(c: char) -> bool {
+        matches!(c, '\x5B'..='\x60' | '\x7B'..='\x7D')
+    }
+
+    let mut chars = nick.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || is_special(c) => (),
+        _ => return false,
+    }
+
+    nick.len() <= nicklen && chars.all(|c| c.is_ascii_alphanumeric() || is_special(c) || c == '-')
+}
+
+/// Checks a username against the RFC 2812 grammar. Control characters and `!` are refused as
+/// well, since the username ends up in the `nick!user@host` prefix other clients see.
+///
+/// ```text
+/// user = 1*( %x01-09 / %x0B-0C / %x0E-1F / %x21-3F / %x41-FF )
+///          ; any octet except NUL, CR, LF, " " and "@"
+/// ```
+///
+/// Examples
+///
+/// ```
+/// use ircd::client::is_valid_user;
+///
+/// assert!(is_valid_user("cardinal"));
+/// assert!(is_valid_user("~card.inal"));
+/// assert!(!is_valid_user("a@evil"));
+/// assert!(!is_valid_user("a!b"));
+/// assert!(!is_valid_user("a\tb"));
+/// assert!(!is_valid_user(""));
+/// ```
+pub fn is_valid_user(user: &str) -> bool {
+    !user.is_empty()
+        && user
+            .chars()
+            .all(|c| !c.is_control() && !matches!(c, ' ' | '@' | '!'))
+}
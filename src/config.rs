@@ -1,22 +1,583 @@
 use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
 use std::fs::read_to_string;
+use std::io;
+use std::net::SocketAddr;
 use toml::value::Datetime;
 
+use crate::accounts;
+
+/// The whole configuration file. Every section except `[irc]` can be left out.
 #[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     pub irc: Irc,
+    #[serde(default)]
+    pub limits: Limits,
+    #[serde(default)]
+    pub sasl: Sasl,
+    #[serde(default = "default_listen")]
+    pub listen: Vec<Listen>,
+    #[serde(default)]
+    pub opers: Vec<Oper>,
+    #[serde(default)]
+    pub classes: Vec<Class>,
+    #[serde(default)]
+    pub bans: Vec<Ban>,
+    /// The lines of the file `irc.motd` names, read when the configuration is loaded.
+    #[serde(skip)]
+    pub motd: Option<Vec<String>>,
 }
 
 #[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Irc {
+    /// The server's name, which must be a valid hostname.
     pub hostname: String,
     pub created_at: Datetime,
+    pub network: Option<String>,
+    /// A short description of the server, shown in WHOIS.
+    #[serde(default = "default_description")]
+    pub description: String,
+    /// Path to a text file sent to clients as the message of the day.
+    pub motd: Option<String>,
+}
+
+fn default_description() -> String {
+    "ircd".to_owned()
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    pub nicklen: usize,
+    pub channellen: usize,
+    /// Usernames given with USER are cut down to this many characters.
+    pub userlen: usize,
+    pub maxtargets: usize,
+    /// Seconds of inactivity before the server sends a PING.
+    pub ping_interval: u64,
+    /// Seconds to wait for a reply to that PING before disconnecting.
+    pub ping_timeout: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            nicklen: 30,
+            channellen: 50,
+            userlen: 10,
+            maxtargets: 4,
+            ping_interval: 120,
+            ping_timeout: 60,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Sasl {
+    /// Path to a TOML file of `[[accounts]]`, see `accounts::TomlAccountStore`.
+    pub accounts: Option<String>,
+}
+
+/// A `[[listen]]` block. Each one is either a TCP `address` or a Unix domain socket `path`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Listen {
+    /// An IPv4 or IPv6 address and port, such as `127.0.0.1:6667` or `[::1]:6667`.
+    pub address: Option<String>,
+    /// Where to create a Unix domain socket.
+    pub path: Option<String>,
+    pub tls: Option<Tls>,
+    /// Whether connections start with a PROXY protocol header giving the client's real address.
+    /// Only enable this for listeners that nothing but the proxy can reach.
+    #[serde(default)]
+    pub proxy: bool,
+    /// The `[[classes]]` block that connections to this listener belong to.
+    pub class: Option<String>,
+}
+
+fn default_listen() -> Vec<Listen> {
+    vec![Listen {
+        address: Some("127.0.0.1:6667".to_owned()),
+        path: None,
+        tls: None,
+        proxy: false,
+        class: None,
+    }]
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tls {
+    /// Path to the PEM encoded certificate chain, starting with the server's own certificate.
+    pub certificate: String,
+    /// Path to the PEM encoded private key for the certificate.
+    pub key: String,
+}
+
+/// An `[[opers]]` block, which OPER checks its name and password against.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Oper {
+    pub name: String,
+    /// An Argon2 hash in PHC string format, as produced by `accounts::hash_password`.
+    pub password: Option<String>,
+    /// When set, the client must also be connected with this TLS client certificate fingerprint.
+    pub certfp: Option<String>,
+}
+
+/// A `[[classes]]` block, which sets limits for the connections of the listeners that name it.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Class {
+    pub name: String,
+    /// Replaces `limits.ping_interval` for the class.
+    pub ping_interval: Option<u64>,
+    /// Replaces `limits.ping_timeout` for the class.
+    pub ping_timeout: Option<u64>,
+    /// The most clients the class may hold at once.
+    pub max_clients: Option<usize>,
+    /// The most clients in the class that may connect from a single host.
+    pub max_per_host: Option<usize>,
 }
 
-pub fn get_config(path: &str) -> Result<Config, String> {
-    let toml_config = read_to_string(path).or(Err(format!("Error opening file: {}", path)))?;
-    let config: Config = toml::from_str(&toml_config)
-        .or_else(|e| Err(format!("Error deserializing config file: {}", e)))?;
+/// A `[[bans]]` block, which stops matching clients from registering.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Ban {
+    /// A `nick!user@host` mask, where `*` matches any run of characters and `?` any one.
+    pub mask: String,
+    #[serde(default = "default_ban_reason")]
+    pub reason: String,
+}
+
+fn default_ban_reason() -> String {
+    "Banned".to_owned()
+}
+
+/// Why a configuration couldn't be loaded.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The configuration file couldn't be read.
+    Io { path: String, error: io::Error },
+    /// The file isn't valid TOML, or a value has the wrong type or an unknown key.
+    Syntax {
+        key: String,
+        line: Option<usize>,
+        message: String,
+    },
+    /// Every value is well-formed, but some don't make sense, on their own or together.
+    Invalid {
+        key: String,
+        line: Option<usize>,
+        message: String,
+    },
+}
+
+impl std::error::Error for ConfigError {}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (key, line, message) = match self {
+            ConfigError::Io { path, error } => {
+                return write!(f, "Error opening config file {}: {}", path, error)
+            }
+            ConfigError::Syntax { key, line, message } => (key, line, message),
+            ConfigError::Invalid { key, line, message } => (key, line, message),
+        };
+
+        write!(f, "Error in config")?;
+        if let Some(line) = line {
+            write!(f, " on line {}", line)?;
+        }
+        if !key.is_empty() {
+            write!(f, " at {}", key)?;
+        }
+        write!(f, ": {}", message)
+    }
+}
+
+impl Config {
+    /// Parses and validates a configuration. Files it refers to, such as the MOTD, aren't read.
+    ///
+    /// Examples
+    ///
+    /// ```
+    /// use ircd::config::{Config, ConfigError};
+    ///
+    /// let config = Config::from_toml(r#"
+    ///     [irc]
+    ///     hostname = "irc.example.com"
+    ///     created_at = 2020-01-20T12:27:00-04:00
+    /// "#).unwrap();
+    /// assert_eq!(config.limits.nicklen, 30);
+    ///
+    /// let error = Config::from_toml(r#"
+    ///     [irc]
+    ///     hostname = "irc example com"
+    ///     created_at = 2020-01-20T12:27:00-04:00
+    /// "#).err().unwrap();
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     "Error in config on line 3 at irc.hostname: not a valid hostname"
+    /// );
+    /// ```
+    pub fn from_toml(toml: &str) -> Result<Self, ConfigError> {
+        let deserializer = toml::Deserializer::new(toml);
+        let config: Config = serde_path_to_error::deserialize(deserializer).map_err(|e| {
+            let key = match e.path().to_string().as_str() {
+                "." => String::new(),
+                key => key.to_owned(),
+            };
+            let line = e
+                .inner()
+                .span()
+                .map(|span| line_at(toml, span.start))
+                .or_else(|| find_line(toml, &key));
+            ConfigError::Syntax {
+                key,
+                line,
+                message: e.inner().message().to_owned(),
+            }
+        })?;
+
+        config
+            .validate()
+            .map_err(|(key, message)| ConfigError::Invalid {
+                line: find_line(toml, &key),
+                key,
+                message,
+            })?;
+
+        Ok(config)
+    }
+
+    /// Checks the values serde can't, returning the key path of the first problem found.
+    fn validate(&self) -> Result<(), (String, String)> {
+        fn invalid(key: impl Into<String>, message: impl Into<String>) -> (String, String) {
+            (key.into(), message.into())
+        }
+
+        if !is_valid_hostname(&self.irc.hostname) {
+            return Err(invalid("irc.hostname", "not a valid hostname"));
+        }
+        if let Some(network) = &self.irc.network {
+            if network.is_empty() || network.contains(' ') {
+                return Err(invalid("irc.network", "must be a single word"));
+            }
+        }
+
+        let limits = &self.limits;
+        for (key, value, minimum) in [
+            ("nicklen", limits.nicklen, 1),
+            ("channellen", limits.channellen, 2),
+            ("userlen", limits.userlen, 1),
+            ("maxtargets", limits.maxtargets, 1),
+            ("ping_interval", limits.ping_interval as usize, 1),
+            ("ping_timeout", limits.ping_timeout as usize, 1),
+        ] {
+            if value < minimum {
+                return Err(invalid(
+                    format!("limits.{}", key),
+                    format!("must be at least {}", minimum),
+                ));
+            }
+        }
+
+        let mut classes = HashSet::new();
+        for (i, class) in self.classes.iter().enumerate() {
+            if !classes.insert(class.name.as_str()) {
+                return Err(invalid(
+                    format!("classes[{}].name", i),
+                    format!("class {} is defined more than once", class.name),
+                ));
+            }
+            for (key, value) in [
+                ("ping_interval", class.ping_interval),
+                ("ping_timeout", class.ping_timeout),
+            ] {
+                if value == Some(0) {
+                    return Err(invalid(
+                        format!("classes[{}].{}", i, key),
+                        "must be at least 1",
+                    ));
+                }
+            }
+        }
+
+        if self.listen.is_empty() {
+            return Err(invalid("listen", "at least one listener is needed"));
+        }
+        let mut listeners = HashSet::new();
+        for (i, listen) in self.listen.iter().enumerate() {
+            let (key, value) = match (&listen.address, &listen.path) {
+                (Some(address), None) => {
+                    if let Err(e) = address.parse::<SocketAddr>() {
+                        return Err(invalid(format!("listen[{}].address", i), e.to_string()));
+                    }
+                    ("address", address)
+                }
+                (None, Some(path)) => ("path", path),
+                _ => {
+                    return Err(invalid(
+                        format!("listen[{}]", i),
+                        "needs exactly one of address or path",
+                    ))
+                }
+            };
+            if !listeners.insert(value.as_str()) {
+                return Err(invalid(
+                    format!("listen[{}].{}", i, key),
+                    format!("{} is listened on more than once", value),
+                ));
+            }
+            if let Some(class) = &listen.class {
+                if !classes.contains(class.as_str()) {
+                    return Err(invalid(
+                        format!("listen[{}].class", i),
+                        format!("there is no class named {}", class),
+                    ));
+                }
+            }
+        }
+
+        let mut opers = HashSet::new();
+        for (i, oper) in self.opers.iter().enumerate() {
+            if oper.name.is_empty() || oper.name.contains(' ') {
+                return Err(invalid(
+                    format!("opers[{}].name", i),
+                    "must be a single word",
+                ));
+            }
+            if !opers.insert(oper.name.as_str()) {
+                return Err(invalid(
+                    format!("opers[{}].name", i),
+                    format!("oper {} is defined more than once", oper.name),
+                ));
+            }
+            if oper.password.is_none() && oper.certfp.is_none() {
+                return Err(invalid(
+                    format!("opers[{}]", i),
+                    "needs a password, a certfp or both",
+                ));
+            }
+            if let Some(password) = &oper.password {
+                if !accounts::is_valid_hash(password) {
+                    return Err(invalid(
+                        format!("opers[{}].password", i),
+                        "not an Argon2 hash in PHC format",
+                    ));
+                }
+            }
+            if let Some(certfp) = &oper.certfp {
+                if certfp.len() != 64 || !certfp.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(invalid(
+                        format!("opers[{}].certfp", i),
+                        "not a hex SHA-256 fingerprint",
+                    ));
+                }
+            }
+        }
+
+        for (i, ban) in self.bans.iter().enumerate() {
+            let valid = match ban.mask.split_once('!') {
+                Some((nick, user_host)) => !nick.is_empty() && user_host.contains('@'),
+                None => false,
+            };
+            if !valid {
+                return Err(invalid(
+                    format!("bans[{}].mask", i),
+                    "must look like nick!user@host",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The class a listener put a client in, if any.
+    pub fn class(&self, name: Option<&str>) -> Option<&Class> {
+        let name = name?;
+        self.classes.iter().find(|class| class.name == name)
+    }
+}
+
+/// Checks a server name, which follows the same rules as a hostname but is limited to 63
+/// characters.
+///
+/// Examples
+///
+/// ```
+/// use ircd::config::is_valid_hostname;
+///
+/// assert!(is_valid_hostname("irc.example.com"));
+/// assert!(is_valid_hostname("localhost"));
+/// assert!(!is_valid_hostname("irc..example.com"));
+/// assert!(!is_valid_hostname("-irc.example.com"));
+/// assert!(!is_valid_hostname("irc_example.com"));
+/// ```
+pub fn is_valid_hostname(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 63
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+/// The 1-based line a byte offset falls on.
+fn line_at(toml: &str, offset: usize) -> usize {
+    toml[..offset.min(toml.len())].matches('\n').count() + 1
+}
+
+/// Finds the line a key path such as `listen[1].address` is defined on. A key that was left out
+/// is reported at the closest table that is there.
+fn find_line(toml: &str, key: &str) -> Option<usize> {
+    let document = toml_edit::ImDocument::parse(toml).ok()?;
+    let mut item = document.as_item();
+    let mut span = None;
+    for segment in key.split('.').filter(|segment| !segment.is_empty()) {
+        let (name, index) = match segment.split_once('[') {
+            Some((name, index)) => (name, index.trim_end_matches(']').parse::<usize>().ok()),
+            None => (segment, None),
+        };
+        item = match item.get(name) {
+            Some(item) => item,
+            None => break,
+        };
+        span = item.span().or(span);
+        if let Some(index) = index {
+            item = match item.get(index) {
+                Some(item) => item,
+                None => break,
+            };
+            span = item.span().or(span);
+        }
+    }
+
+    span.map(|span| line_at(toml, span.start))
+}
+
+/// Reads, parses and validates the configuration file, along with the MOTD it names.
+pub fn get_config(path: &str) -> Result<Config, ConfigError> {
+    let toml_config = read_to_string(path).map_err(|error| ConfigError::Io {
+        path: path.to_owned(),
+        error,
+    })?;
+    let mut config = Config::from_toml(&toml_config)?;
+
+    if let Some(motd) = &config.irc.motd {
+        let text = read_to_string(motd).map_err(|e| ConfigError::Invalid {
+            key: "irc.motd".to_owned(),
+            line: find_line(&toml_config, "irc.motd"),
+            message: format!("can't read {}: {}", motd, e),
+        })?;
+        config.motd = Some(text.lines().map(str::to_owned).collect());
+    }
 
     Ok(config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(toml: &str) -> String {
+        let toml = format!(
+            "[irc]\nhostname = \"irc.example.com\"\ncreated_at = 2020-01-20T12:27:00-04:00\n{}",
+            toml
+        );
+        Config::from_toml(&toml).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(
+            error("[limits]\nnicklen = \"thirty\"\n"),
+            "Error in config on line 5 at limits.nicklen: invalid type: string \"thirty\", expected usize"
+        );
+        assert!(error("[limits]\nnicklength = 30\n").starts_with(
+            "Error in config on line 5 at limits.nicklength: unknown field `nicklength`"
+        ));
+        assert!(error("[[listen]]\nadress = \"127.0.0.1:6667\"\n")
+            .starts_with("Error in config on line 5 at listen[0].adress: unknown field `adress`"));
+        assert!(error("[irc\n").starts_with("Error in config on line 4: "));
+        assert_eq!(
+            Config::from_toml("").err().unwrap().to_string(),
+            "Error in config on line 1: missing field `irc`"
+        );
+    }
+
+    #[test]
+    fn validation_errors() {
+        assert_eq!(
+            error("[limits]\nping_timeout = 0\n"),
+            "Error in config on line 5 at limits.ping_timeout: must be at least 1"
+        );
+        assert_eq!(
+            error("[[listen]]\naddress = \"127.0.0.1:6667\"\n[[listen]]\npath = \"a\"\nclass = \"users\"\n"),
+            "Error in config on line 8 at listen[1].class: there is no class named users"
+        );
+        assert_eq!(
+            error("[[listen]]\naddress = \"127.0.0.1:6667\"\naddress6 = \"\"\n"),
+            "Error in config on line 6 at listen[0].address6: unknown field `address6`, expected one of `address`, `path`, `tls`, `proxy`, `class`"
+        );
+        assert_eq!(
+            error("[[listen]]\nproxy = true\n"),
+            "Error in config on line 4 at listen[0]: needs exactly one of address or path"
+        );
+        assert_eq!(
+            Config::from_toml("listen = []\n[irc]\nhostname = \"irc.example.com\"\ncreated_at = 2020-01-20T12:27:00-04:00\n")
+                .err()
+                .unwrap()
+                .to_string(),
+            "Error in config on line 1 at listen: at least one listener is needed"
+        );
+        assert_eq!(
+            error("[[opers]]\nname = \"admin\"\npassword = \"hunter2\"\n"),
+            "Error in config on line 6 at opers[0].password: not an Argon2 hash in PHC format"
+        );
+        assert_eq!(
+            error("[[opers]]\nname = \"admin\"\n"),
+            "Error in config on line 4 at opers[0]: needs a password, a certfp or both"
+        );
+        assert_eq!(
+            error("[[classes]]\nname = \"a\"\n[[classes]]\nname = \"a\"\n"),
+            "Error in config on line 7 at classes[1].name: class a is defined more than once"
+        );
+        assert_eq!(
+            error("[[bans]]\nmask = \"192.0.2.1\"\n"),
+            "Error in config on line 5 at bans[0].mask: must look like nick!user@host"
+        );
+    }
+
+    #[test]
+    fn example_is_valid() {
+        let config = Config::from_toml(include_str!("../config.toml.example")).unwrap();
+        assert_eq!(config.listen.len(), 4);
+        assert_eq!(
+            config
+                .class(config.listen[0].class.as_deref())
+                .unwrap()
+                .name,
+            "users"
+        );
+    }
+
+    #[test]
+    fn defaults() {
+        let config = Config::from_toml(
+            "[irc]\nhostname = \"irc.example.com\"\ncreated_at = 2020-01-20T12:27:00-04:00\n",
+        )
+        .unwrap();
+        assert_eq!(config.irc.description, "ircd");
+        assert_eq!(config.listen.len(), 1);
+        assert_eq!(config.listen[0].address.as_deref(), Some("127.0.0.1:6667"));
+        assert!(config.opers.is_empty() && config.classes.is_empty() && config.bans.is_empty());
+    }
+}
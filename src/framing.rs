@@ -0,0 +1,216 @@
+//! Splits the byte stream from a client into lines, enforcing the message size limits.
+
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// RFC 2812 section 2.3: a message is at most 512 bytes, including the trailing CR-LF.
+pub const MAX_MESSAGE_LEN: usize = 512;
+
+/// IRCv3 message-tags: the tags section, including the leading `@` and trailing space, has a
+/// budget of its own on top of `MAX_MESSAGE_LEN`.
+pub const MAX_TAGS_LEN: usize = 8191;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Frame {
+    /// A complete line, without its terminator.
+    Line(Vec<u8>),
+    /// A line exceeded the size limits and was discarded.
+    TooLong,
+}
+
+/// Reads lines terminated by CR-LF, a bare LF or a bare CR.
+///
+/// At most one maximum-length line is ever buffered. A line that runs past the limit is reported
+/// once as `Frame::TooLong`, and the rest of it is dropped as it arrives.
+pub struct LineReader<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    /// Where the unread part of `buffer` starts. Lines are consumed by moving this along, and the
+    /// buffer is only compacted once per read.
+    start: usize,
+    /// Set after a CR terminator, so that the LF of a CR-LF pair isn't read as an empty line.
+    skip_lf: bool,
+    /// Set while dropping the remainder of a line that has already been reported as too long.
+    discarding: bool,
+}
+
+impl<R: AsyncRead + Unpin> LineReader<R> {
+    pub fn new(reader: R) -> Self {
+        LineReader {
+            reader,
+            buffer: vec![],
+            start: 0,
+            skip_lf: false,
+            discarding: false,
+        }
+    }
+
+    /// Returns the next frame, or `None` once the stream has ended.
+    ///
+    /// This is cancel safe: bytes read before the future is dropped stay buffered for the next
+    /// call.
+    pub async fn next_frame(&mut self) -> io::Result<Option<Frame>> {
+        let mut chunk = [0; 4096];
+        loop {
+            if let Some(frame) = self.take_frame() {
+                return Ok(Some(frame));
+            }
+
+            let read = self.reader.read(&mut chunk).await?;
+            if read == 0 {
+                return Ok(None);
+            }
+            self.buffer.drain(..self.start);
+            self.start = 0;
+            self.buffer.extend_from_slice(&chunk[..read]);
+        }
+    }
+
+    fn take_frame(&mut self) -> Option<Frame> {
+        loop {
+            let unread = &self.buffer[self.start..];
+            if self.skip_lf {
+                match unread.first() {
+                    None => return None,
+                    Some(b'\n') => self.start += 1,
+                    Some(_) => (),
+                }
+                self.skip_lf = false;
+                continue;
+            }
+
+            let end = match unread.iter().position(|b| *b == b'\r' || *b == b'\n') {
+                Some(end) => self.start + end,
+                None => {
+                    if unread.len() >= MAX_TAGS_LEN + MAX_MESSAGE_LEN {
+                        self.buffer.clear();
+                        self.start = 0;
+                        if !self.discarding {
+                            self.discarding = true;
+                            return Some(Frame::TooLong);
+                        }
+                    }
+                    return None;
+                }
+            };
+
+            let line = self.buffer[self.start..end].to_vec();
+            self.skip_lf = self.buffer[end] == b'\r';
+            self.start = end + 1;
+
+            if self.discarding {
+                self.discarding = false;
+                continue;
+            }
+            if is_too_long(&line) {
+                return Some(Frame::TooLong);
+            }
+            return Some(Frame::Line(line));
+        }
+    }
+}
+
+/// Checks a line, without its terminator, against the size limits.
+fn is_too_long(line: &[u8]) -> bool {
+    // the terminator counts against the limit, even if the client only sent one byte of it
+    let (tags, message) = match line.first() {
+        Some(b'@') => {
+            let tags_end = line
+                .iter()
+                .position(|b| *b == b' ')
+                .map_or(line.len(), |i| i + 1);
+            line.split_at(tags_end)
+        }
+        _ => (&line[..0], line),
+    };
+
+    tags.len() > MAX_TAGS_LEN || message.len() + 2 > MAX_MESSAGE_LEN
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn frames(input: &[u8]) -> Vec<Frame> {
+        let mut reader = LineReader::new(input);
+        let mut frames = vec![];
+        while let Some(frame) = reader.next_frame().await.unwrap() {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    fn line(s: &str) -> Frame {
+        Frame::Line(s.as_bytes().to_vec())
+    }
+
+    #[tokio::test]
+    async fn terminators() {
+        assert_eq!(
+            frames(b"PING a\r\nPING b\nPING c\rPING d\r\n\r\n").await,
+            vec![
+                line("PING a"),
+                line("PING b"),
+                line("PING c"),
+                line("PING d"),
+                line("")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn unterminated_line_dropped_at_eof() {
+        assert_eq!(frames(b"PING a\r\nPING b").await, vec![line("PING a")]);
+    }
+
+    #[tokio::test]
+    async fn message_length_limit() {
+        let longest = format!("PRIVMSG #a :{}", "a".repeat(MAX_MESSAGE_LEN - 14));
+        let input = format!("{}\r\n{}a\r\nPING\r\n", longest, longest);
+
+        assert_eq!(
+            frames(input.as_bytes()).await,
+            vec![line(&longest), Frame::TooLong, line("PING")]
+        );
+    }
+
+    #[tokio::test]
+    async fn tags_have_their_own_budget() {
+        let tags = format!("@a={}", "b".repeat(MAX_TAGS_LEN - 4));
+        let message = format!("PRIVMSG #a :{}", "a".repeat(MAX_MESSAGE_LEN - 14));
+        let input = format!("{} {}\r\n{}b {}\r\n", tags, message, tags, message);
+
+        assert_eq!(
+            frames(input.as_bytes()).await,
+            vec![line(&format!("{} {}", tags, message)), Frame::TooLong]
+        );
+    }
+
+    #[tokio::test]
+    async fn unterminated_line_is_not_buffered() {
+        let mut input = vec![b'a'; 100_000];
+        input.extend_from_slice(b"\r\nPING\r\n");
+        let mut reader = LineReader::new(&input[..]);
+
+        assert_eq!(reader.next_frame().await.unwrap(), Some(Frame::TooLong));
+        assert!(reader.buffer.len() < MAX_TAGS_LEN + MAX_MESSAGE_LEN);
+        assert_eq!(reader.next_frame().await.unwrap(), Some(line("PING")));
+        assert_eq!(reader.next_frame().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn buffer_compacted_once_per_read() {
+        let first = "PING a\r\n".repeat(100);
+        let mut reader = LineReader::new(first.as_bytes().chain(&b"PING b\r\n"[..]));
+
+        for _ in 0..100 {
+            assert_eq!(reader.next_frame().await.unwrap(), Some(line("PING a")));
+            assert_eq!(reader.buffer.len(), first.len());
+        }
+
+        // everything read so far is dropped in one go before the next read
+        assert_eq!(reader.next_frame().await.unwrap(), Some(line("PING b")));
+        assert_eq!(reader.buffer, b"PING b\r\n");
+    }
+}
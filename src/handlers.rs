@@ -0,0 +1,1592 @@
+use std::convert::TryFrom;
+
+use log::{debug, warn};
+
+use crate::accounts::{PasswordCheck, Verified};
+use crate::casemap::{casefold, matches_mask};
+use crate::channel::{is_valid_channel_name, Channel};
+use crate::client::{is_valid_nick, is_valid_user, Client, ClientId};
+use crate::isupport;
+use crate::message::Message;
+use crate::reply::Reply;
+use crate::sasl;
+use crate::server::{Server, State, VERSION};
+use crate::structs::{Command, IrcMessage, ModeChange, ParseError};
+
+impl Server {
+    /// Handles a single line from a client. Bad input only ever affects the client that sent it,
+    /// so nothing here is fatal to the connection.
+    ///
+    /// A command that needs a password checked hands it back, to be passed to
+    /// `finish_password_check` once it has been verified.
+    pub(crate) fn handle_line(&self, id: ClientId, line: &str) -> Option<PasswordCheck> {
+        // RFC 2812 section 2.3.1: empty messages are silently ignored
+        if line.trim().is_empty() {
+            return None;
+        }
+
+        // translate to internal irc message struct
+        let irc_message = match IrcMessage::try_from(line) {
+            Ok(irc_message) => irc_message,
+            Err(error) => {
+                debug!("{:?} -> {}", line, error);
+                return None;
+            }
+        };
+
+        let mut state = self.state();
+        let registered = state.clients.get(&id)?.registered;
+
+        // decide whether to generate a reply
+        let replies = match irc_message.to_command() {
+            Ok(command) => {
+                debug!("{:?} -> {:?}", irc_message, command);
+                self.handle_command(&mut state, id, command)
+            }
+            Err(error) => {
+                debug!("{:?} -> {:?}", irc_message, error);
+                parse_error_replies(error, registered)
+            }
+        };
+
+        self.finish_command(&mut state, id, replies)
+    }
+
+    /// Lets the command that was waiting on a password check finish.
+    pub(crate) fn finish_password_check(
+        &self,
+        id: ClientId,
+        verified: Verified,
+    ) -> Option<PasswordCheck> {
+        let mut state = self.state();
+        let client = state.clients.get_mut(&id)?;
+        let replies = match verified {
+            Verified::Sasl(step) => sasl::finish(client, step),
+            Verified::Oper { name, matched } => finish_oper(client, name, matched),
+        };
+
+        self.finish_command(&mut state, id, replies)
+    }
+
+    /// Sends the replies to a command, then disconnects the client if the command called for it,
+    /// or hands back the password the command is waiting on.
+    fn finish_command(
+        &self,
+        state: &mut State,
+        id: ClientId,
+        replies: Vec<Reply>,
+    ) -> Option<PasswordCheck> {
+        let client = state.clients.get_mut(&id)?;
+        let config = self.config();
+        for reply in replies {
+            client.reply(&config.irc.hostname, reply);
+        }
+        if let Some(reason) = client.closing.clone() {
+            state.quit(id, &reason);
+            return None;
+        }
+
+        client.password_check.take()
+    }
+
+    fn handle_command(&self, state: &mut State, id: ClientId, command: Command) -> Vec<Reply> {
+        if !state.client(id).registered && !allowed_before_registration(&command) {
+            return vec![Reply::ERR_NOTREGISTERED];
+        }
+
+        match command {
+            Command::CAP(subcommand, argument) => self.handle_cap(state, id, subcommand, argument),
+            Command::AUTHENTICATE(argument) => self.handle_authenticate(state, id, argument),
+            Command::PASS(password) => self.handle_pass(state, id, password),
+            Command::NICK(nick) => self.handle_nick(state, id, nick),
+            Command::USER(user, _mode, _unused, realname) => {
+                self.handle_user(state, id, user, realname)
+            }
+            Command::JOIN(channels, keys) => self.handle_join(state, id, &channels, &keys),
+            Command::PART(channels, message) => self.handle_part(state, id, &channels, message),
+            Command::PING(token, _) => {
+                let config = self.config();
+                state.client(id).send_message(
+                    Message::new("PONG")
+                        .source(&config.irc.hostname)
+                        .param(&config.irc.hostname)
+                        .trailing(token),
+                );
+                vec![]
+            }
+            // receiving anything at all resets the ping timer, so there's nothing left to do
+            Command::PONG(_, _) => vec![],
+            Command::QUIT(message) => {
+                let reason = match message {
+                    Some(message) => format!("Quit: {}", message),
+                    None => "Quit".to_owned(),
+                };
+                state.quit(id, &reason);
+                vec![]
+            }
+            Command::PRIVMSG(targets, text) => {
+                self.handle_message(state, id, "PRIVMSG", &targets, text)
+            }
+            Command::NOTICE(targets, text) => {
+                self.handle_message(state, id, "NOTICE", &targets, text)
+            }
+            Command::MODE(target, changes, arguments) => {
+                if target.starts_with(|c| isupport::CHANTYPES.contains(c)) {
+                    self.handle_channel_mode(state, id, target, &changes, &arguments)
+                } else {
+                    handle_user_mode(state.client_mut(id), target, &changes)
+                }
+            }
+            Command::OPER(name, password) => self.handle_oper(state, id, name, password),
+            Command::REHASH => {
+                if state.client(id).oper.is_none() {
+                    return vec![Reply::ERR_NOPRIVILEGES];
+                }
+                // the outcome follows as a NOTICE once the new configuration has been loaded
+                self.request_rehash(Some(id));
+                vec![Reply::RPL_REHASHING {
+                    file: self.config_path().unwrap_or("*").to_owned(),
+                }]
+            }
+            // there's only the one server to ask
+            Command::WHOIS(_, nicks) => nicks
+                .iter()
+                .flat_map(|nick| self.handle_whois(state, id, nick))
+                .collect(),
+            Command::MOTD(_) => self.motd(),
+            command => vec![Reply::ERR_UNKNOWNCOMMAND {
+                command: command.name().to_owned(),
+            }],
+        }
+    }
+
+    fn handle_pass(&self, state: &mut State, id: ClientId, password: &str) -> Vec<Reply> {
+        let client = state.client_mut(id);
+        if client.registered {
+            return vec![Reply::ERR_ALREADYREGISTRED];
+        }
+        client.password = Some(password.to_owned());
+
+        vec![]
+    }
+
+    fn handle_nick(&self, state: &mut State, id: ClientId, nick: &str) -> Vec<Reply> {
+        if nick.is_empty() {
+            return vec![Reply::ERR_NONICKNAMEGIVEN];
+        }
+        if !is_valid_nick(nick, self.config().limits.nicklen) {
+            return vec![Reply::ERR_ERRONEUSNICKNAME {
+                new_nick: nick.to_owned(),
+            }];
+        }
+        match state.find_nick(nick) {
+            // changing the case of your own nick is allowed
+            Some(owner) if owner != id => {
+                return vec![Reply::ERR_NICKNAMEINUSE {
+                    new_nick: nick.to_owned(),
+                }]
+            }
+            _ => (),
+        }
+
+        let client = state.client_mut(id);
+        if client.nick.as_deref() == Some(nick) {
+            return vec![];
+        }
+        let old_prefix = client.prefix();
+        let old_nick = client.nick.replace(nick.to_owned());
+
+        if let Some(old_nick) = old_nick {
+            state.nicks.remove(&casefold(&old_nick));
+        }
+        state.nicks.insert(casefold(nick), id);
+
+        if !state.client(id).registered {
+            return self.try_register(state.client_mut(id));
+        }
+
+        let message = Message::new("NICK").source(old_prefix).param(nick).build();
+        let message = match message {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("Not announcing a nick change to {}: {}", nick, e);
+                return vec![];
+            }
+        };
+        state.client(id).send(&message);
+        for neighbour in state.neighbours(id) {
+            state.client(neighbour).send(&message);
+        }
+
+        vec![]
+    }
+
+    fn handle_user(
+        &self,
+        state: &mut State,
+        id: ClientId,
+        user: &str,
+        realname: &str,
+    ) -> Vec<Reply> {
+        let client = state.client_mut(id);
+        if client.registered {
+            return vec![Reply::ERR_ALREADYREGISTRED];
+        }
+        if !is_valid_user(user) {
+            return vec![Reply::ERR_NEEDMOREPARAMS {
+                command: "USER".to_owned(),
+            }];
+        }
+        let userlen = self.config().limits.userlen;
+        client.user = Some(user.chars().take(userlen).collect());
+        client.realname = Some(realname.to_owned());
+
+        self.try_register(client)
+    }
+
+    fn handle_join(
+        &self,
+        state: &mut State,
+        id: ClientId,
+        channels: &[&str],
+        _keys: &[&str],
+    ) -> Vec<Reply> {
+        // "JOIN 0" leaves every channel the client is in
+        if channels == ["0"] {
+            let joined: Vec<String> = state
+                .channels
+                .values()
+                .filter(|channel| channel.members.contains(&id))
+                .map(|channel| channel.name.clone())
+                .collect();
+            for name in joined {
+                self.part(state, id, &name, None);
+            }
+            return vec![];
+        }
+
+        // replies are sent as we go so that each channel's burst directly follows its JOIN
+        for &name in channels {
+            if !is_valid_channel_name(name, self.config().limits.channellen) {
+                state.client(id).reply(
+                    &self.config().irc.hostname,
+                    Reply::ERR_NOSUCHCHANNEL {
+                        channel: name.to_owned(),
+                    },
+                );
+                continue;
+            }
+
+            let channel = state
+                .channels
+                .entry(casefold(name))
+                .or_insert_with(|| Channel::new(name));
+            if channel.members.contains(&id) {
+                continue;
+            }
+            channel.join(id);
+            let name = channel.name.clone();
+
+            let client = state.client(id);
+            let message = Message::new("JOIN")
+                .source(client.prefix())
+                .param(&name)
+                .build();
+            match message {
+                Ok(message) => state.send_to_channel(&name, &message, None),
+                Err(e) => warn!("Not announcing a join to {}: {}", name, e),
+            }
+
+            for reply in self.join_burst(state, id, &name) {
+                client.reply(&self.config().irc.hostname, reply);
+            }
+        }
+
+        vec![]
+    }
+
+    /// The topic and names list sent to a client after joining a channel.
+    fn join_burst(&self, state: &State, id: ClientId, name: &str) -> Vec<Reply> {
+        let channel = match state.find_channel(name) {
+            Some(channel) => channel,
+            None => return vec![],
+        };
+        let nick = state.client(id).display_nick();
+
+        let mut replies = vec![];
+        if let Some(topic) = &channel.topic {
+            replies.push(Reply::RPL_TOPIC {
+                channel: channel.name.clone(),
+                topic: topic.text.clone(),
+            });
+            replies.push(Reply::RPL_TOPICWHOTIME {
+                channel: channel.name.clone(),
+                set_by: topic.set_by.clone(),
+                set_at: topic.set_at,
+            });
+        }
+
+        // split the names over as many replies as it takes to keep each line within 512 bytes
+        let overhead = format!(
+            ":{} 353 {} = {} :\r\n",
+            self.config().irc.hostname,
+            nick,
+            channel.name
+        )
+        .len();
+        let mut names: Vec<String> = vec![];
+        let mut length = overhead;
+        for member in &channel.members {
+            let name = format!(
+                "{}{}",
+                channel.member_prefix(*member),
+                state.client(*member).display_nick()
+            );
+            if !names.is_empty() && length + 1 + name.len() > 512 {
+                replies.push(Reply::RPL_NAMREPLY {
+                    channel: channel.name.clone(),
+                    names: std::mem::take(&mut names),
+                });
+                length = overhead;
+            }
+            length += name.len() + 1;
+            names.push(name);
+        }
+        if !names.is_empty() {
+            replies.push(Reply::RPL_NAMREPLY {
+                channel: channel.name.clone(),
+                names,
+            });
+        }
+        replies.push(Reply::RPL_ENDOFNAMES {
+            channel: channel.name.clone(),
+        });
+
+        replies
+    }
+
+    fn handle_part(
+        &self,
+        state: &mut State,
+        id: ClientId,
+        channels: &[&str],
+        message: Option<&str>,
+    ) -> Vec<Reply> {
+        let mut replies = vec![];
+        for &name in channels {
+            match state.find_channel(name) {
+                None => replies.push(Reply::ERR_NOSUCHCHANNEL {
+                    channel: name.to_owned(),
+                }),
+                Some(channel) if !channel.members.contains(&id) => {
+                    replies.push(Reply::ERR_NOTONCHANNEL {
+                        channel: name.to_owned(),
+                    })
+                }
+                Some(_) => self.part(state, id, name, message),
+            }
+        }
+
+        replies
+    }
+
+    /// Announces a member leaving a channel to everyone in it, then removes them.
+    fn part(&self, state: &mut State, id: ClientId, name: &str, message: Option<&str>) {
+        let channel_name = match state.find_channel(name) {
+            Some(channel) => channel.name.clone(),
+            None => return,
+        };
+
+        let mut builder = Message::new("PART")
+            .source(state.client(id).prefix())
+            .param(&channel_name);
+        if let Some(message) = message {
+            builder = builder.param(message);
+        }
+        let message = builder.build();
+        match message {
+            Ok(message) => state.send_to_channel(name, &message, None),
+            Err(e) => warn!("Not announcing a part from {}: {}", channel_name, e),
+        }
+
+        state.leave_channel(id, name);
+    }
+
+    /// Delivers a PRIVMSG or NOTICE to each of the comma-separated targets, up to MAXTARGETS of
+    /// them. NOTICE never generates an error reply, so that automated clients can't be made to
+    /// loop.
+    fn handle_message(
+        &self,
+        state: &mut State,
+        id: ClientId,
+        command: &str,
+        targets: &[&str],
+        text: &str,
+    ) -> Vec<Reply> {
+        let is_notice = command == "NOTICE";
+        if text.is_empty() {
+            return if is_notice {
+                vec![]
+            } else {
+                vec![Reply::ERR_NOTEXTTOSEND]
+            };
+        }
+
+        let maxtargets = self.config().limits.maxtargets;
+        let (targets, skipped) = targets.split_at(targets.len().min(maxtargets));
+
+        let prefix = state.client(id).prefix();
+        let mut replies = vec![];
+        for &target in targets {
+            let message = Message::new(command)
+                .source(&prefix)
+                .param(target)
+                .trailing(text)
+                .build();
+            let message = match message {
+                Ok(message) => message,
+                Err(e) => {
+                    warn!("Not delivering a {} to {}: {}", command, target, e);
+                    continue;
+                }
+            };
+
+            if target.starts_with(|c| isupport::CHANTYPES.contains(c)) {
+                match state.find_channel(target) {
+                    None => replies.push(Reply::ERR_NOSUCHNICK {
+                        target: target.to_owned(),
+                    }),
+                    // channels behave as if +n is always set
+                    Some(channel) if !channel.members.contains(&id) => {
+                        replies.push(Reply::ERR_CANNOTSENDTOCHAN {
+                            channel: channel.name.clone(),
+                        })
+                    }
+                    Some(_) => state.send_to_channel(target, &message, Some(id)),
+                }
+            } else {
+                match state.find_nick(target) {
+                    Some(recipient) if state.client(recipient).registered => {
+                        state.client(recipient).send(&message)
+                    }
+                    _ => replies.push(Reply::ERR_NOSUCHNICK {
+                        target: target.to_owned(),
+                    }),
+                }
+            }
+        }
+
+        if let Some(target) = skipped.first() {
+            replies.push(Reply::ERR_TOOMANYTARGETS {
+                target: (*target).to_owned(),
+                error_code: "Too many".to_owned(),
+                message: format!("Only {} processed", maxtargets),
+            });
+        }
+
+        if is_notice {
+            vec![]
+        } else {
+            replies
+        }
+    }
+
+    /// Queries a channel's modes, or gives and takes channel operator status. Every channel
+    /// behaves as if `n` is set, so setting or unsetting it does nothing.
+    fn handle_channel_mode(
+        &self,
+        state: &mut State,
+        id: ClientId,
+        name: &str,
+        changes: &[ModeChange],
+        arguments: &[&str],
+    ) -> Vec<Reply> {
+        let channel = match state.find_channel(name) {
+            Some(channel) => channel,
+            None => {
+                return vec![Reply::ERR_NOSUCHCHANNEL {
+                    channel: name.to_owned(),
+                }]
+            }
+        };
+        let channel_name = channel.name.clone();
+        if changes.is_empty() {
+            return vec![Reply::RPL_CHANNELMODEIS {
+                channel: channel_name,
+                modes: vec!["+n".to_owned()],
+            }];
+        }
+
+        let is_operator = channel.operators.contains(&id);
+        let mut arguments = arguments.iter();
+        let mut replies = vec![];
+        let mut requested = vec![];
+        for change in changes {
+            match change.mode {
+                'n' => (),
+                'o' => {
+                    let nick = match arguments.next() {
+                        Some(nick) => nick,
+                        None => continue,
+                    };
+                    if !is_operator {
+                        replies.push(Reply::ERR_CHANOPRIVSNEEDED {
+                            channel: channel_name.clone(),
+                        });
+                        break;
+                    }
+                    match state.find_nick(nick) {
+                        Some(member) if channel.members.contains(&member) => {
+                            requested.push((change.adding, member))
+                        }
+                        _ => replies.push(Reply::ERR_USERNOTINCHANNEL {
+                            target: (*nick).to_owned(),
+                            channel: channel_name.clone(),
+                        }),
+                    }
+                }
+                mode => replies.push(Reply::ERR_UNKNOWNMODE {
+                    mode,
+                    channel: channel_name.clone(),
+                }),
+            }
+        }
+
+        // only announce the changes that actually changed something
+        let channel = state
+            .channels
+            .get_mut(&casefold(name))
+            .expect("channel was found above");
+        let applied: Vec<(bool, ClientId)> = requested
+            .into_iter()
+            .filter(|&(adding, member)| {
+                if adding {
+                    channel.operators.insert(member)
+                } else {
+                    channel.operators.remove(&member)
+                }
+            })
+            .collect();
+        if applied.is_empty() {
+            return replies;
+        }
+
+        let mut modes = String::new();
+        let mut adding = None;
+        for &(change, _) in &applied {
+            if adding != Some(change) {
+                modes.push(if change { '+' } else { '-' });
+                adding = Some(change);
+            }
+            modes.push('o');
+        }
+        let mut builder = Message::new("MODE")
+            .source(state.client(id).prefix())
+            .param(&channel_name)
+            .param(modes);
+        for &(_, member) in &applied {
+            builder = builder.param(state.client(member).display_nick());
+        }
+        match builder.build() {
+            Ok(message) => state.send_to_channel(name, &message, None),
+            Err(e) => warn!("Not announcing a mode change in {}: {}", channel_name, e),
+        }
+
+        replies
+    }
+
+    /// Makes the client an IRC operator if it matches an oper block's password and, when the
+    /// block names one, certificate fingerprint.
+    fn handle_oper(
+        &self,
+        state: &mut State,
+        id: ClientId,
+        name: &str,
+        password: &str,
+    ) -> Vec<Reply> {
+        let client = state.client_mut(id);
+        let config = self.config();
+        let oper = match config.opers.iter().find(|oper| oper.name == name) {
+            Some(oper) => oper,
+            None => return vec![Reply::ERR_PASSWDMISMATCH],
+        };
+
+        if let Some(certfp) = &oper.certfp {
+            let matches = client
+                .certfp
+                .as_deref()
+                .is_some_and(|client_certfp| client_certfp.eq_ignore_ascii_case(certfp));
+            if !matches {
+                return vec![Reply::ERR_NOOPERHOST];
+            }
+        }
+        match &oper.password {
+            Some(hash) => {
+                client.password_check = Some(PasswordCheck::Oper {
+                    name: oper.name.clone(),
+                    hash: hash.clone(),
+                    password: password.to_owned(),
+                });
+                vec![]
+            }
+            // a block with only a fingerprint doesn't care what password is given
+            None => finish_oper(client, oper.name.clone(), oper.certfp.is_some()),
+        }
+    }
+
+    fn handle_whois(&self, state: &State, id: ClientId, target: &str) -> Vec<Reply> {
+        let asker = state.client(id);
+        let client = match state.find_nick(target).map(|target| state.client(target)) {
+            Some(client) if client.registered => client,
+            _ => {
+                return vec![
+                    Reply::ERR_NOSUCHNICK {
+                        target: target.to_owned(),
+                    },
+                    Reply::RPL_ENDOFWHOIS {
+                        target: target.to_owned(),
+                    },
+                ]
+            }
+        };
+        let target = client.display_nick().to_owned();
+
+        let mut replies = vec![Reply::RPL_WHOISUSER {
+            target: target.clone(),
+            user: client.user.clone().unwrap_or_default(),
+            host: client.host.clone(),
+            realname: client.realname.clone().unwrap_or_default(),
+        }];
+
+        let mut channels: Vec<String> = state
+            .channels
+            .values()
+            .filter(|channel| channel.members.contains(&client.id))
+            .map(|channel| format!("{}{}", channel.member_prefix(client.id), channel.name))
+            .collect();
+        if !channels.is_empty() {
+            channels.sort();
+            replies.push(Reply::RPL_WHOISCHANNELS {
+                target: target.clone(),
+                channels,
+            });
+        }
+
+        replies.push(Reply::RPL_WHOISSERVER {
+            target: target.clone(),
+            server_name: self.config().irc.hostname.clone(),
+            server_info: self.config().irc.description.clone(),
+        });
+        if client.oper.is_some() {
+            replies.push(Reply::RPL_WHOISOPERATOR {
+                target: target.clone(),
+            });
+        }
+        if client.secure {
+            replies.push(Reply::RPL_WHOISSECURE {
+                target: target.clone(),
+            });
+        }
+        if let Some(account) = &client.account {
+            replies.push(Reply::RPL_WHOISACCOUNT {
+                target: target.clone(),
+                account: account.clone(),
+            });
+        }
+        // a fingerprint identifies its owner across nicks, so only they and opers get to see it
+        if let Some(certfp) = &client.certfp {
+            if client.id == id || asker.oper.is_some() {
+                replies.push(Reply::RPL_WHOISCERTFP {
+                    target: target.clone(),
+                    certfp: certfp.clone(),
+                });
+            }
+        }
+        replies.push(Reply::RPL_ENDOFWHOIS { target });
+
+        replies
+    }
+
+    /// Completes registration once the client has sent both NICK and USER, returning the welcome
+    /// burst.
+    pub(crate) fn try_register(&self, client: &mut Client) -> Vec<Reply> {
+        if !client.can_register() {
+            return vec![];
+        }
+        let prefix = client.prefix();
+        if let Some(ban) = self
+            .config()
+            .bans
+            .iter()
+            .find(|ban| matches_mask(&ban.mask, &prefix))
+        {
+            client.closing = Some(format!("Banned: {}", ban.reason));
+            return vec![Reply::ERR_YOUREBANNEDCREEP {
+                reason: ban.reason.clone(),
+            }];
+        }
+        client.registered = true;
+
+        self.welcome_burst(&prefix)
+    }
+
+    /// The message of the day, or an error if the server doesn't have one.
+    fn motd(&self) -> Vec<Reply> {
+        let config = self.config();
+        let lines = match &config.motd {
+            Some(lines) => lines,
+            None => return vec![Reply::ERR_NOMOTD],
+        };
+
+        let mut replies = vec![Reply::RPL_MOTDSTART {
+            server_name: self.config().irc.hostname.clone(),
+        }];
+        replies.extend(
+            lines
+                .iter()
+                .map(|line| Reply::RPL_MOTD { line: line.clone() }),
+        );
+        replies.push(Reply::RPL_ENDOFMOTD);
+
+        replies
+    }
+
+    /// The 001-005 numerics and the MOTD, sent once registration completes.
+    fn welcome_burst(&self, prefix: &str) -> Vec<Reply> {
+        let mut replies = vec![
+            Reply::RPL_WELCOME {
+                prefix: prefix.to_owned(),
+            },
+            Reply::RPL_YOURHOST {
+                server_name: self.config().irc.hostname.clone(),
+                version: VERSION.to_owned(),
+            },
+            Reply::RPL_CREATED {
+                created_at: self.config().irc.created_at.to_string(),
+            },
+            Reply::RPL_MYINFO {
+                server_name: self.config().irc.hostname.clone(),
+                version: VERSION.to_owned(),
+                user_modes: isupport::USER_MODES.to_owned(),
+                channel_modes: isupport::CHANNEL_MODES.to_owned(),
+            },
+        ];
+
+        let tokens = isupport::tokens(&self.config());
+        for chunk in tokens.chunks(isupport::TOKENS_PER_LINE) {
+            replies.push(Reply::RPL_ISUPPORT {
+                tokens: chunk.to_vec(),
+            });
+        }
+        replies.extend(self.motd());
+
+        replies
+    }
+}
+
+/// Queries the client's own modes, or drops its operator status. Only OPER can set `o`, so
+/// `+o` is ignored.
+fn handle_user_mode(client: &mut Client, target: &str, changes: &[ModeChange]) -> Vec<Reply> {
+    if casefold(target) != casefold(client.display_nick()) {
+        return vec![Reply::ERR_USERSDONTMATCH];
+    }
+    if changes.is_empty() {
+        let modes = if client.oper.is_some() { "+o" } else { "+" };
+        return vec![Reply::RPL_UMODEIS {
+            modes: modes.to_owned(),
+        }];
+    }
+
+    let mut replies = vec![];
+    for change in changes {
+        match change.mode {
+            'o' if !change.adding && client.oper.is_some() => {
+                client.oper = None;
+                let nick = client.display_nick();
+                client.send_message(Message::new("MODE").source(nick).param(nick).param("-o"));
+            }
+            'o' => (),
+            _ if replies.is_empty() => replies.push(Reply::ERR_UMODEUNKNOWNFLAG),
+            _ => (),
+        }
+    }
+
+    replies
+}
+
+/// Makes the client an IRC operator if it gave the right password for the oper block `name`.
+fn finish_oper(client: &mut Client, name: String, matched: bool) -> Vec<Reply> {
+    if !matched {
+        return vec![Reply::ERR_PASSWDMISMATCH];
+    }
+
+    client.oper = Some(name);
+    vec![Reply::RPL_YOUREOPER]
+}
+
+fn allowed_before_registration(command: &Command) -> bool {
+    matches!(
+        command,
+        Command::CAP(_, _)
+            | Command::AUTHENTICATE(_)
+            | Command::PASS(_)
+            | Command::NICK(_)
+            | Command::USER(_, _, _, _)
+            | Command::PING(_, _)
+            | Command::PONG(_, _)
+            | Command::QUIT(_)
+    )
+}
+
+/// Maps a command that could not be parsed to the numerics the client should receive.
+fn parse_error_replies(error: ParseError, registered: bool) -> Vec<Reply> {
+    match error {
+        // unregistered clients may only use the registration commands, and hear nothing else
+        ParseError::UnknownCommandError { command: _ } if !registered => {
+            vec![Reply::ERR_NOTREGISTERED]
+        }
+        ParseError::MissingCommandParameterError { command, .. }
+        | ParseError::InvalidCommandParameterError { command, .. }
+            if !registered
+                && !matches!(
+                    command.as_str(),
+                    "CAP" | "AUTHENTICATE" | "PASS" | "NICK" | "USER" | "PING" | "PONG"
+                ) =>
+        {
+            vec![Reply::ERR_NOTREGISTERED]
+        }
+        ParseError::UnknownCommandError { command } => {
+            vec![Reply::ERR_UNKNOWNCOMMAND { command }]
+        }
+        ParseError::MissingCommandParameterError {
+            command,
+            parameter: _,
+            index: _,
+        } if command == "NICK" || command == "WHOIS" => vec![Reply::ERR_NONICKNAMEGIVEN],
+        ParseError::MissingCommandParameterError {
+            command,
+            parameter: _,
+            index: _,
+        } if command == "PING" => vec![Reply::ERR_NOORIGIN],
+        // NOTICE must never trigger an automatic reply
+        ParseError::MissingCommandParameterError {
+            command,
+            parameter: _,
+            index: _,
+        } if command == "NOTICE" => vec![],
+        ParseError::MissingCommandParameterError {
+            command,
+            parameter: _,
+            index: 0,
+        } if command == "PRIVMSG" => vec![Reply::ERR_NORECIPIENT { command }],
+        ParseError::MissingCommandParameterError {
+            command,
+            parameter: _,
+            index: _,
+        } if command == "PRIVMSG" => vec![Reply::ERR_NOTEXTTOSEND],
+        ParseError::MissingCommandParameterError { command, .. }
+        | ParseError::InvalidCommandParameterError { command, .. } => {
+            vec![Reply::ERR_NEEDMOREPARAMS { command }]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts;
+    use crate::channel::Topic;
+    use crate::config::{Ban, Oper};
+    use crate::testing::{connect, received, register, send, server, server_with};
+
+    #[test]
+    fn registration_in_any_order() {
+        let server = server();
+        let (id, mut receiver) = connect(&server);
+
+        send(&server, id, &["USER cardinal 0 * :Cardinal"]);
+        assert!(received(&mut receiver).is_empty());
+
+        send(&server, id, &["NICK Cardinal"]);
+        let lines = received(&mut receiver);
+        assert_eq!(
+            lines[0],
+            ":irc.example.com 001 Cardinal :Welcome to the network Cardinal!cardinal@127.0.0.1\r\n"
+        );
+        assert!(server.state().clients[&id].registered);
+    }
+
+    #[test]
+    fn user_validated_and_truncated() {
+        let server = server();
+        let (id, mut receiver) = connect(&server);
+
+        send(&server, id, &["NICK Cardinal", "USER a@evil 0 * :x"]);
+        assert_eq!(
+            received(&mut receiver),
+            vec![":irc.example.com 461 Cardinal USER :Not enough parameters\r\n"]
+        );
+        assert_eq!(server.state().client(id).user, None);
+
+        send(&server, id, &["USER cardinal-of-the-north 0 * :x"]);
+        assert_eq!(
+            received(&mut receiver)[0],
+            ":irc.example.com 001 Cardinal :Welcome to the network Cardinal!cardinal-o@127.0.0.1\r\n"
+        );
+        assert_eq!(
+            server.state().client(id).user.as_deref(),
+            Some("cardinal-o")
+        );
+    }
+
+    #[test]
+    fn welcome_burst() {
+        let server = server();
+        let (id, mut receiver) = connect(&server);
+
+        send(
+            &server,
+            id,
+            &["NICK Cardinal", "USER cardinal 0 * :Cardinal"],
+        );
+        let lines = received(&mut receiver);
+        assert_eq!(lines.len(), 6);
+        assert_eq!(
+            lines[2],
+            ":irc.example.com 003 Cardinal :This server was created 2020-01-20T12:27:00-04:00\r\n"
+        );
+        assert_eq!(
+            lines[3],
+            format!(
+                ":irc.example.com 004 Cardinal irc.example.com {} o :no\r\n",
+                VERSION
+            )
+        );
+        assert!(lines[4].starts_with(":irc.example.com 005 Cardinal CASEMAPPING=rfc1459 "));
+        assert!(lines[4].ends_with(" :are supported by this server\r\n"));
+        assert_eq!(
+            lines[5],
+            ":irc.example.com 422 Cardinal :MOTD File is missing\r\n"
+        );
+    }
+
+    #[test]
+    fn motd() {
+        let server = server_with(|config| {
+            config.motd = Some(vec!["Be nice".to_owned(), String::new()]);
+        });
+        let (id, mut receiver) = connect(&server);
+
+        send(
+            &server,
+            id,
+            &["NICK Cardinal", "USER cardinal 0 * :Cardinal"],
+        );
+        let burst = received(&mut receiver);
+        let motd = vec![
+            ":irc.example.com 375 Cardinal :- irc.example.com Message of the day - \r\n",
+            ":irc.example.com 372 Cardinal :- Be nice\r\n",
+            ":irc.example.com 372 Cardinal :- \r\n",
+            ":irc.example.com 376 Cardinal :End of /MOTD command\r\n",
+        ];
+        assert_eq!(burst[5..], motd);
+
+        send(&server, id, &["MOTD", "MOTD irc.example.com"]);
+        assert_eq!(received(&mut receiver), [motd.clone(), motd].concat());
+    }
+
+    #[test]
+    fn banned_clients_disconnected() {
+        let server = server_with(|config| {
+            config.bans.push(Ban {
+                mask: "*!*@127.0.0.*".to_owned(),
+                reason: "Spamming".to_owned(),
+            });
+        });
+        let (id, mut receiver) = connect(&server);
+
+        send(
+            &server,
+            id,
+            &["NICK Cardinal", "USER cardinal 0 * :Cardinal"],
+        );
+        assert_eq!(
+            received(&mut receiver),
+            vec![
+                ":irc.example.com 465 Cardinal :You are banned from this server: Spamming\r\n",
+                "ERROR :Closing Link (Banned: Spamming)\r\n",
+            ]
+        );
+        assert!(!server.state().clients.contains_key(&id));
+    }
+
+    #[test]
+    fn nick_errors() {
+        let server = server();
+        let (_, _) = register(&server, "Cardinal");
+        let (id, mut receiver) = connect(&server);
+
+        send(&server, id, &["NICK", "NICK 1abc", "NICK cardinal"]);
+        assert_eq!(
+            received(&mut receiver),
+            vec![
+                ":irc.example.com 431 * :No nickname given\r\n",
+                ":irc.example.com 432 * 1abc :Erroneous nickname\r\n",
+                ":irc.example.com 433 * cardinal :Nickname is already in use\r\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn nick_change_after_registration() {
+        let server = server();
+        let (id, mut receiver) = register(&server, "Cardinal");
+        let (other, mut other_receiver) = register(&server, "Other");
+        let (_, mut stranger_receiver) = register(&server, "Stranger");
+
+        let mut channel = Channel::new("#test");
+        channel.members.extend(vec![id, other]);
+        server.state().channels.insert("#test".to_owned(), channel);
+
+        send(&server, id, &["NICK Robin"]);
+        let line = ":Cardinal!Cardinal@127.0.0.1 NICK Robin\r\n";
+        assert_eq!(received(&mut receiver), vec![line]);
+        assert_eq!(received(&mut other_receiver), vec![line]);
+        assert!(received(&mut stranger_receiver).is_empty());
+
+        let state = server.state();
+        assert_eq!(state.find_nick("robin"), Some(id));
+        assert_eq!(state.find_nick("cardinal"), None);
+    }
+
+    #[test]
+    fn nick_released_on_disconnect() {
+        let server = server();
+        let (id, _) = register(&server, "Cardinal");
+        server.quit(id, "Connection closed");
+
+        assert_eq!(server.state().find_nick("Cardinal"), None);
+    }
+
+    #[test]
+    fn join_sends_names_and_notifies_members() {
+        let server = server();
+        let (id, mut receiver) = register(&server, "Cardinal");
+        let (other, mut other_receiver) = register(&server, "Other");
+
+        send(&server, id, &["JOIN #test"]);
+        assert_eq!(
+            received(&mut receiver),
+            vec![
+                ":Cardinal!Cardinal@127.0.0.1 JOIN #test\r\n",
+                ":irc.example.com 353 Cardinal = #test :@Cardinal\r\n",
+                ":irc.example.com 366 Cardinal #test :End of /NAMES list\r\n",
+            ]
+        );
+
+        send(&server, other, &["JOIN #TEST,#other"]);
+        assert_eq!(
+            received(&mut receiver),
+            vec![":Other!Other@127.0.0.1 JOIN #test\r\n"]
+        );
+        let lines = received(&mut other_receiver);
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0], ":Other!Other@127.0.0.1 JOIN #test\r\n");
+        assert!(
+            lines[1] == ":irc.example.com 353 Other = #test :@Cardinal Other\r\n"
+                || lines[1] == ":irc.example.com 353 Other = #test :Other @Cardinal\r\n"
+        );
+        assert_eq!(lines[3], ":Other!Other@127.0.0.1 JOIN #other\r\n");
+    }
+
+    #[test]
+    fn join_topic_burst() {
+        let server = server();
+        let (id, mut receiver) = register(&server, "Cardinal");
+
+        let mut channel = Channel::new("#test");
+        channel.topic = Some(Topic {
+            text: "Welcome".to_owned(),
+            set_by: "Other".to_owned(),
+            set_at: 1579537620,
+        });
+        server.state().channels.insert("#test".to_owned(), channel);
+
+        send(&server, id, &["JOIN #test"]);
+        let lines = received(&mut receiver);
+        assert_eq!(lines[1], ":irc.example.com 332 Cardinal #test :Welcome\r\n");
+        assert_eq!(
+            lines[2],
+            ":irc.example.com 333 Cardinal #test Other :1579537620\r\n"
+        );
+    }
+
+    #[test]
+    fn join_errors() {
+        let server = server();
+        let (id, mut receiver) = register(&server, "Cardinal");
+
+        send(
+            &server,
+            id,
+            &["JOIN test", "PART #test", "JOIN #test", "JOIN #test"],
+        );
+        let lines = received(&mut receiver);
+        assert_eq!(
+            lines[0],
+            ":irc.example.com 403 Cardinal test :No such channel\r\n"
+        );
+        assert_eq!(
+            lines[1],
+            ":irc.example.com 403 Cardinal #test :No such channel\r\n"
+        );
+        // joining a channel twice is silently ignored
+        assert_eq!(lines.len(), 5);
+
+        let (other, mut other_receiver) = register(&server, "Other");
+        send(&server, other, &["PART #test"]);
+        assert_eq!(
+            received(&mut other_receiver),
+            vec![":irc.example.com 442 Other #test :You're not on that channel\r\n"]
+        );
+    }
+
+    #[test]
+    fn channel_messages_serialized_once() {
+        let server = server();
+        let (id, _) = register(&server, "Cardinal");
+        let (other, mut other_receiver) = register(&server, "Other");
+        let (third, mut third_receiver) = register(&server, "Third");
+        send(&server, id, &["JOIN #test"]);
+        send(&server, other, &["JOIN #test"]);
+        send(&server, third, &["JOIN #test"]);
+        received(&mut other_receiver);
+        received(&mut third_receiver);
+
+        send(&server, id, &["PRIVMSG #test :hi"]);
+        let line = other_receiver.try_recv().unwrap();
+        assert_eq!(
+            &line[..],
+            b":Cardinal!Cardinal@127.0.0.1 PRIVMSG #test :hi\r\n"
+        );
+        assert_eq!(line.as_ptr(), third_receiver.try_recv().unwrap().as_ptr());
+    }
+
+    #[test]
+    fn part_and_join_zero() {
+        let server = server();
+        let (id, mut receiver) = register(&server, "Cardinal");
+        let (other, mut other_receiver) = register(&server, "Other");
+
+        send(&server, id, &["JOIN #a,#b,#c"]);
+        send(&server, other, &["JOIN #a"]);
+        received(&mut receiver);
+        received(&mut other_receiver);
+
+        send(&server, id, &["PART #a :Goodbye for now"]);
+        let line = ":Cardinal!Cardinal@127.0.0.1 PART #a :Goodbye for now\r\n";
+        assert_eq!(received(&mut receiver), vec![line]);
+        assert_eq!(received(&mut other_receiver), vec![line]);
+
+        send(&server, id, &["JOIN 0"]);
+        assert_eq!(received(&mut receiver).len(), 2);
+        let state = server.state();
+        assert!(state.find_channel("#b").is_none());
+        assert!(state.find_channel("#c").is_none());
+        assert_eq!(state.find_channel("#a").unwrap().members.len(), 1);
+    }
+
+    #[test]
+    fn privmsg_to_users_and_channels() {
+        let server = server();
+        let (id, mut receiver) = register(&server, "Cardinal");
+        let (other, mut other_receiver) = register(&server, "Other");
+        let (third, mut third_receiver) = register(&server, "Third");
+
+        send(&server, id, &["JOIN #test"]);
+        send(&server, other, &["JOIN #test"]);
+        received(&mut receiver);
+        received(&mut other_receiver);
+
+        send(&server, id, &["PRIVMSG #test,Third :hello there"]);
+        assert!(received(&mut receiver).is_empty());
+        assert_eq!(
+            received(&mut other_receiver),
+            vec![":Cardinal!Cardinal@127.0.0.1 PRIVMSG #test :hello there\r\n"]
+        );
+        assert_eq!(
+            received(&mut third_receiver),
+            vec![":Cardinal!Cardinal@127.0.0.1 PRIVMSG Third :hello there\r\n"]
+        );
+
+        send(&server, third, &["NOTICE other :psst"]);
+        assert_eq!(
+            received(&mut other_receiver),
+            vec![":Third!Third@127.0.0.1 NOTICE other :psst\r\n"]
+        );
+    }
+
+    #[test]
+    fn privmsg_errors() {
+        let server = server();
+        let (id, mut receiver) = register(&server, "Cardinal");
+        let (other, _) = register(&server, "Other");
+        send(&server, other, &["JOIN #test"]);
+
+        send(
+            &server,
+            id,
+            &[
+                "PRIVMSG",
+                "PRIVMSG Other",
+                "PRIVMSG Other :",
+                "PRIVMSG Nobody,#nowhere :hi",
+                "PRIVMSG #test :hi",
+            ],
+        );
+        assert_eq!(
+            received(&mut receiver),
+            vec![
+                ":irc.example.com 411 Cardinal :No recipient given (PRIVMSG)\r\n",
+                ":irc.example.com 412 Cardinal :No text to send\r\n",
+                ":irc.example.com 412 Cardinal :No text to send\r\n",
+                ":irc.example.com 401 Cardinal Nobody :No such nick/channel\r\n",
+                ":irc.example.com 401 Cardinal #nowhere :No such nick/channel\r\n",
+                ":irc.example.com 404 Cardinal #test :Cannot send to channel\r\n",
+            ]
+        );
+
+        send(
+            &server,
+            id,
+            &["NOTICE", "NOTICE Other", "NOTICE Nobody,#nowhere,#test :hi"],
+        );
+        assert!(received(&mut receiver).is_empty());
+    }
+
+    #[test]
+    fn too_many_targets() {
+        let server = server_with(|config| config.limits.maxtargets = 2);
+        let (id, mut receiver) = register(&server, "Cardinal");
+        let (_, mut other_receiver) = register(&server, "Other");
+        let (_, mut robin_receiver) = register(&server, "Robin");
+
+        send(&server, id, &["PRIVMSG Other,Nobody,Robin,Other :hi"]);
+        assert_eq!(
+            received(&mut receiver),
+            vec![
+                ":irc.example.com 401 Cardinal Nobody :No such nick/channel\r\n",
+                ":irc.example.com 407 Cardinal Robin :Too many recipients. Only 2 processed\r\n",
+            ]
+        );
+        assert_eq!(received(&mut other_receiver).len(), 1);
+        assert!(received(&mut robin_receiver).is_empty());
+
+        // NOTICE is cut short the same way, without saying so
+        send(&server, id, &["NOTICE Robin,Other,Robin :hi"]);
+        assert!(received(&mut receiver).is_empty());
+        assert_eq!(received(&mut other_receiver).len(), 1);
+        assert_eq!(received(&mut robin_receiver).len(), 1);
+    }
+
+    #[test]
+    fn ping_before_registration() {
+        let server = server();
+        let (id, mut receiver) = connect(&server);
+
+        send(&server, id, &["PING :12345", "PING"]);
+        assert_eq!(
+            received(&mut receiver),
+            vec![
+                ":irc.example.com PONG irc.example.com :12345\r\n",
+                ":irc.example.com 409 * :No origin specified\r\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn quit_notifies_neighbours_once() {
+        let server = server();
+        let (id, mut receiver) = register(&server, "Cardinal");
+        let (other, mut other_receiver) = register(&server, "Other");
+        let (_, mut stranger_receiver) = register(&server, "Stranger");
+
+        send(&server, id, &["JOIN #a,#b,#c"]);
+        send(&server, other, &["JOIN #a,#b"]);
+        received(&mut receiver);
+        received(&mut other_receiver);
+
+        send(&server, id, &["QUIT :Gone fishing"]);
+        assert_eq!(
+            received(&mut receiver),
+            vec!["ERROR :Closing Link (Quit: Gone fishing)\r\n"]
+        );
+        assert_eq!(
+            received(&mut other_receiver),
+            vec![":Cardinal!Cardinal@127.0.0.1 QUIT :Quit: Gone fishing\r\n"]
+        );
+        assert!(received(&mut stranger_receiver).is_empty());
+
+        let state = server.state();
+        assert!(!state.clients.contains_key(&id));
+        assert_eq!(state.find_nick("Cardinal"), None);
+        assert!(state.find_channel("#c").is_none());
+        assert_eq!(state.find_channel("#a").unwrap().members.len(), 1);
+    }
+
+    #[test]
+    fn malformed_lines_ignored() {
+        let server = server();
+        let (id, mut receiver) = register(&server, "Cardinal");
+
+        send(&server, id, &["", "   ", ":", ": PING", ":prefix"]);
+        assert!(received(&mut receiver).is_empty());
+        assert!(server.state().clients.contains_key(&id));
+    }
+
+    #[test]
+    fn commands_case_insensitive_and_unimplemented() {
+        let server = server();
+        let (id, mut receiver) = register(&server, "Cardinal");
+
+        send(&server, id, &["ping :12345", "Lusers", "foo"]);
+        assert_eq!(
+            received(&mut receiver),
+            vec![
+                ":irc.example.com PONG irc.example.com :12345\r\n",
+                ":irc.example.com 421 Cardinal LUSERS :Unknown command\r\n",
+                ":irc.example.com 421 Cardinal foo :Unknown command\r\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn commands_before_registration_rejected() {
+        let server = server();
+        let (id, mut receiver) = connect(&server);
+
+        send(&server, id, &["LIST"]);
+        assert_eq!(
+            received(&mut receiver),
+            vec![":irc.example.com 451 * :You have not registered\r\n"]
+        );
+    }
+
+    #[test]
+    fn reregistration_rejected() {
+        let server = server();
+        let (id, mut receiver) = connect(&server);
+
+        send(
+            &server,
+            id,
+            &[
+                "PASS secret",
+                "NICK Cardinal",
+                "USER cardinal 0 * :Cardinal",
+            ],
+        );
+        received(&mut receiver);
+        assert_eq!(
+            server.state().clients[&id].password.as_deref(),
+            Some("secret")
+        );
+
+        send(&server, id, &["USER cardinal 0 * :Cardinal", "PASS secret"]);
+        assert_eq!(
+            received(&mut receiver),
+            vec![
+                ":irc.example.com 462 Cardinal :Unauthorized command (already registered)\r\n",
+                ":irc.example.com 462 Cardinal :Unauthorized command (already registered)\r\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn whois() {
+        let server = server();
+        let (id, mut receiver) = register(&server, "Cardinal");
+        let (other, _other_receiver) = register(&server, "Other");
+        send(&server, other, &["JOIN #test", "JOIN #abc"]);
+        {
+            let mut state = server.state();
+            let client = state.client_mut(other);
+            client.secure = true;
+            client.account = Some("OtherAccount".to_owned());
+            client.certfp = Some("abc123".to_owned());
+        }
+
+        send(&server, id, &["WHOIS other", "WHOIS Nobody", "WHOIS"]);
+        assert_eq!(
+            received(&mut receiver),
+            vec![
+                ":irc.example.com 311 Cardinal Other Other 127.0.0.1 * :Other\r\n",
+                ":irc.example.com 319 Cardinal Other :@#abc @#test\r\n",
+                ":irc.example.com 312 Cardinal Other irc.example.com :ircd\r\n",
+                ":irc.example.com 671 Cardinal Other :is using a secure connection\r\n",
+                ":irc.example.com 330 Cardinal Other OtherAccount :is logged in as\r\n",
+                ":irc.example.com 318 Cardinal Other :End of /WHOIS list\r\n",
+                ":irc.example.com 401 Cardinal Nobody :No such nick/channel\r\n",
+                ":irc.example.com 318 Cardinal Nobody :End of /WHOIS list\r\n",
+                ":irc.example.com 431 Cardinal :No nickname given\r\n",
+            ]
+        );
+
+        // the fingerprint is only shown to its owner and to opers
+        server.state().client_mut(id).oper = Some("admin".to_owned());
+        send(&server, id, &["WHOIS irc.example.com Other"]);
+        assert_eq!(
+            received(&mut receiver)[5],
+            ":irc.example.com 276 Cardinal Other :has client certificate fingerprint abc123\r\n"
+        );
+    }
+
+    #[test]
+    fn oper() {
+        let server = server_with(|config| {
+            config.opers.push(Oper {
+                name: "admin".to_owned(),
+                password: None,
+                certfp: Some("ABC123".to_owned()),
+            });
+            config.opers.push(Oper {
+                name: "nothing".to_owned(),
+                password: None,
+                certfp: None,
+            });
+            config.opers.push(Oper {
+                name: "password".to_owned(),
+                password: Some(accounts::hash_password("hunter2")),
+                certfp: None,
+            });
+        });
+        let (id, mut receiver) = register(&server, "Cardinal");
+
+        send(
+            &server,
+            id,
+            &[
+                "OPER nobody x",
+                "OPER nothing x",
+                "OPER admin x",
+                "OPER password hunter3",
+                "OPER",
+                "REHASH",
+            ],
+        );
+        assert_eq!(
+            received(&mut receiver),
+            vec![
+                ":irc.example.com 464 Cardinal :Password incorrect\r\n",
+                ":irc.example.com 464 Cardinal :Password incorrect\r\n",
+                ":irc.example.com 491 Cardinal :No O-lines for your host\r\n",
+                ":irc.example.com 464 Cardinal :Password incorrect\r\n",
+                ":irc.example.com 461 Cardinal OPER :Not enough parameters\r\n",
+                ":irc.example.com 481 Cardinal :Permission Denied- You're not an IRC operator\r\n",
+            ]
+        );
+        assert_eq!(server.state().client(id).oper, None);
+
+        server.state().client_mut(id).certfp = Some("abc123".to_owned());
+        send(&server, id, &["OPER admin x", "WHOIS Cardinal"]);
+        let lines = received(&mut receiver);
+        assert_eq!(
+            lines[0],
+            ":irc.example.com 381 Cardinal :You are now an IRC operator\r\n"
+        );
+        assert!(lines.contains(
+            &":irc.example.com 313 Cardinal Cardinal :is an IRC operator\r\n".to_owned()
+        ));
+        assert_eq!(server.state().client(id).oper.as_deref(), Some("admin"));
+
+        send(&server, id, &["REHASH"]);
+        assert_eq!(
+            received(&mut receiver),
+            vec![":irc.example.com 382 Cardinal * :Rehashing\r\n"]
+        );
+
+        let (id, mut receiver) = register(&server, "Robin");
+        send(&server, id, &["OPER password hunter2"]);
+        assert_eq!(
+            received(&mut receiver),
+            vec![":irc.example.com 381 Robin :You are now an IRC operator\r\n"]
+        );
+        assert_eq!(server.state().client(id).oper.as_deref(), Some("password"));
+    }
+
+    #[test]
+    fn user_mode() {
+        let server = server();
+        let (id, mut receiver) = register(&server, "Cardinal");
+        let (_, _) = register(&server, "Other");
+
+        server.state().client_mut(id).oper = Some("admin".to_owned());
+        send(
+            &server,
+            id,
+            &[
+                "MODE Other",
+                "MODE cardinal",
+                "MODE Cardinal +iw",
+                "MODE Cardinal +o-o",
+                "MODE Cardinal",
+            ],
+        );
+        assert_eq!(
+            received(&mut receiver),
+            vec![
+                ":irc.example.com 502 Cardinal :Cannot change mode for other users\r\n",
+                ":irc.example.com 221 Cardinal :+o\r\n",
+                ":irc.example.com 501 Cardinal :Unknown MODE flag\r\n",
+                ":Cardinal MODE Cardinal -o\r\n",
+                ":irc.example.com 221 Cardinal :+\r\n",
+            ]
+        );
+        assert_eq!(server.state().client(id).oper, None);
+    }
+
+    #[test]
+    fn channel_mode() {
+        let server = server();
+        let (id, mut receiver) = register(&server, "Cardinal");
+        let (other, mut other_receiver) = register(&server, "Other");
+        let (_, _) = register(&server, "Robin");
+        send(&server, id, &["JOIN #test"]);
+        send(&server, other, &["JOIN #test"]);
+        received(&mut receiver);
+        received(&mut other_receiver);
+
+        send(
+            &server,
+            other,
+            &["MODE #test", "MODE #test +o Other", "MODE #nowhere"],
+        );
+        assert_eq!(
+            received(&mut other_receiver),
+            vec![
+                ":irc.example.com 324 Other #test :+n\r\n",
+                ":irc.example.com 482 Other #test :You're not channel operator\r\n",
+                ":irc.example.com 403 Other #nowhere :No such channel\r\n",
+            ]
+        );
+
+        // unknown modes and members are reported, and the rest still applied
+        send(&server, id, &["MODE #test +nzo-o+o Robin Cardinal other"]);
+        assert_eq!(
+            received(&mut receiver),
+            vec![
+                ":Cardinal!Cardinal@127.0.0.1 MODE #test -o+o Cardinal Other\r\n",
+                ":irc.example.com 472 Cardinal z :is unknown mode char to me for #test\r\n",
+                ":irc.example.com 441 Cardinal Robin #test :They aren't on that channel\r\n",
+            ]
+        );
+        assert_eq!(
+            received(&mut other_receiver),
+            vec![":Cardinal!Cardinal@127.0.0.1 MODE #test -o+o Cardinal Other\r\n"]
+        );
+        let state = server.state();
+        let channel = state.find_channel("#test").unwrap();
+        assert!(!channel.operators.contains(&id));
+        assert!(channel.operators.contains(&other));
+    }
+}
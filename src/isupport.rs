@@ -0,0 +1,57 @@
+//! Features advertised to clients in RPL_MYINFO (004) and RPL_ISUPPORT (005).
+
+use crate::config::Config;
+
+/// Only OPER sets `o`, though MODE can unset it.
+pub const USER_MODES: &str = "o";
+/// Every channel behaves as if `n` is set, and `o` is the only membership prefix.
+pub const CHANNEL_MODES: &str = "no";
+
+pub const CHANTYPES: &str = "#";
+pub const PREFIX: &str = "(o)@";
+pub const CHANMODES: &str = ",,,n";
+pub const CASEMAPPING: &str = "rfc1459";
+
+/// RPL_ISUPPORT allows at most 13 tokens per line, leaving room for the nick and trailing text.
+pub const TOKENS_PER_LINE: usize = 13;
+
+/// Builds the RPL_ISUPPORT tokens for the current configuration.
+///
+/// Examples
+///
+/// ```
+/// use ircd::config::Config;
+/// use ircd::isupport;
+///
+/// let config: Config = toml::from_str(r#"
+///     [irc]
+///     hostname = "irc.example.com"
+///     created_at = 2020-01-20T12:27:00-04:00
+///     network = "ExampleNet"
+/// "#).unwrap();
+/// let tokens = isupport::tokens(&config);
+///
+/// assert!(tokens.contains(&"NETWORK=ExampleNet".to_owned()));
+/// assert!(tokens.contains(&"NICKLEN=30".to_owned()));
+/// ```
+pub fn tokens(config: &Config) -> Vec<String> {
+    let limits = &config.limits;
+
+    let mut tokens = vec![
+        format!("CASEMAPPING={}", CASEMAPPING),
+        format!("CHANMODES={}", CHANMODES),
+        format!("CHANNELLEN={}", limits.channellen),
+        format!("CHANTYPES={}", CHANTYPES),
+        format!("MAXTARGETS={}", limits.maxtargets),
+    ];
+    if let Some(network) = &config.irc.network {
+        tokens.push(format!("NETWORK={}", network));
+    }
+    tokens.extend(vec![
+        format!("NICKLEN={}", limits.nicklen),
+        format!("PREFIX={}", PREFIX),
+        format!("USERLEN={}", limits.userlen),
+    ]);
+
+    tokens
+}
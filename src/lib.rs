@@ -1,2 +1,20 @@
+pub mod accounts;
+pub mod caps;
+pub mod casemap;
+pub mod channel;
+pub mod client;
 pub mod config;
+pub mod framing;
+mod handlers;
+pub mod isupport;
+pub mod listener;
+pub mod message;
+pub mod proxy;
+pub mod reply;
+pub mod sasl;
+pub mod scram;
+pub mod server;
 pub mod structs;
+#[cfg(test)]
+mod testing;
+pub mod tls;
@@ -0,0 +1,210 @@
+//! The sockets clients connect to, one for each `[[listen]]` block.
+
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::os::unix::fs::FileTypeExt;
+
+use tokio::net::{TcpListener, UnixListener};
+use tokio_rustls::TlsAcceptor;
+
+use crate::config::Listen;
+use crate::tls;
+
+pub enum Socket {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+/// What happens to each connection a listener accepts before it is served.
+#[derive(Clone, Default)]
+pub struct Settings {
+    pub acceptor: Option<TlsAcceptor>,
+    pub proxy: bool,
+    /// The `[[classes]]` block connections belong to.
+    pub class: Option<String>,
+}
+
+impl Settings {
+    /// The settings a `[[listen]]` block describes, loading its TLS certificate if it has one.
+    pub fn new(config: &Listen, name: &str) -> Result<Self, String> {
+        let acceptor = match &config.tls {
+            Some(tls) => Some(
+                tls::acceptor(tls)
+                    .map_err(|e| format!("Error setting up TLS for {}: {}", name, e))?,
+            ),
+            None => None,
+        };
+
+        Ok(Settings {
+            acceptor,
+            proxy: config.proxy,
+            class: config.class.clone(),
+        })
+    }
+}
+
+/// A bound socket, along with what to do with each connection before it is served.
+pub struct Listener {
+    /// The address or path, for logs.
+    pub name: String,
+    pub socket: Socket,
+    pub settings: Settings,
+}
+
+impl Listener {
+    /// Binds the socket a `[[listen]]` block describes, and loads its TLS certificate if it has
+    /// one. Errors say which listener failed and why.
+    pub async fn bind(config: &Listen) -> Result<Self, String> {
+        let name = Listener::name(config)?;
+        let socket = match (&config.address, &config.path) {
+            (Some(_), None) => {
+                let socket = TcpListener::bind(&name)
+                    .await
+                    .map_err(|e| format!("Error binding {}: {}", name, e))?;
+                Socket::Tcp(socket)
+            }
+            _ => {
+                remove_stale_socket(&name)
+                    .map_err(|e| format!("Error removing old socket {}: {}", name, e))?;
+                let socket = UnixListener::bind(&name)
+                    .map_err(|e| format!("Error binding {}: {}", name, e))?;
+                Socket::Unix(socket)
+            }
+        };
+        let settings = Settings::new(config, &name)?;
+
+        Ok(Listener {
+            name,
+            socket,
+            settings,
+        })
+    }
+
+    /// The normalised address or path a `[[listen]]` block would be bound to, which identifies
+    /// the listener across reloads of the configuration.
+    pub fn name(config: &Listen) -> Result<String, String> {
+        match (&config.address, &config.path) {
+            (Some(address), None) => {
+                let address: SocketAddr = address
+                    .parse()
+                    .map_err(|e| format!("Invalid listen address {}: {}", address, e))?;
+                Ok(address.to_string())
+            }
+            (None, Some(path)) => Ok(path.clone()),
+            _ => Err("Each [[listen]] block needs exactly one of address or path".to_owned()),
+        }
+    }
+}
+
+/// A socket left behind by a previous run would stop the path being bound again, so it is
+/// removed. Anything else at the path is left for binding to fail on.
+fn remove_stale_socket(path: &str) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(path),
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpStream, UnixStream};
+
+    use super::*;
+    use crate::testing;
+
+    fn listen(address: Option<&str>, path: Option<&str>, proxy: bool) -> Listen {
+        Listen {
+            address: address.map(str::to_owned),
+            path: path.map(str::to_owned),
+            tls: None,
+            proxy,
+            class: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn unix_socket_with_proxy() {
+        let path = std::env::temp_dir().join(format!("ircd-{}.sock", std::process::id()));
+        let path = path.to_str().unwrap();
+        // binding twice replaces the socket left behind by the first listener
+        drop(
+            Listener::bind(&listen(None, Some(path), true))
+                .await
+                .unwrap(),
+        );
+        let listener = Listener::bind(&listen(None, Some(path), true))
+            .await
+            .unwrap();
+        Arc::new(testing::server()).spawn_listener(listener);
+
+        let (read_half, mut write_half) = UnixStream::connect(path).await.unwrap().into_split();
+        write_half
+            .write_all(
+                b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 6667\r\nNICK a\r\nUSER a 0 * :a\r\n",
+            )
+            .await
+            .unwrap();
+        let line = BufReader::new(read_half).lines().next_line().await.unwrap();
+        assert_eq!(
+            line.as_deref(),
+            Some(":irc.example.com 001 a :Welcome to the network a!a@192.0.2.1")
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn tcp() {
+        let listener = Listener::bind(&listen(Some("127.0.0.1:0"), None, false))
+            .await
+            .unwrap();
+        let address = match &listener.socket {
+            Socket::Tcp(socket) => socket.local_addr().unwrap(),
+            Socket::Unix(_) => unreachable!(),
+        };
+        Arc::new(testing::server()).spawn_listener(listener);
+
+        let (read_half, mut write_half) = TcpStream::connect(address).await.unwrap().into_split();
+        write_half
+            .write_all(b"NICK a\r\nUSER a 0 * :a\r\n")
+            .await
+            .unwrap();
+        let line = BufReader::new(read_half).lines().next_line().await.unwrap();
+        assert_eq!(
+            line.as_deref(),
+            Some(":irc.example.com 001 a :Welcome to the network a!a@127.0.0.1")
+        );
+
+        let error = Listener::bind(&listen(Some(&address.to_string()), None, false)).await;
+        assert!(error
+            .err()
+            .unwrap()
+            .starts_with(&format!("Error binding {}: ", address)));
+    }
+
+    #[tokio::test]
+    async fn invalid_config() {
+        for (config, error) in [
+            (
+                listen(None, None, false),
+                "Each [[listen]] block needs exactly one of address or path",
+            ),
+            (
+                listen(Some("127.0.0.1:6667"), Some("ircd.sock"), false),
+                "Each [[listen]] block needs exactly one of address or path",
+            ),
+            (
+                listen(Some("localhost:6667"), None, false),
+                "Invalid listen address localhost:6667: invalid socket address syntax",
+            ),
+        ] {
+            assert_eq!(Listener::bind(&config).await.err().as_deref(), Some(error));
+        }
+    }
+}
@@ -1,72 +1,155 @@
-use std::convert::TryFrom;
-use std::io::{BufRead, BufReader, Write};
-use std::net::{Ipv4Addr, SocketAddrV4, TcpListener};
-
-mod config;
-mod structs;
-
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // read config
-    let config = config::get_config("./config.toml")?;
-
-    // listen for connection on 127.0.0.1:6667
-    let socket = SocketAddrV4::new(Ipv4Addr::new(127, 0, 0, 1), 6667);
-    let listener = TcpListener::bind(socket)?;
-    println!("Listening on 127.0.0.1:6667");
-
-    let (mut tcp_stream, addr) = listener.accept()?; // blocks until connection
-    println!("Connection from {:?}", addr);
-
-    let read_stream = tcp_stream.try_clone()?;
-    let reader = BufReader::new(read_stream);
-    let lines = reader.lines();
-
-    for line in lines {
-        // translate to internal irc message struct
-        let line = line.unwrap();
-        let irc_message = structs::IrcMessage::try_from(line.as_str())?;
-
-        // decide whether to generate a reply
-        let mut replies: Vec<structs::Reply> = vec![];
-        match irc_message.to_command() {
-            Ok(command) => {
-                println!("{:?} -> {:?}", irc_message, command);
-
-                match command {
-                    structs::Command::USER(user, _mode, _unused, _realname) => {
-                        replies.push(structs::Reply::RPL_WELCOME {
-                            nick: "nick".to_owned(),
-                            user: user.to_owned(),
-                            host: "host".to_owned(),
-                        });
-                        replies.push(structs::Reply::RPL_YOURHOST {
-                            nick: "nick".to_owned(),
-                            server_name: config.irc.hostname.clone(),
-                            version: "0.1.0".to_owned(),
-                        });
-                    }
-                    _ => (),
-                };
+use std::io::{self, BufRead};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use clap::{Parser, Subcommand};
+use log::{info, LevelFilter};
+use tokio::signal::unix::{signal, SignalKind};
+
+use ircd::accounts::{self, TomlAccountStore};
+use ircd::config::{self, Config};
+use ircd::listener::{Listener, Settings};
+use ircd::server::Server;
+
+/// An IRC server.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// The configuration file to load.
+    #[arg(short, long, default_value = "config.toml")]
+    config: String,
+    /// Check the configuration, including the files it refers to, then exit without starting.
+    #[arg(long)]
+    check_config: bool,
+    /// The most detailed messages to log: off, error, warn, info, debug or trace. Overrides
+    /// RUST_LOG, and defaults to info.
+    #[arg(short, long)]
+    log_level: Option<LevelFilter>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Read a password from stdin and print its hash, for the password of an [[opers]] block.
+    HashPassword,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let mut logger =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"));
+    if let Some(level) = cli.log_level {
+        logger.filter_level(level);
+    }
+    logger.init();
+
+    if let Some(Command::HashPassword) = cli.command {
+        return hash_password();
+    }
+
+    let config = match config::get_config(&cli.config) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if cli.check_config {
+        return check_config(&cli.config, &config);
+    }
+
+    let mut server = Server::new(config).with_config_path(cli.config);
+    if let Some(path) = &server.config().sasl.accounts {
+        match TomlAccountStore::load(path) {
+            Ok(accounts) => server = server.with_account_store(accounts),
+            Err(e) => {
+                eprintln!("{}", e);
+                return ExitCode::FAILURE;
             }
-            Err(error) => {
-                println!("{:?} -> {:?}", irc_message, error);
-                match error {
-                    structs::ParseError::UnknownCommandError { command } => {
-                        replies.push(structs::Reply::ERR_UNKNOWNCOMMAND { command })
-                    }
-                    structs::ParseError::MissingCommandParameterError {
-                        command,
-                        parameter: _,
-                        index: _,
-                    } => replies.push(structs::Reply::ERR_NEEDMOREPARAMS { command }),
-                }
+        }
+    }
+
+    // bind everything before serving anyone, and report every listener that fails rather than
+    // just the first
+    let mut listeners = vec![];
+    let mut errors = vec![];
+    for config in &server.config().listen {
+        match Listener::bind(config).await {
+            Ok(listener) => {
+                info!("Listening on {}", listener.name);
+                listeners.push(listener);
             }
+            Err(e) => errors.push(e),
         }
+    }
+    if !errors.is_empty() {
+        for e in errors {
+            eprintln!("{}", e);
+        }
+        return ExitCode::FAILURE;
+    }
+
+    let server = Arc::new(server);
+    for listener in listeners {
+        server.spawn_listener(listener);
+    }
+    tokio::spawn(Arc::clone(&server).handle_rehashes());
+
+    // SIGHUP reloads the configuration, as with REHASH
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            eprintln!("Error handling SIGHUP: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    while hangups.recv().await.is_some() {
+        server.request_rehash(None);
+    }
+
+    ExitCode::SUCCESS
+}
 
-        for reply in replies {
-            tcp_stream.write(reply.as_line().as_bytes())?;
+/// Loads everything the configuration refers to that `get_config` doesn't, short of binding the
+/// listeners, and reports every problem found.
+fn check_config(path: &str, config: &Config) -> ExitCode {
+    let mut errors = vec![];
+    if let Some(accounts) = &config.sasl.accounts {
+        if let Err(e) = TomlAccountStore::load(accounts) {
+            errors.push(e);
         }
     }
+    for listen in &config.listen {
+        if let Err(e) = Listener::name(listen).and_then(|name| Settings::new(listen, &name)) {
+            errors.push(e);
+        }
+    }
+
+    if !errors.is_empty() {
+        for e in errors {
+            eprintln!("{}", e);
+        }
+        return ExitCode::FAILURE;
+    }
+    println!("{} is valid", path);
+    ExitCode::SUCCESS
+}
+
+fn hash_password() -> ExitCode {
+    let mut password = String::new();
+    if let Err(e) = io::stdin().lock().read_line(&mut password) {
+        eprintln!("Error reading the password: {}", e);
+        return ExitCode::FAILURE;
+    }
+    let password = password.trim_end_matches(&['\r', '\n'][..]);
+    if password.is_empty() {
+        eprintln!("The password can't be empty");
+        return ExitCode::FAILURE;
+    }
 
-    Ok(())
+    println!("{}", accounts::hash_password(password));
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,303 @@
+//! Owned messages, which can be queued, kept around or handed to another connection's task.
+//!
+//! An `IrcMessage` borrows from the line it was parsed from. A `Message` instead keeps the
+//! serialized line in a shared buffer along with where each part of it is, so cloning one to send
+//! to every member of a channel doesn't serialize or copy the line again.
+//!
+//! Messages the server sends itself are put together with a `MessageBuilder`, which owns its
+//! parts so they can come from temporaries.
+
+use std::convert::TryFrom;
+use std::ops::Range;
+use std::str;
+
+use bytes::Bytes;
+
+use crate::structs::{self, IrcMessage, SerializeError, Tag, Trailing};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Message {
+    /// The serialized line, ending in CRLF.
+    line: Bytes,
+    layout: Layout,
+}
+
+/// Where each part of a message is in its serialized line.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Layout {
+    /// The tags section, without the `@`.
+    pub tags: Option<Range<usize>>,
+    pub prefix: Option<Range<usize>>,
+    pub command: Range<usize>,
+    pub parameters: Vec<Range<usize>>,
+}
+
+impl Message {
+    /// Starts building a message with the given command.
+    ///
+    /// Examples
+    ///
+    /// ```
+    /// use ircd::message::Message;
+    ///
+    /// let message = Message::new("PRIVMSG")
+    ///     .source("Cardinal!cardinal@127.0.0.1")
+    ///     .param("#test")
+    ///     .trailing("hi")
+    ///     .tag("time", "2020-01-20T12:27:00.000Z")
+    ///     .build()?;
+    ///
+    /// assert_eq!(
+    ///     message.as_str(),
+    ///     "@time=2020-01-20T12:27:00.000Z :Cardinal!cardinal@127.0.0.1 PRIVMSG #test :hi\r\n"
+    /// );
+    ///
+    /// Ok::<(), ircd::structs::SerializeError>(())
+    /// ```
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(command: impl Into<String>) -> MessageBuilder {
+        MessageBuilder {
+            command: command.into(),
+            ..MessageBuilder::default()
+        }
+    }
+
+    pub fn serialize(
+        irc_message: &IrcMessage<'_>,
+        trailing: Trailing,
+    ) -> Result<Self, SerializeError> {
+        let (line, layout) = irc_message.serialize(trailing)?;
+        Ok(Message {
+            line: Bytes::from(line),
+            layout,
+        })
+    }
+
+    /// The serialized line, ending in CRLF.
+    pub fn as_bytes(&self) -> &Bytes {
+        &self.line
+    }
+
+    /// The serialized line, ending in CRLF.
+    pub fn as_str(&self) -> &str {
+        self.slice(0..self.line.len())
+    }
+
+    pub fn tags(&self) -> Vec<Tag<'_>> {
+        self.layout
+            .tags
+            .clone()
+            .map_or_else(Vec::new, |range| structs::parse_tags(self.slice(range)))
+    }
+
+    pub fn prefix(&self) -> Option<&str> {
+        self.layout.prefix.clone().map(|range| self.slice(range))
+    }
+
+    pub fn command(&self) -> &str {
+        self.slice(self.layout.command.clone())
+    }
+
+    pub fn parameters(&self) -> Vec<&str> {
+        self.layout
+            .parameters
+            .iter()
+            .map(|range| self.slice(range.clone()))
+            .collect()
+    }
+
+    /// Borrows the message back without parsing the line again.
+    ///
+    /// Examples
+    ///
+    /// ```
+    /// use std::convert::TryFrom;
+    /// use ircd::message::Message;
+    /// use ircd::structs::IrcMessage;
+    ///
+    /// let irc_message = IrcMessage {
+    ///     tags: vec![],
+    ///     prefix: Some("Cardinal!cardinal@127.0.0.1"),
+    ///     command: "PRIVMSG",
+    ///     command_parameters: vec!["#test", "hello there"],
+    /// };
+    /// let message = Message::try_from(irc_message)?;
+    ///
+    /// assert_eq!(
+    ///     &message.as_bytes()[..],
+    ///     b":Cardinal!cardinal@127.0.0.1 PRIVMSG #test :hello there\r\n"
+    /// );
+    /// assert_eq!(message.to_irc_message().command_parameters, vec!["#test", "hello there"]);
+    ///
+    /// Ok::<(), ircd::structs::SerializeError>(())
+    /// ```
+    pub fn to_irc_message(&self) -> IrcMessage<'_> {
+        IrcMessage {
+            tags: self.tags(),
+            prefix: self.prefix(),
+            command: self.command(),
+            command_parameters: self.parameters(),
+        }
+    }
+
+    fn slice(&self, range: Range<usize>) -> &str {
+        str::from_utf8(&self.line[range]).expect("messages are serialized from strings")
+    }
+}
+
+impl TryFrom<IrcMessage<'_>> for Message {
+    type Error = SerializeError;
+
+    fn try_from(irc_message: IrcMessage<'_>) -> Result<Self, Self::Error> {
+        Message::serialize(&irc_message, Trailing::IfNeeded)
+    }
+}
+
+/// The parts of a message, which are only checked once it's built.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MessageBuilder {
+    tags: Vec<(String, Option<String>)>,
+    source: Option<String>,
+    command: String,
+    parameters: Vec<String>,
+    trailing: Option<String>,
+}
+
+impl MessageBuilder {
+    /// Adds a tag, or replaces the value of one that was already added. An empty value is the
+    /// same as no value.
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        let value = Some(value.into()).filter(|value| !value.is_empty());
+        match self.tags.iter_mut().find(|(k, _)| *k == key) {
+            Some(tag) => tag.1 = value,
+            None => self.tags.push((key, value)),
+        }
+        self
+    }
+
+    /// Sets the prefix, i.e. the server name or `nick!user@host` the message is from.
+    pub fn source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Adds a parameter. The last one is only put behind a colon if it has to be.
+    pub fn param(mut self, parameter: impl Into<String>) -> Self {
+        self.parameters.push(parameter.into());
+        self
+    }
+
+    /// Sets the last parameter, which is always put behind a colon and may be cut short to fit
+    /// the line. It comes after every other parameter, whenever it was set.
+    pub fn trailing(mut self, text: impl Into<String>) -> Self {
+        self.trailing = Some(text.into());
+        self
+    }
+
+    pub fn build(&self) -> Result<Message, SerializeError> {
+        let mut command_parameters: Vec<&str> =
+            self.parameters.iter().map(String::as_str).collect();
+        command_parameters.extend(self.trailing.as_deref());
+        let irc_message = IrcMessage {
+            tags: self
+                .tags
+                .iter()
+                .map(|(key, value)| Tag::new(key, value.as_deref()))
+                .collect(),
+            prefix: self.source.as_deref(),
+            command: &self.command,
+            command_parameters,
+        };
+
+        let trailing = match self.trailing {
+            Some(_) => Trailing::Always,
+            None => Trailing::IfNeeded,
+        };
+        Message::serialize(&irc_message, trailing)
+    }
+}
+
+/// Shares the serialized line, without copying it.
+impl From<&Message> for Bytes {
+    fn from(message: &Message) -> Self {
+        message.line.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let irc_message = IrcMessage {
+            tags: vec![
+                Tag::new("time", Some("2020-01-20T12:27:00.000Z")),
+                Tag::new("+draft/reply", Some("a b;c")),
+            ],
+            prefix: Some("irc.example.com"),
+            command: "NOTICE",
+            command_parameters: vec!["*", "two words"],
+        };
+        let message = Message::try_from(irc_message.clone()).unwrap();
+
+        assert_eq!(
+            &message.as_bytes()[..],
+            &b"@time=2020-01-20T12:27:00.000Z;+draft/reply=a\\sb\\:c :irc.example.com NOTICE * :two words\r\n"[..]
+        );
+        assert_eq!(message.to_irc_message(), irc_message);
+
+        // sharing the line doesn't copy it
+        let shared = Bytes::from(&message);
+        assert_eq!(shared.as_ptr(), message.as_bytes().as_ptr());
+    }
+
+    #[test]
+    fn without_parameters() {
+        let message = Message::try_from(IrcMessage {
+            tags: vec![],
+            prefix: None,
+            command: "REHASH",
+            command_parameters: vec![],
+        })
+        .unwrap();
+
+        assert_eq!(&message.as_bytes()[..], b"REHASH\r\n");
+        assert_eq!(message.prefix(), None);
+        assert!(message.tags().is_empty());
+        assert!(message.parameters().is_empty());
+    }
+
+    #[test]
+    fn builder() {
+        let builder = Message::new("NOTICE").param("*").param("hello there");
+        assert_eq!(
+            builder.build().unwrap().as_str(),
+            "NOTICE * :hello there\r\n"
+        );
+
+        // the trailing parameter always comes last and always has a colon
+        let message = Message::new("PRIVMSG")
+            .trailing("hi")
+            .param("#test")
+            .tag("a", "1")
+            .tag("b", "")
+            .tag("a", "2")
+            .build()
+            .unwrap();
+        assert_eq!(message.as_str(), "@a=2;b PRIVMSG #test :hi\r\n");
+
+        assert_eq!(
+            Message::new("PRIVMSG")
+                .param("#test channel")
+                .trailing("hi")
+                .build(),
+            Err(SerializeError::InvalidParameter { index: 0 })
+        );
+        assert_eq!(
+            Message::new("").build(),
+            Err(SerializeError::InvalidCommand)
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! The PROXY protocol, which load balancers use to pass on the address a connection really came
+//! from.
+//!
+//! See <https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt>. Both the text (v1) and
+//! binary (v2) headers are accepted.
+
+use std::convert::TryInto;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// A v1 header is at most this long, including the CRLF.
+const MAX_V1_LEN: usize = 107;
+
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+
+/// Reads a PROXY header from the start of a connection, returning the client's address. `None`
+/// means the proxy didn't say, such as for its own health checks.
+///
+/// Only the header is consumed, so the stream can be handed on as if it had just been accepted.
+pub async fn read_header<R>(stream: &mut R) -> io::Result<Option<SocketAddr>>
+where
+    R: AsyncRead + Unpin,
+{
+    match stream.read_u8().await? {
+        b'P' => read_v1(stream).await,
+        b'\r' => read_v2(stream).await,
+        _ => Err(invalid("Missing PROXY header")),
+    }
+}
+
+/// `PROXY TCP4 <source> <destination> <source port> <destination port>\r\n`, or
+/// `PROXY UNKNOWN ...\r\n`. The leading `P` has already been read.
+async fn read_v1<R>(stream: &mut R) -> io::Result<Option<SocketAddr>>
+where
+    R: AsyncRead + Unpin,
+{
+    // read a byte at a time so nothing past the header is consumed
+    let mut header = vec![b'P'];
+    while !header.ends_with(b"\r\n") {
+        if header.len() == MAX_V1_LEN {
+            return Err(invalid("PROXY header too long"));
+        }
+        header.push(stream.read_u8().await?);
+    }
+
+    let header = std::str::from_utf8(&header[..header.len() - 2])
+        .map_err(|_| invalid("Invalid PROXY header"))?;
+    parse_v1(header).ok_or_else(|| invalid("Invalid PROXY header"))
+}
+
+/// Parses a v1 header without its CRLF.
+///
+/// Examples
+///
+/// ```
+/// use ircd::proxy::parse_v1;
+///
+/// assert_eq!(
+///     parse_v1("PROXY TCP4 192.0.2.1 198.51.100.1 56324 6667"),
+///     Some(Some("192.0.2.1:56324".parse().unwrap()))
+/// );
+/// assert_eq!(parse_v1("PROXY UNKNOWN"), Some(None));
+/// assert_eq!(parse_v1("PROXY TCP4 2001:db8::1 198.51.100.1 56324 6667"), None);
+/// ```
+pub fn parse_v1(header: &str) -> Option<Option<SocketAddr>> {
+    let fields: Vec<&str> = header.split(' ').collect();
+    match fields.as_slice() {
+        ["PROXY", "UNKNOWN", ..] => Some(None),
+        ["PROXY", protocol, source, _destination, source_port, _destination_port] => {
+            let ip: IpAddr = match *protocol {
+                "TCP4" => IpAddr::V4(source.parse().ok()?),
+                "TCP6" => IpAddr::V6(source.parse().ok()?),
+                _ => return None,
+            };
+            Some(Some(SocketAddr::new(ip, source_port.parse().ok()?)))
+        }
+        _ => None,
+    }
+}
+
+/// The binary header: a 12 byte signature, version and command, address family, and the length
+/// of the addresses that follow. The leading `\r` has already been read.
+async fn read_v2<R>(stream: &mut R) -> io::Result<Option<SocketAddr>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut header = [0; 16];
+    header[0] = b'\r';
+    stream.read_exact(&mut header[1..]).await?;
+    if &header[..12] != V2_SIGNATURE {
+        return Err(invalid("Missing PROXY header"));
+    }
+
+    let length = u16::from_be_bytes([header[14], header[15]]) as usize;
+    let mut addresses = vec![0; length];
+    stream.read_exact(&mut addresses).await?;
+
+    parse_v2(header[12], header[13], &addresses).ok_or_else(|| invalid("Invalid PROXY header"))
+}
+
+fn parse_v2(version_command: u8, family: u8, addresses: &[u8]) -> Option<Option<SocketAddr>> {
+    if version_command >> 4 != 2 {
+        return None;
+    }
+    match version_command & 0x0F {
+        // LOCAL: the proxy connected on its own behalf
+        0 => return Some(None),
+        1 => (),
+        _ => return None,
+    }
+
+    let port = |at: usize| {
+        Some(u16::from_be_bytes([
+            *addresses.get(at)?,
+            *addresses.get(at + 1)?,
+        ]))
+    };
+    match family >> 4 {
+        // AF_INET: source, destination, source port, destination port
+        1 if addresses.len() >= 12 => {
+            let ip: [u8; 4] = addresses[..4].try_into().ok()?;
+            Some(Some(SocketAddr::new(Ipv4Addr::from(ip).into(), port(8)?)))
+        }
+        // AF_INET6
+        2 if addresses.len() >= 36 => {
+            let ip: [u8; 16] = addresses[..16].try_into().ok()?;
+            Some(Some(SocketAddr::new(Ipv6Addr::from(ip).into(), port(32)?)))
+        }
+        // AF_UNSPEC and AF_UNIX carry no address worth reporting
+        0 | 3 => Some(None),
+        _ => None,
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read(mut input: &[u8]) -> (io::Result<Option<SocketAddr>>, Vec<u8>) {
+        let result = read_header(&mut input).await;
+        (result, input.to_vec())
+    }
+
+    #[tokio::test]
+    async fn v1() {
+        let (address, rest) =
+            read(b"PROXY TCP6 2001:db8::1 2001:db8::2 56324 6667\r\nNICK a\r\n").await;
+        assert_eq!(
+            address.unwrap(),
+            Some("[2001:db8::1]:56324".parse().unwrap())
+        );
+        assert_eq!(rest, b"NICK a\r\n");
+
+        let (address, _) = read(b"PROXY UNKNOWN ffff::1 ffff::2 1 2\r\n").await;
+        assert_eq!(address.unwrap(), None);
+
+        for input in [
+            &b"NICK a\r\n"[..],
+            b"PROXY TCP4 192.0.2.1\r\n",
+            b"PROXY TCP4 192.0.2.1 198.51.100.1 99999 6667\r\n",
+            &[b'P'; 200],
+        ] {
+            assert!(read(input).await.0.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn v2() {
+        let mut input = V2_SIGNATURE.to_vec();
+        input.extend([0x21, 0x11, 0, 12]);
+        input.extend([192, 0, 2, 1, 198, 51, 100, 1, 0xDC, 0x04, 0x1A, 0x0B]);
+        input.extend(b"NICK a\r\n");
+        let (address, rest) = read(&input).await;
+        assert_eq!(address.unwrap(), Some("192.0.2.1:56324".parse().unwrap()));
+        assert_eq!(rest, b"NICK a\r\n");
+
+        let mut input = V2_SIGNATURE.to_vec();
+        input.extend([0x20, 0x00, 0, 0]);
+        assert_eq!(read(&input).await.0.unwrap(), None);
+
+        let mut input = V2_SIGNATURE.to_vec();
+        input.extend([0x21, 0x11, 0, 4, 192, 0, 2, 1]);
+        assert!(read(&input).await.0.is_err());
+    }
+}
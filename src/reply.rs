@@ -0,0 +1,2526 @@
+//! Numeric replies, from RFC 1459 and RFC 2812 along with the common modern additions.
+//!
+//! Every numeric is sent from the server to a single client, with that client's nick as the first
+//! parameter. Variants only hold what follows it, so the same reply can be rendered for whoever
+//! ends up receiving it.
+//!
+//! Numerics the RFCs only list as reserved or unused, with no format given, are left out:
+//! RPL_TRACERECONNECT (210), RPL_STATSQLINE (217), RPL_SERVICEINFO (231), RPL_ENDOFSERVICES
+//! (232), RPL_SERVICE (233), RPL_STATSVLINE (240), RPL_STATSPING (246), RPL_STATSBLINE (247),
+//! RPL_STATSDLINE (250), RPL_NONE (300), RPL_WHOISCHANOP (316), RPL_KILLDONE (361), RPL_CLOSING
+//! (362), RPL_CLOSEEND (363), RPL_INFOSTART (373), RPL_MYPORTIS (384) and ERR_NOSERVICEHOST (492).
+
+use crate::message::Message;
+use crate::structs::SerializeError;
+
+#[allow(non_camel_case_types)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Reply {
+    // Connection registration
+    RPL_WELCOME {
+        /// The client's `nick!user@host`.
+        prefix: String,
+    },
+    RPL_YOURHOST {
+        server_name: String,
+        version: String,
+    },
+    RPL_CREATED {
+        created_at: String,
+    },
+    RPL_MYINFO {
+        server_name: String,
+        version: String,
+        user_modes: String,
+        channel_modes: String,
+    },
+    RPL_ISUPPORT {
+        tokens: Vec<String>,
+    },
+    RPL_BOUNCE {
+        server_name: String,
+        port: u16,
+    },
+
+    // Replies to TRACE, STATS and the other server queries
+    RPL_TRACELINK {
+        version: String,
+        destination: String,
+        next_server: String,
+        protocol_version: String,
+        uptime: u64,
+        backstream_sendq: u64,
+        upstream_sendq: u64,
+    },
+    RPL_TRACECONNECTING {
+        class: String,
+        server_name: String,
+    },
+    RPL_TRACEHANDSHAKE {
+        class: String,
+        server_name: String,
+    },
+    RPL_TRACEUNKNOWN {
+        class: String,
+        ip: String,
+    },
+    RPL_TRACEOPERATOR {
+        class: String,
+        target: String,
+    },
+    RPL_TRACEUSER {
+        class: String,
+        target: String,
+    },
+    RPL_TRACESERVER {
+        class: String,
+        servers: u32,
+        clients: u32,
+        server_name: String,
+        /// Who made the link, as `nick!user@host` or `*!*@server`.
+        link_by: String,
+        protocol_version: String,
+    },
+    RPL_TRACESERVICE {
+        class: String,
+        name: String,
+        service_type: String,
+        active_type: String,
+    },
+    RPL_TRACENEWTYPE {
+        new_type: String,
+        client_name: String,
+    },
+    RPL_TRACECLASS {
+        class: String,
+        count: u32,
+    },
+    RPL_STATSLINKINFO {
+        link_name: String,
+        sendq: u64,
+        sent_messages: u64,
+        sent_kbytes: u64,
+        received_messages: u64,
+        received_kbytes: u64,
+        time_open: u64,
+    },
+    RPL_STATSCOMMANDS {
+        command: String,
+        count: u64,
+        bytes: u64,
+        remote_count: u64,
+    },
+    RPL_STATSCLINE {
+        host: String,
+        name: String,
+        port: u16,
+        class: String,
+    },
+    RPL_STATSNLINE {
+        host: String,
+        name: String,
+        port: u16,
+        class: String,
+    },
+    RPL_STATSILINE {
+        ip_mask: String,
+        host_mask: String,
+        port: u16,
+        class: String,
+    },
+    RPL_STATSKLINE {
+        host: String,
+        username: String,
+        port: u16,
+        class: String,
+    },
+    RPL_STATSYLINE {
+        class: String,
+        /// In seconds.
+        ping_frequency: u32,
+        /// In seconds.
+        connect_frequency: u32,
+        max_sendq: u64,
+    },
+    RPL_ENDOFSTATS {
+        letter: char,
+    },
+    RPL_UMODEIS {
+        modes: String,
+    },
+    RPL_SERVLIST {
+        name: String,
+        server_name: String,
+        mask: String,
+        service_type: String,
+        hopcount: u32,
+        info: String,
+    },
+    RPL_SERVLISTEND {
+        mask: String,
+        service_type: String,
+    },
+    RPL_STATSLLINE {
+        hostmask: String,
+        server_name: String,
+        max_depth: u32,
+    },
+    RPL_STATSUPTIME {
+        /// In seconds.
+        uptime: u64,
+    },
+    RPL_STATSOLINE {
+        hostmask: String,
+        name: String,
+    },
+    RPL_STATSHLINE {
+        hostmask: String,
+        server_name: String,
+    },
+    RPL_LUSERCLIENT {
+        users: usize,
+        services: usize,
+        servers: usize,
+    },
+    RPL_LUSEROP {
+        count: usize,
+    },
+    RPL_LUSERUNKNOWN {
+        count: usize,
+    },
+    RPL_LUSERCHANNELS {
+        count: usize,
+    },
+    RPL_LUSERME {
+        clients: usize,
+        servers: usize,
+    },
+    RPL_ADMINME {
+        server_name: String,
+    },
+    RPL_ADMINLOC1 {
+        info: String,
+    },
+    RPL_ADMINLOC2 {
+        info: String,
+    },
+    RPL_ADMINEMAIL {
+        info: String,
+    },
+    RPL_TRACELOG {
+        file: String,
+        debug_level: u32,
+    },
+    RPL_TRACEEND {
+        server_name: String,
+        version: String,
+    },
+    RPL_TRYAGAIN {
+        command: String,
+    },
+
+    // Replies to commands about users and channels
+    RPL_WHOISCERTFP {
+        target: String,
+        certfp: String,
+    },
+    RPL_AWAY {
+        target: String,
+        message: String,
+    },
+    RPL_USERHOST {
+        /// Each is `nick[*]=(+|-)user@host`.
+        replies: Vec<String>,
+    },
+    RPL_ISON {
+        nicks: Vec<String>,
+    },
+    RPL_UNAWAY,
+    RPL_NOWAWAY,
+    RPL_WHOISUSER {
+        target: String,
+        user: String,
+        host: String,
+        realname: String,
+    },
+    RPL_WHOISSERVER {
+        target: String,
+        server_name: String,
+        server_info: String,
+    },
+    RPL_WHOISOPERATOR {
+        target: String,
+    },
+    RPL_WHOWASUSER {
+        target: String,
+        user: String,
+        host: String,
+        realname: String,
+    },
+    RPL_ENDOFWHO {
+        mask: String,
+    },
+    RPL_WHOISIDLE {
+        target: String,
+        /// Seconds since the user last spoke.
+        idle: u64,
+        /// When the user connected, as a Unix timestamp.
+        signon: u64,
+    },
+    RPL_ENDOFWHOIS {
+        target: String,
+    },
+    RPL_WHOISCHANNELS {
+        target: String,
+        /// Each channel is prefixed by the user's status in it, if any.
+        channels: Vec<String>,
+    },
+    RPL_LISTSTART,
+    RPL_LIST {
+        channel: String,
+        visible: usize,
+        topic: String,
+    },
+    RPL_LISTEND,
+    RPL_CHANNELMODEIS {
+        channel: String,
+        /// The mode string followed by any mode parameters.
+        modes: Vec<String>,
+    },
+    RPL_UNIQOPIS {
+        channel: String,
+        target: String,
+    },
+    RPL_CREATIONTIME {
+        channel: String,
+        created_at: u64,
+    },
+    RPL_WHOISACCOUNT {
+        target: String,
+        account: String,
+    },
+    RPL_NOTOPIC {
+        channel: String,
+    },
+    RPL_TOPIC {
+        channel: String,
+        topic: String,
+    },
+    RPL_TOPICWHOTIME {
+        channel: String,
+        set_by: String,
+        set_at: u64,
+    },
+    RPL_INVITING {
+        target: String,
+        channel: String,
+    },
+    RPL_SUMMONING {
+        user: String,
+    },
+    RPL_INVITELIST {
+        channel: String,
+        mask: String,
+    },
+    RPL_ENDOFINVITELIST {
+        channel: String,
+    },
+    RPL_EXCEPTLIST {
+        channel: String,
+        mask: String,
+    },
+    RPL_ENDOFEXCEPTLIST {
+        channel: String,
+    },
+    RPL_VERSION {
+        version: String,
+        server_name: String,
+        comments: String,
+    },
+    RPL_WHOREPLY {
+        channel: String,
+        user: String,
+        host: String,
+        server_name: String,
+        target: String,
+        /// `H` or `G` for here or gone, then `*` for opers and the channel status prefix.
+        flags: String,
+        hopcount: u32,
+        realname: String,
+    },
+    RPL_NAMREPLY {
+        channel: String,
+        names: Vec<String>,
+    },
+    RPL_LINKS {
+        mask: String,
+        server_name: String,
+        hopcount: u32,
+        server_info: String,
+    },
+    RPL_ENDOFLINKS {
+        mask: String,
+    },
+    RPL_ENDOFNAMES {
+        channel: String,
+    },
+    RPL_BANLIST {
+        channel: String,
+        mask: String,
+    },
+    RPL_ENDOFBANLIST {
+        channel: String,
+    },
+    RPL_ENDOFWHOWAS {
+        target: String,
+    },
+    RPL_INFO {
+        line: String,
+    },
+    RPL_MOTD {
+        line: String,
+    },
+    RPL_ENDOFINFO,
+    RPL_MOTDSTART {
+        server_name: String,
+    },
+    RPL_ENDOFMOTD,
+    RPL_YOUREOPER,
+    RPL_REHASHING {
+        file: String,
+    },
+    RPL_YOURESERVICE {
+        name: String,
+    },
+    RPL_TIME {
+        server_name: String,
+        time: String,
+    },
+    RPL_USERSSTART,
+    RPL_USERS {
+        user: String,
+        tty: String,
+        host: String,
+    },
+    RPL_ENDOFUSERS,
+    RPL_NOUSERS,
+    RPL_WHOISSECURE {
+        target: String,
+    },
+
+    // Errors
+    ERR_UNKNOWNERROR {
+        command: String,
+        message: String,
+    },
+    ERR_NOSUCHNICK {
+        target: String,
+    },
+    ERR_NOSUCHSERVER {
+        server_name: String,
+    },
+    ERR_NOSUCHCHANNEL {
+        channel: String,
+    },
+    ERR_CANNOTSENDTOCHAN {
+        channel: String,
+    },
+    ERR_TOOMANYCHANNELS {
+        channel: String,
+    },
+    ERR_WASNOSUCHNICK {
+        target: String,
+    },
+    ERR_TOOMANYTARGETS {
+        target: String,
+        error_code: String,
+        message: String,
+    },
+    ERR_NOSUCHSERVICE {
+        name: String,
+    },
+    ERR_NOORIGIN,
+    ERR_INVALIDCAPCMD {
+        subcommand: String,
+    },
+    ERR_NORECIPIENT {
+        command: String,
+    },
+    ERR_NOTEXTTOSEND,
+    ERR_NOTOPLEVEL {
+        mask: String,
+    },
+    ERR_WILDTOPLEVEL {
+        mask: String,
+    },
+    ERR_BADMASK {
+        mask: String,
+    },
+    ERR_INPUTTOOLONG,
+    ERR_UNKNOWNCOMMAND {
+        command: String,
+    },
+    ERR_NOMOTD,
+    ERR_NOADMININFO {
+        server_name: String,
+    },
+    ERR_FILEERROR {
+        operation: String,
+        file: String,
+    },
+    ERR_NONICKNAMEGIVEN,
+    ERR_ERRONEUSNICKNAME {
+        new_nick: String,
+    },
+    ERR_NICKNAMEINUSE {
+        new_nick: String,
+    },
+    ERR_NICKCOLLISION {
+        new_nick: String,
+        user: String,
+        host: String,
+    },
+    ERR_UNAVAILRESOURCE {
+        /// The nick or channel.
+        name: String,
+    },
+    ERR_USERNOTINCHANNEL {
+        target: String,
+        channel: String,
+    },
+    ERR_NOTONCHANNEL {
+        channel: String,
+    },
+    ERR_USERONCHANNEL {
+        target: String,
+        channel: String,
+    },
+    ERR_NOLOGIN {
+        user: String,
+    },
+    ERR_SUMMONDISABLED,
+    ERR_USERSDISABLED,
+    ERR_NOTREGISTERED,
+    ERR_NEEDMOREPARAMS {
+        command: String,
+    },
+    ERR_ALREADYREGISTRED,
+    ERR_NOPERMFORHOST,
+    ERR_PASSWDMISMATCH,
+    ERR_YOUREBANNEDCREEP {
+        reason: String,
+    },
+    ERR_YOUWILLBEBANNED,
+    ERR_KEYSET {
+        channel: String,
+    },
+    ERR_CHANNELISFULL {
+        channel: String,
+    },
+    ERR_UNKNOWNMODE {
+        mode: char,
+        channel: String,
+    },
+    ERR_INVITEONLYCHAN {
+        channel: String,
+    },
+    ERR_BANNEDFROMCHAN {
+        channel: String,
+    },
+    ERR_BADCHANNELKEY {
+        channel: String,
+    },
+    ERR_BADCHANMASK {
+        channel: String,
+    },
+    ERR_NOCHANMODES {
+        channel: String,
+    },
+    ERR_BANLISTFULL {
+        channel: String,
+        mode: char,
+    },
+    ERR_NOPRIVILEGES,
+    ERR_CHANOPRIVSNEEDED {
+        channel: String,
+    },
+    ERR_CANTKILLSERVER,
+    ERR_RESTRICTED,
+    ERR_UNIQOPPRIVSNEEDED,
+    ERR_NOOPERHOST,
+    ERR_UMODEUNKNOWNFLAG,
+    ERR_USERSDONTMATCH,
+
+    // SASL, see https://ircv3.net/specs/extensions/sasl-3.1
+    RPL_LOGGEDIN {
+        prefix: String,
+        account: String,
+    },
+    RPL_LOGGEDOUT {
+        prefix: String,
+    },
+    ERR_NICKLOCKED,
+    RPL_SASLSUCCESS,
+    ERR_SASLFAIL,
+    ERR_SASLTOOLONG,
+    ERR_SASLABORTED,
+    ERR_SASLALREADY,
+    RPL_SASLMECHS {
+        mechanisms: String,
+    },
+}
+
+impl Reply {
+    /// The three digit numeric sent as the command.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Reply::RPL_WELCOME { .. } => "001",
+            Reply::RPL_YOURHOST { .. } => "002",
+            Reply::RPL_CREATED { .. } => "003",
+            Reply::RPL_MYINFO { .. } => "004",
+            Reply::RPL_ISUPPORT { .. } => "005",
+            Reply::RPL_BOUNCE { .. } => "010",
+            Reply::RPL_TRACELINK { .. } => "200",
+            Reply::RPL_TRACECONNECTING { .. } => "201",
+            Reply::RPL_TRACEHANDSHAKE { .. } => "202",
+            Reply::RPL_TRACEUNKNOWN { .. } => "203",
+            Reply::RPL_TRACEOPERATOR { .. } => "204",
+            Reply::RPL_TRACEUSER { .. } => "205",
+            Reply::RPL_TRACESERVER { .. } => "206",
+            Reply::RPL_TRACESERVICE { .. } => "207",
+            Reply::RPL_TRACENEWTYPE { .. } => "208",
+            Reply::RPL_TRACECLASS { .. } => "209",
+            Reply::RPL_STATSLINKINFO { .. } => "211",
+            Reply::RPL_STATSCOMMANDS { .. } => "212",
+            Reply::RPL_STATSCLINE { .. } => "213",
+            Reply::RPL_STATSNLINE { .. } => "214",
+            Reply::RPL_STATSILINE { .. } => "215",
+            Reply::RPL_STATSKLINE { .. } => "216",
+            Reply::RPL_STATSYLINE { .. } => "218",
+            Reply::RPL_ENDOFSTATS { .. } => "219",
+            Reply::RPL_UMODEIS { .. } => "221",
+            Reply::RPL_SERVLIST { .. } => "234",
+            Reply::RPL_SERVLISTEND { .. } => "235",
+            Reply::RPL_STATSLLINE { .. } => "241",
+            Reply::RPL_STATSUPTIME { .. } => "242",
+            Reply::RPL_STATSOLINE { .. } => "243",
+            Reply::RPL_STATSHLINE { .. } => "244",
+            Reply::RPL_LUSERCLIENT { .. } => "251",
+            Reply::RPL_LUSEROP { .. } => "252",
+            Reply::RPL_LUSERUNKNOWN { .. } => "253",
+            Reply::RPL_LUSERCHANNELS { .. } => "254",
+            Reply::RPL_LUSERME { .. } => "255",
+            Reply::RPL_ADMINME { .. } => "256",
+            Reply::RPL_ADMINLOC1 { .. } => "257",
+            Reply::RPL_ADMINLOC2 { .. } => "258",
+            Reply::RPL_ADMINEMAIL { .. } => "259",
+            Reply::RPL_TRACELOG { .. } => "261",
+            Reply::RPL_TRACEEND { .. } => "262",
+            Reply::RPL_TRYAGAIN { .. } => "263",
+            Reply::RPL_WHOISCERTFP { .. } => "276",
+            Reply::RPL_AWAY { .. } => "301",
+            Reply::RPL_USERHOST { .. } => "302",
+            Reply::RPL_ISON { .. } => "303",
+            Reply::RPL_UNAWAY => "305",
+            Reply::RPL_NOWAWAY => "306",
+            Reply::RPL_WHOISUSER { .. } => "311",
+            Reply::RPL_WHOISSERVER { .. } => "312",
+            Reply::RPL_WHOISOPERATOR { .. } => "313",
+            Reply::RPL_WHOWASUSER { .. } => "314",
+            Reply::RPL_ENDOFWHO { .. } => "315",
+            Reply::RPL_WHOISIDLE { .. } => "317",
+            Reply::RPL_ENDOFWHOIS { .. } => "318",
+            Reply::RPL_WHOISCHANNELS { .. } => "319",
+            Reply::RPL_LISTSTART => "321",
+            Reply::RPL_LIST { .. } => "322",
+            Reply::RPL_LISTEND => "323",
+            Reply::RPL_CHANNELMODEIS { .. } => "324",
+            Reply::RPL_UNIQOPIS { .. } => "325",
+            Reply::RPL_CREATIONTIME { .. } => "329",
+            Reply::RPL_WHOISACCOUNT { .. } => "330",
+            Reply::RPL_NOTOPIC { .. } => "331",
+            Reply::RPL_TOPIC { .. } => "332",
+            Reply::RPL_TOPICWHOTIME { .. } => "333",
+            Reply::RPL_INVITING { .. } => "341",
+            Reply::RPL_SUMMONING { .. } => "342",
+            Reply::RPL_INVITELIST { .. } => "346",
+            Reply::RPL_ENDOFINVITELIST { .. } => "347",
+            Reply::RPL_EXCEPTLIST { .. } => "348",
+            Reply::RPL_ENDOFEXCEPTLIST { .. } => "349",
+            Reply::RPL_VERSION { .. } => "351",
+            Reply::RPL_WHOREPLY { .. } => "352",
+            Reply::RPL_NAMREPLY { .. } => "353",
+            Reply::RPL_LINKS { .. } => "364",
+            Reply::RPL_ENDOFLINKS { .. } => "365",
+            Reply::RPL_ENDOFNAMES { .. } => "366",
+            Reply::RPL_BANLIST { .. } => "367",
+            Reply::RPL_ENDOFBANLIST { .. } => "368",
+            Reply::RPL_ENDOFWHOWAS { .. } => "369",
+            Reply::RPL_INFO { .. } => "371",
+            Reply::RPL_MOTD { .. } => "372",
+            Reply::RPL_ENDOFINFO => "374",
+            Reply::RPL_MOTDSTART { .. } => "375",
+            Reply::RPL_ENDOFMOTD => "376",
+            Reply::RPL_YOUREOPER => "381",
+            Reply::RPL_REHASHING { .. } => "382",
+            Reply::RPL_YOURESERVICE { .. } => "383",
+            Reply::RPL_TIME { .. } => "391",
+            Reply::RPL_USERSSTART => "392",
+            Reply::RPL_USERS { .. } => "393",
+            Reply::RPL_ENDOFUSERS => "394",
+            Reply::RPL_NOUSERS => "395",
+            Reply::RPL_WHOISSECURE { .. } => "671",
+            Reply::ERR_UNKNOWNERROR { .. } => "400",
+            Reply::ERR_NOSUCHNICK { .. } => "401",
+            Reply::ERR_NOSUCHSERVER { .. } => "402",
+            Reply::ERR_NOSUCHCHANNEL { .. } => "403",
+            Reply::ERR_CANNOTSENDTOCHAN { .. } => "404",
+            Reply::ERR_TOOMANYCHANNELS { .. } => "405",
+            Reply::ERR_WASNOSUCHNICK { .. } => "406",
+            Reply::ERR_TOOMANYTARGETS { .. } => "407",
+            Reply::ERR_NOSUCHSERVICE { .. } => "408",
+            Reply::ERR_NOORIGIN => "409",
+            Reply::ERR_INVALIDCAPCMD { .. } => "410",
+            Reply::ERR_NORECIPIENT { .. } => "411",
+            Reply::ERR_NOTEXTTOSEND => "412",
+            Reply::ERR_NOTOPLEVEL { .. } => "413",
+            Reply::ERR_WILDTOPLEVEL { .. } => "414",
+            Reply::ERR_BADMASK { .. } => "415",
+            Reply::ERR_INPUTTOOLONG => "417",
+            Reply::ERR_UNKNOWNCOMMAND { .. } => "421",
+            Reply::ERR_NOMOTD => "422",
+            Reply::ERR_NOADMININFO { .. } => "423",
+            Reply::ERR_FILEERROR { .. } => "424",
+            Reply::ERR_NONICKNAMEGIVEN => "431",
+            Reply::ERR_ERRONEUSNICKNAME { .. } => "432",
+            Reply::ERR_NICKNAMEINUSE { .. } => "433",
+            Reply::ERR_NICKCOLLISION { .. } => "436",
+            Reply::ERR_UNAVAILRESOURCE { .. } => "437",
+            Reply::ERR_USERNOTINCHANNEL { .. } => "441",
+            Reply::ERR_NOTONCHANNEL { .. } => "442",
+            Reply::ERR_USERONCHANNEL { .. } => "443",
+            Reply::ERR_NOLOGIN { .. } => "444",
+            Reply::ERR_SUMMONDISABLED => "445",
+            Reply::ERR_USERSDISABLED => "446",
+            Reply::ERR_NOTREGISTERED => "451",
+            Reply::ERR_NEEDMOREPARAMS { .. } => "461",
+            Reply::ERR_ALREADYREGISTRED => "462",
+            Reply::ERR_NOPERMFORHOST => "463",
+            Reply::ERR_PASSWDMISMATCH => "464",
+            Reply::ERR_YOUREBANNEDCREEP { .. } => "465",
+            Reply::ERR_YOUWILLBEBANNED => "466",
+            Reply::ERR_KEYSET { .. } => "467",
+            Reply::ERR_CHANNELISFULL { .. } => "471",
+            Reply::ERR_UNKNOWNMODE { .. } => "472",
+            Reply::ERR_INVITEONLYCHAN { .. } => "473",
+            Reply::ERR_BANNEDFROMCHAN { .. } => "474",
+            Reply::ERR_BADCHANNELKEY { .. } => "475",
+            Reply::ERR_BADCHANMASK { .. } => "476",
+            Reply::ERR_NOCHANMODES { .. } => "477",
+            Reply::ERR_BANLISTFULL { .. } => "478",
+            Reply::ERR_NOPRIVILEGES => "481",
+            Reply::ERR_CHANOPRIVSNEEDED { .. } => "482",
+            Reply::ERR_CANTKILLSERVER => "483",
+            Reply::ERR_RESTRICTED => "484",
+            Reply::ERR_UNIQOPPRIVSNEEDED => "485",
+            Reply::ERR_NOOPERHOST => "491",
+            Reply::ERR_UMODEUNKNOWNFLAG => "501",
+            Reply::ERR_USERSDONTMATCH => "502",
+            Reply::RPL_LOGGEDIN { .. } => "900",
+            Reply::RPL_LOGGEDOUT { .. } => "901",
+            Reply::ERR_NICKLOCKED => "902",
+            Reply::RPL_SASLSUCCESS => "903",
+            Reply::ERR_SASLFAIL => "904",
+            Reply::ERR_SASLTOOLONG => "905",
+            Reply::ERR_SASLABORTED => "906",
+            Reply::ERR_SASLALREADY => "907",
+            Reply::RPL_SASLMECHS { .. } => "908",
+        }
+    }
+
+    /// The parameters that follow the recipient's nick. The last one is sent as the trailing
+    /// parameter.
+    pub fn parameters(&self) -> Vec<String> {
+        match self {
+            Reply::RPL_WELCOME { prefix } => {
+                vec![format!("Welcome to the network {}", prefix)]
+            }
+            Reply::RPL_YOURHOST {
+                server_name,
+                version,
+            } => vec![format!(
+                "Your host is {}, running ircd version {}",
+                server_name, version
+            )],
+            Reply::RPL_CREATED { created_at } => {
+                vec![format!("This server was created {}", created_at)]
+            }
+            Reply::RPL_MYINFO {
+                server_name,
+                version,
+                user_modes,
+                channel_modes,
+            } => vec![
+                server_name.clone(),
+                version.clone(),
+                user_modes.clone(),
+                channel_modes.clone(),
+            ],
+            Reply::RPL_ISUPPORT { tokens } => {
+                let mut parameters = tokens.clone();
+                parameters.push("are supported by this server".to_owned());
+                parameters
+            }
+            Reply::RPL_BOUNCE { server_name, port } => vec![
+                server_name.clone(),
+                port.to_string(),
+                "Try another server".to_owned(),
+            ],
+
+            Reply::RPL_TRACELINK {
+                version,
+                destination,
+                next_server,
+                protocol_version,
+                uptime,
+                backstream_sendq,
+                upstream_sendq,
+            } => vec![
+                "Link".to_owned(),
+                version.clone(),
+                destination.clone(),
+                next_server.clone(),
+                format!("V{}", protocol_version),
+                uptime.to_string(),
+                backstream_sendq.to_string(),
+                upstream_sendq.to_string(),
+            ],
+            Reply::RPL_TRACECONNECTING { class, server_name } => {
+                vec!["Try.".to_owned(), class.clone(), server_name.clone()]
+            }
+            Reply::RPL_TRACEHANDSHAKE { class, server_name } => {
+                vec!["H.S.".to_owned(), class.clone(), server_name.clone()]
+            }
+            Reply::RPL_TRACEUNKNOWN { class, ip } => {
+                vec!["????".to_owned(), class.clone(), ip.clone()]
+            }
+            Reply::RPL_TRACEOPERATOR { class, target } => {
+                vec!["Oper".to_owned(), class.clone(), target.clone()]
+            }
+            Reply::RPL_TRACEUSER { class, target } => {
+                vec!["User".to_owned(), class.clone(), target.clone()]
+            }
+            Reply::RPL_TRACESERVER {
+                class,
+                servers,
+                clients,
+                server_name,
+                link_by,
+                protocol_version,
+            } => vec![
+                "Serv".to_owned(),
+                class.clone(),
+                format!("{}S", servers),
+                format!("{}C", clients),
+                server_name.clone(),
+                link_by.clone(),
+                format!("V{}", protocol_version),
+            ],
+            Reply::RPL_TRACESERVICE {
+                class,
+                name,
+                service_type,
+                active_type,
+            } => vec![
+                "Service".to_owned(),
+                class.clone(),
+                name.clone(),
+                service_type.clone(),
+                active_type.clone(),
+            ],
+            Reply::RPL_TRACENEWTYPE {
+                new_type,
+                client_name,
+            } => vec![new_type.clone(), "0".to_owned(), client_name.clone()],
+            Reply::RPL_TRACECLASS { class, count } => {
+                vec!["Class".to_owned(), class.clone(), count.to_string()]
+            }
+            Reply::RPL_STATSLINKINFO {
+                link_name,
+                sendq,
+                sent_messages,
+                sent_kbytes,
+                received_messages,
+                received_kbytes,
+                time_open,
+            } => vec![
+                link_name.clone(),
+                sendq.to_string(),
+                sent_messages.to_string(),
+                sent_kbytes.to_string(),
+                received_messages.to_string(),
+                received_kbytes.to_string(),
+                time_open.to_string(),
+            ],
+            Reply::RPL_STATSCOMMANDS {
+                command,
+                count,
+                bytes,
+                remote_count,
+            } => vec![
+                command.clone(),
+                count.to_string(),
+                bytes.to_string(),
+                remote_count.to_string(),
+            ],
+            Reply::RPL_STATSCLINE {
+                host,
+                name,
+                port,
+                class,
+            } => vec![
+                "C".to_owned(),
+                host.clone(),
+                "*".to_owned(),
+                name.clone(),
+                port.to_string(),
+                class.clone(),
+            ],
+            Reply::RPL_STATSNLINE {
+                host,
+                name,
+                port,
+                class,
+            } => vec![
+                "N".to_owned(),
+                host.clone(),
+                "*".to_owned(),
+                name.clone(),
+                port.to_string(),
+                class.clone(),
+            ],
+            Reply::RPL_STATSILINE {
+                ip_mask,
+                host_mask,
+                port,
+                class,
+            } => vec![
+                "I".to_owned(),
+                ip_mask.clone(),
+                "*".to_owned(),
+                host_mask.clone(),
+                port.to_string(),
+                class.clone(),
+            ],
+            Reply::RPL_STATSKLINE {
+                host,
+                username,
+                port,
+                class,
+            } => vec![
+                "K".to_owned(),
+                host.clone(),
+                "*".to_owned(),
+                username.clone(),
+                port.to_string(),
+                class.clone(),
+            ],
+            Reply::RPL_STATSYLINE {
+                class,
+                ping_frequency,
+                connect_frequency,
+                max_sendq,
+            } => vec![
+                "Y".to_owned(),
+                class.clone(),
+                ping_frequency.to_string(),
+                connect_frequency.to_string(),
+                max_sendq.to_string(),
+            ],
+            Reply::RPL_ENDOFSTATS { letter } => {
+                vec![letter.to_string(), "End of STATS report".to_owned()]
+            }
+            Reply::RPL_UMODEIS { modes } => vec![modes.clone()],
+            Reply::RPL_SERVLIST {
+                name,
+                server_name,
+                mask,
+                service_type,
+                hopcount,
+                info,
+            } => vec![
+                name.clone(),
+                server_name.clone(),
+                mask.clone(),
+                service_type.clone(),
+                hopcount.to_string(),
+                info.clone(),
+            ],
+            Reply::RPL_SERVLISTEND { mask, service_type } => vec![
+                mask.clone(),
+                service_type.clone(),
+                "End of service listing".to_owned(),
+            ],
+            Reply::RPL_STATSLLINE {
+                hostmask,
+                server_name,
+                max_depth,
+            } => vec![
+                "L".to_owned(),
+                hostmask.clone(),
+                "*".to_owned(),
+                server_name.clone(),
+                max_depth.to_string(),
+            ],
+            Reply::RPL_STATSUPTIME { uptime } => vec![format!(
+                "Server Up {} days {}:{:02}:{:02}",
+                uptime / 86400,
+                uptime / 3600 % 24,
+                uptime / 60 % 60,
+                uptime % 60
+            )],
+            Reply::RPL_STATSOLINE { hostmask, name } => vec![
+                "O".to_owned(),
+                hostmask.clone(),
+                "*".to_owned(),
+                name.clone(),
+            ],
+            Reply::RPL_STATSHLINE {
+                hostmask,
+                server_name,
+            } => vec![
+                "H".to_owned(),
+                hostmask.clone(),
+                "*".to_owned(),
+                server_name.clone(),
+            ],
+            Reply::RPL_LUSERCLIENT {
+                users,
+                services,
+                servers,
+            } => vec![format!(
+                "There are {} users and {} services on {} servers",
+                users, services, servers
+            )],
+            Reply::RPL_LUSEROP { count } => {
+                vec![count.to_string(), "operator(s) online".to_owned()]
+            }
+            Reply::RPL_LUSERUNKNOWN { count } => {
+                vec![count.to_string(), "unknown connection(s)".to_owned()]
+            }
+            Reply::RPL_LUSERCHANNELS { count } => {
+                vec![count.to_string(), "channels formed".to_owned()]
+            }
+            Reply::RPL_LUSERME { clients, servers } => vec![format!(
+                "I have {} clients and {} servers",
+                clients, servers
+            )],
+            Reply::RPL_ADMINME { server_name } => {
+                vec![server_name.clone(), "Administrative info".to_owned()]
+            }
+            Reply::RPL_ADMINLOC1 { info }
+            | Reply::RPL_ADMINLOC2 { info }
+            | Reply::RPL_ADMINEMAIL { info } => vec![info.clone()],
+            Reply::RPL_TRACELOG { file, debug_level } => {
+                vec!["File".to_owned(), file.clone(), debug_level.to_string()]
+            }
+            Reply::RPL_TRACEEND {
+                server_name,
+                version,
+            } => vec![
+                server_name.clone(),
+                version.clone(),
+                "End of TRACE".to_owned(),
+            ],
+            Reply::RPL_TRYAGAIN { command } => vec![
+                command.clone(),
+                "Please wait a while and try again.".to_owned(),
+            ],
+
+            Reply::RPL_WHOISCERTFP { target, certfp } => vec![
+                target.clone(),
+                format!("has client certificate fingerprint {}", certfp),
+            ],
+            Reply::RPL_AWAY { target, message } => vec![target.clone(), message.clone()],
+            Reply::RPL_USERHOST { replies } => vec![replies.join(" ")],
+            Reply::RPL_ISON { nicks } => vec![nicks.join(" ")],
+            Reply::RPL_UNAWAY => vec!["You are no longer marked as being away".to_owned()],
+            Reply::RPL_NOWAWAY => vec!["You have been marked as being away".to_owned()],
+            Reply::RPL_WHOISUSER {
+                target,
+                user,
+                host,
+                realname,
+            }
+            | Reply::RPL_WHOWASUSER {
+                target,
+                user,
+                host,
+                realname,
+            } => vec![
+                target.clone(),
+                user.clone(),
+                host.clone(),
+                "*".to_owned(),
+                realname.clone(),
+            ],
+            Reply::RPL_WHOISSERVER {
+                target,
+                server_name,
+                server_info,
+            } => vec![target.clone(), server_name.clone(), server_info.clone()],
+            Reply::RPL_WHOISOPERATOR { target } => {
+                vec![target.clone(), "is an IRC operator".to_owned()]
+            }
+            Reply::RPL_ENDOFWHO { mask } => vec![mask.clone(), "End of /WHO list".to_owned()],
+            Reply::RPL_WHOISIDLE {
+                target,
+                idle,
+                signon,
+            } => vec![
+                target.clone(),
+                idle.to_string(),
+                signon.to_string(),
+                "seconds idle, signon time".to_owned(),
+            ],
+            Reply::RPL_ENDOFWHOIS { target } => {
+                vec![target.clone(), "End of /WHOIS list".to_owned()]
+            }
+            Reply::RPL_WHOISCHANNELS { target, channels } => {
+                vec![target.clone(), channels.join(" ")]
+            }
+            Reply::RPL_LISTSTART => vec!["Channel".to_owned(), "Users  Name".to_owned()],
+            Reply::RPL_LIST {
+                channel,
+                visible,
+                topic,
+            } => vec![channel.clone(), visible.to_string(), topic.clone()],
+            Reply::RPL_LISTEND => vec!["End of /LIST".to_owned()],
+            Reply::RPL_CHANNELMODEIS { channel, modes } => {
+                let mut parameters = vec![channel.clone()];
+                parameters.extend(modes.iter().cloned());
+                parameters
+            }
+            Reply::RPL_UNIQOPIS { channel, target } => vec![channel.clone(), target.clone()],
+            Reply::RPL_CREATIONTIME {
+                channel,
+                created_at,
+            } => vec![channel.clone(), created_at.to_string()],
+            Reply::RPL_WHOISACCOUNT { target, account } => vec![
+                target.clone(),
+                account.clone(),
+                "is logged in as".to_owned(),
+            ],
+            Reply::RPL_NOTOPIC { channel } => vec![channel.clone(), "No topic is set".to_owned()],
+            Reply::RPL_TOPIC { channel, topic } => vec![channel.clone(), topic.clone()],
+            Reply::RPL_TOPICWHOTIME {
+                channel,
+                set_by,
+                set_at,
+            } => vec![channel.clone(), set_by.clone(), set_at.to_string()],
+            // RFC 2812 has the channel first, but every server since sends the nick first and
+            // that's what clients expect
+            Reply::RPL_INVITING { target, channel } => vec![target.clone(), channel.clone()],
+            Reply::RPL_SUMMONING { user } => {
+                vec![user.clone(), "Summoning user to IRC".to_owned()]
+            }
+            Reply::RPL_INVITELIST { channel, mask } | Reply::RPL_EXCEPTLIST { channel, mask } => {
+                vec![channel.clone(), mask.clone()]
+            }
+            Reply::RPL_ENDOFINVITELIST { channel } => {
+                vec![channel.clone(), "End of channel invite list".to_owned()]
+            }
+            Reply::RPL_ENDOFEXCEPTLIST { channel } => {
+                vec![channel.clone(), "End of channel exception list".to_owned()]
+            }
+            Reply::RPL_VERSION {
+                version,
+                server_name,
+                comments,
+            } => vec![version.clone(), server_name.clone(), comments.clone()],
+            Reply::RPL_WHOREPLY {
+                channel,
+                user,
+                host,
+                server_name,
+                target,
+                flags,
+                hopcount,
+                realname,
+            } => vec![
+                channel.clone(),
+                user.clone(),
+                host.clone(),
+                server_name.clone(),
+                target.clone(),
+                flags.clone(),
+                format!("{} {}", hopcount, realname),
+            ],
+            // "=" marks a public channel
+            Reply::RPL_NAMREPLY { channel, names } => {
+                vec!["=".to_owned(), channel.clone(), names.join(" ")]
+            }
+            Reply::RPL_LINKS {
+                mask,
+                server_name,
+                hopcount,
+                server_info,
+            } => vec![
+                mask.clone(),
+                server_name.clone(),
+                format!("{} {}", hopcount, server_info),
+            ],
+            Reply::RPL_ENDOFLINKS { mask } => vec![mask.clone(), "End of /LINKS list".to_owned()],
+            Reply::RPL_ENDOFNAMES { channel } => {
+                vec![channel.clone(), "End of /NAMES list".to_owned()]
+            }
+            Reply::RPL_BANLIST { channel, mask } => vec![channel.clone(), mask.clone()],
+            Reply::RPL_ENDOFBANLIST { channel } => {
+                vec![channel.clone(), "End of channel ban list".to_owned()]
+            }
+            Reply::RPL_ENDOFWHOWAS { target } => {
+                vec![target.clone(), "End of WHOWAS".to_owned()]
+            }
+            Reply::RPL_INFO { line } => vec![line.clone()],
+            Reply::RPL_MOTD { line } => vec![format!("- {}", line)],
+            Reply::RPL_ENDOFINFO => vec!["End of /INFO list".to_owned()],
+            Reply::RPL_MOTDSTART { server_name } => {
+                vec![format!("- {} Message of the day - ", server_name)]
+            }
+            Reply::RPL_ENDOFMOTD => vec!["End of /MOTD command".to_owned()],
+            Reply::RPL_YOUREOPER => vec!["You are now an IRC operator".to_owned()],
+            Reply::RPL_REHASHING { file } => vec![file.clone(), "Rehashing".to_owned()],
+            Reply::RPL_YOURESERVICE { name } => vec![format!("You are service {}", name)],
+            Reply::RPL_TIME { server_name, time } => vec![server_name.clone(), time.clone()],
+            Reply::RPL_USERSSTART => vec!["UserID   Terminal  Host".to_owned()],
+            Reply::RPL_USERS { user, tty, host } => vec![format!("{} {} {}", user, tty, host)],
+            Reply::RPL_ENDOFUSERS => vec!["End of users".to_owned()],
+            Reply::RPL_NOUSERS => vec!["Nobody logged in".to_owned()],
+            Reply::RPL_WHOISSECURE { target } => {
+                vec![target.clone(), "is using a secure connection".to_owned()]
+            }
+
+            Reply::ERR_UNKNOWNERROR { command, message } => vec![command.clone(), message.clone()],
+            Reply::ERR_NOSUCHNICK { target } => {
+                vec![target.clone(), "No such nick/channel".to_owned()]
+            }
+            Reply::ERR_NOSUCHSERVER { server_name } => {
+                vec![server_name.clone(), "No such server".to_owned()]
+            }
+            Reply::ERR_NOSUCHCHANNEL { channel } => {
+                vec![channel.clone(), "No such channel".to_owned()]
+            }
+            Reply::ERR_CANNOTSENDTOCHAN { channel } => {
+                vec![channel.clone(), "Cannot send to channel".to_owned()]
+            }
+            Reply::ERR_TOOMANYCHANNELS { channel } => vec![
+                channel.clone(),
+                "You have joined too many channels".to_owned(),
+            ],
+            Reply::ERR_WASNOSUCHNICK { target } => {
+                vec![target.clone(), "There was no such nickname".to_owned()]
+            }
+            Reply::ERR_TOOMANYTARGETS {
+                target,
+                error_code,
+                message,
+            } => vec![
+                target.clone(),
+                format!("{} recipients. {}", error_code, message),
+            ],
+            Reply::ERR_NOSUCHSERVICE { name } => vec![name.clone(), "No such service".to_owned()],
+            Reply::ERR_NOORIGIN => vec!["No origin specified".to_owned()],
+            Reply::ERR_INVALIDCAPCMD { subcommand } => {
+                vec![subcommand.clone(), "Invalid CAP command".to_owned()]
+            }
+            Reply::ERR_NORECIPIENT { command } => {
+                vec![format!("No recipient given ({})", command)]
+            }
+            Reply::ERR_NOTEXTTOSEND => vec!["No text to send".to_owned()],
+            Reply::ERR_NOTOPLEVEL { mask } => {
+                vec![mask.clone(), "No toplevel domain specified".to_owned()]
+            }
+            Reply::ERR_WILDTOPLEVEL { mask } => {
+                vec![mask.clone(), "Wildcard in toplevel domain".to_owned()]
+            }
+            Reply::ERR_BADMASK { mask } => vec![mask.clone(), "Bad Server/host mask".to_owned()],
+            Reply::ERR_INPUTTOOLONG => vec!["Input line was too long".to_owned()],
+            Reply::ERR_UNKNOWNCOMMAND { command } => {
+                vec![command.clone(), "Unknown command".to_owned()]
+            }
+            Reply::ERR_NOMOTD => vec!["MOTD File is missing".to_owned()],
+            Reply::ERR_NOADMININFO { server_name } => vec![
+                server_name.clone(),
+                "No administrative info available".to_owned(),
+            ],
+            Reply::ERR_FILEERROR { operation, file } => {
+                vec![format!("File error doing {} on {}", operation, file)]
+            }
+            Reply::ERR_NONICKNAMEGIVEN => vec!["No nickname given".to_owned()],
+            Reply::ERR_ERRONEUSNICKNAME { new_nick } => {
+                vec![new_nick.clone(), "Erroneous nickname".to_owned()]
+            }
+            Reply::ERR_NICKNAMEINUSE { new_nick } => {
+                vec![new_nick.clone(), "Nickname is already in use".to_owned()]
+            }
+            Reply::ERR_NICKCOLLISION {
+                new_nick,
+                user,
+                host,
+            } => vec![
+                new_nick.clone(),
+                format!("Nickname collision KILL from {}@{}", user, host),
+            ],
+            Reply::ERR_UNAVAILRESOURCE { name } => vec![
+                name.clone(),
+                "Nick/channel is temporarily unavailable".to_owned(),
+            ],
+            Reply::ERR_USERNOTINCHANNEL { target, channel } => vec![
+                target.clone(),
+                channel.clone(),
+                "They aren't on that channel".to_owned(),
+            ],
+            Reply::ERR_NOTONCHANNEL { channel } => {
+                vec![channel.clone(), "You're not on that channel".to_owned()]
+            }
+            Reply::ERR_USERONCHANNEL { target, channel } => vec![
+                target.clone(),
+                channel.clone(),
+                "is already on channel".to_owned(),
+            ],
+            Reply::ERR_NOLOGIN { user } => vec![user.clone(), "User not logged in".to_owned()],
+            Reply::ERR_SUMMONDISABLED => vec!["SUMMON has been disabled".to_owned()],
+            Reply::ERR_USERSDISABLED => vec!["USERS has been disabled".to_owned()],
+            Reply::ERR_NOTREGISTERED => vec!["You have not registered".to_owned()],
+            Reply::ERR_NEEDMOREPARAMS { command } => {
+                vec![command.clone(), "Not enough parameters".to_owned()]
+            }
+            Reply::ERR_ALREADYREGISTRED => {
+                vec!["Unauthorized command (already registered)".to_owned()]
+            }
+            Reply::ERR_NOPERMFORHOST => vec!["Your host isn't among the privileged".to_owned()],
+            Reply::ERR_PASSWDMISMATCH => vec!["Password incorrect".to_owned()],
+            Reply::ERR_YOUREBANNEDCREEP { reason } => {
+                vec![format!("You are banned from this server: {}", reason)]
+            }
+            Reply::ERR_YOUWILLBEBANNED => vec!["You will be banned from this server".to_owned()],
+            Reply::ERR_KEYSET { channel } => {
+                vec![channel.clone(), "Channel key already set".to_owned()]
+            }
+            Reply::ERR_CHANNELISFULL { channel } => {
+                vec![channel.clone(), "Cannot join channel (+l)".to_owned()]
+            }
+            Reply::ERR_UNKNOWNMODE { mode, channel } => vec![
+                mode.to_string(),
+                format!("is unknown mode char to me for {}", channel),
+            ],
+            Reply::ERR_INVITEONLYCHAN { channel } => {
+                vec![channel.clone(), "Cannot join channel (+i)".to_owned()]
+            }
+            Reply::ERR_BANNEDFROMCHAN { channel } => {
+                vec![channel.clone(), "Cannot join channel (+b)".to_owned()]
+            }
+            Reply::ERR_BADCHANNELKEY { channel } => {
+                vec![channel.clone(), "Cannot join channel (+k)".to_owned()]
+            }
+            Reply::ERR_BADCHANMASK { channel } => {
+                vec![channel.clone(), "Bad Channel Mask".to_owned()]
+            }
+            Reply::ERR_NOCHANMODES { channel } => {
+                vec![channel.clone(), "Channel doesn't support modes".to_owned()]
+            }
+            Reply::ERR_BANLISTFULL { channel, mode } => vec![
+                channel.clone(),
+                mode.to_string(),
+                "Channel list is full".to_owned(),
+            ],
+            Reply::ERR_NOPRIVILEGES => {
+                vec!["Permission Denied- You're not an IRC operator".to_owned()]
+            }
+            Reply::ERR_CHANOPRIVSNEEDED { channel } => {
+                vec![channel.clone(), "You're not channel operator".to_owned()]
+            }
+            Reply::ERR_CANTKILLSERVER => vec!["You can't kill a server!".to_owned()],
+            Reply::ERR_RESTRICTED => vec!["Your connection is restricted!".to_owned()],
+            Reply::ERR_UNIQOPPRIVSNEEDED => {
+                vec!["You're not the original channel operator".to_owned()]
+            }
+            Reply::ERR_NOOPERHOST => vec!["No O-lines for your host".to_owned()],
+            Reply::ERR_UMODEUNKNOWNFLAG => vec!["Unknown MODE flag".to_owned()],
+            Reply::ERR_USERSDONTMATCH => vec!["Cannot change mode for other users".to_owned()],
+
+            Reply::RPL_LOGGEDIN { prefix, account } => vec![
+                prefix.clone(),
+                account.clone(),
+                format!("You are now logged in as {}", account),
+            ],
+            Reply::RPL_LOGGEDOUT { prefix } => {
+                vec![prefix.clone(), "You are now logged out".to_owned()]
+            }
+            Reply::ERR_NICKLOCKED => vec!["You must use a nick assigned to you".to_owned()],
+            Reply::RPL_SASLSUCCESS => vec!["SASL authentication successful".to_owned()],
+            Reply::ERR_SASLFAIL => vec!["SASL authentication failed".to_owned()],
+            Reply::ERR_SASLTOOLONG => vec!["SASL message too long".to_owned()],
+            Reply::ERR_SASLABORTED => vec!["SASL authentication aborted".to_owned()],
+            Reply::ERR_SASLALREADY => {
+                vec!["You have already authenticated using SASL".to_owned()]
+            }
+            Reply::RPL_SASLMECHS { mechanisms } => vec![
+                mechanisms.clone(),
+                "are available SASL mechanisms".to_owned(),
+            ],
+        }
+    }
+
+    /// Renders the reply as sent by `server_name` to `nick`, which is `*` for a client that
+    /// hasn't chosen one yet. This fails when a parameter taken from a client can't be sent, such
+    /// as an empty target.
+    ///
+    /// Examples
+    ///
+    /// ```
+    /// use ircd::reply::Reply;
+    ///
+    /// let reply = Reply::ERR_NOSUCHNICK {
+    ///     target: "Nobody".to_owned(),
+    /// };
+    /// assert_eq!(
+    ///     reply.to_message("irc.example.com", "Cardinal")?.as_str(),
+    ///     ":irc.example.com 401 Cardinal Nobody :No such nick/channel\r\n"
+    /// );
+    /// assert_eq!(
+    ///     Reply::ERR_NOTREGISTERED
+    ///         .to_message("irc.example.com", "*")?
+    ///         .as_str(),
+    ///     ":irc.example.com 451 * :You have not registered\r\n"
+    /// );
+    ///
+    /// Ok::<(), ircd::structs::SerializeError>(())
+    /// ```
+    pub fn to_message(&self, server_name: &str, nick: &str) -> Result<Message, SerializeError> {
+        let mut parameters = self.parameters();
+        let mut builder = Message::new(self.code()).source(server_name);
+
+        // the last parameter always gets a colon, even when the nick is all there is
+        let last = match parameters.pop() {
+            Some(last) => {
+                builder = builder.param(nick);
+                last
+            }
+            None => nick.to_owned(),
+        };
+        for parameter in parameters {
+            builder = builder.param(parameter);
+        }
+
+        builder.trailing(last).build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catalog() {
+        // each reply with its code and the parameters that follow the recipient's nick
+        let cases = vec![
+            (
+                Reply::RPL_WELCOME {
+                    prefix: "Cardinal!cardinal@127.0.0.1".to_owned(),
+                },
+                "001",
+                ":Welcome to the network Cardinal!cardinal@127.0.0.1",
+            ),
+            (
+                Reply::RPL_YOURHOST {
+                    server_name: "irc.example.com".to_owned(),
+                    version: "0.1.0".to_owned(),
+                },
+                "002",
+                ":Your host is irc.example.com, running ircd version 0.1.0",
+            ),
+            (
+                Reply::RPL_CREATED {
+                    created_at: "2020-01-20T12:27:00-04:00".to_owned(),
+                },
+                "003",
+                ":This server was created 2020-01-20T12:27:00-04:00",
+            ),
+            (
+                Reply::RPL_MYINFO {
+                    server_name: "irc.example.com".to_owned(),
+                    version: "0.1.0".to_owned(),
+                    user_modes: "io".to_owned(),
+                    channel_modes: "bklmnt".to_owned(),
+                },
+                "004",
+                "irc.example.com 0.1.0 io :bklmnt",
+            ),
+            (
+                Reply::RPL_ISUPPORT {
+                    tokens: vec!["CASEMAPPING=rfc1459".to_owned(), "NICKLEN=9".to_owned()],
+                },
+                "005",
+                "CASEMAPPING=rfc1459 NICKLEN=9 :are supported by this server",
+            ),
+            (
+                Reply::RPL_BOUNCE {
+                    server_name: "irc.example.com".to_owned(),
+                    port: 6697,
+                },
+                "010",
+                "irc.example.com 6697 :Try another server",
+            ),
+            (
+                Reply::RPL_TRACELINK {
+                    version: "0.1.0".to_owned(),
+                    destination: "irc.example.org".to_owned(),
+                    next_server: "irc.example.org".to_owned(),
+                    protocol_version: "0210".to_owned(),
+                    uptime: 600,
+                    backstream_sendq: 5,
+                    upstream_sendq: 5,
+                },
+                "200",
+                "Link 0.1.0 irc.example.org irc.example.org V0210 600 5 :5",
+            ),
+            (
+                Reply::RPL_TRACECONNECTING {
+                    class: "users".to_owned(),
+                    server_name: "irc.example.com".to_owned(),
+                },
+                "201",
+                "Try. users :irc.example.com",
+            ),
+            (
+                Reply::RPL_TRACEHANDSHAKE {
+                    class: "users".to_owned(),
+                    server_name: "irc.example.com".to_owned(),
+                },
+                "202",
+                "H.S. users :irc.example.com",
+            ),
+            (
+                Reply::RPL_TRACEUNKNOWN {
+                    class: "users".to_owned(),
+                    ip: "192.0.2.1".to_owned(),
+                },
+                "203",
+                "???? users :192.0.2.1",
+            ),
+            (
+                Reply::RPL_TRACEOPERATOR {
+                    class: "users".to_owned(),
+                    target: "Other".to_owned(),
+                },
+                "204",
+                "Oper users :Other",
+            ),
+            (
+                Reply::RPL_TRACEUSER {
+                    class: "users".to_owned(),
+                    target: "Other".to_owned(),
+                },
+                "205",
+                "User users :Other",
+            ),
+            (
+                Reply::RPL_TRACESERVER {
+                    class: "users".to_owned(),
+                    servers: 1,
+                    clients: 2,
+                    server_name: "irc.example.com".to_owned(),
+                    link_by: "irc.example.org".to_owned(),
+                    protocol_version: "0210".to_owned(),
+                },
+                "206",
+                "Serv users 1S 2C irc.example.com irc.example.org :V0210",
+            ),
+            (
+                Reply::RPL_TRACESERVICE {
+                    class: "users".to_owned(),
+                    name: "Other".to_owned(),
+                    service_type: "bot".to_owned(),
+                    active_type: "bot".to_owned(),
+                },
+                "207",
+                "Service users Other bot :bot",
+            ),
+            (
+                Reply::RPL_TRACENEWTYPE {
+                    new_type: "bot".to_owned(),
+                    client_name: "Other".to_owned(),
+                },
+                "208",
+                "bot 0 :Other",
+            ),
+            (
+                Reply::RPL_TRACECLASS {
+                    class: "users".to_owned(),
+                    count: 3,
+                },
+                "209",
+                "Class users :3",
+            ),
+            (
+                Reply::RPL_STATSLINKINFO {
+                    link_name: "irc.example.org".to_owned(),
+                    sendq: 5,
+                    sent_messages: 5,
+                    sent_kbytes: 5,
+                    received_messages: 5,
+                    received_kbytes: 5,
+                    time_open: 600,
+                },
+                "211",
+                "irc.example.org 5 5 5 5 5 :600",
+            ),
+            (
+                Reply::RPL_STATSCOMMANDS {
+                    command: "FOO".to_owned(),
+                    count: 3,
+                    bytes: 5,
+                    remote_count: 5,
+                },
+                "212",
+                "FOO 3 5 :5",
+            ),
+            (
+                Reply::RPL_STATSCLINE {
+                    host: "127.0.0.1".to_owned(),
+                    name: "irc.example.org".to_owned(),
+                    port: 6667,
+                    class: "servers".to_owned(),
+                },
+                "213",
+                "C 127.0.0.1 * irc.example.org 6667 :servers",
+            ),
+            (
+                Reply::RPL_STATSNLINE {
+                    host: "127.0.0.1".to_owned(),
+                    name: "irc.example.org".to_owned(),
+                    port: 6667,
+                    class: "servers".to_owned(),
+                },
+                "214",
+                "N 127.0.0.1 * irc.example.org 6667 :servers",
+            ),
+            (
+                Reply::RPL_STATSILINE {
+                    ip_mask: "127.0.0.*".to_owned(),
+                    host_mask: "*.example.com".to_owned(),
+                    port: 6667,
+                    class: "users".to_owned(),
+                },
+                "215",
+                "I 127.0.0.* * *.example.com 6667 :users",
+            ),
+            (
+                Reply::RPL_STATSKLINE {
+                    host: "*.example.org".to_owned(),
+                    username: "spammer".to_owned(),
+                    port: 0,
+                    class: "users".to_owned(),
+                },
+                "216",
+                "K *.example.org * spammer 0 :users",
+            ),
+            (
+                Reply::RPL_STATSYLINE {
+                    class: "users".to_owned(),
+                    ping_frequency: 120,
+                    connect_frequency: 600,
+                    max_sendq: 100000,
+                },
+                "218",
+                "Y users 120 600 :100000",
+            ),
+            (
+                Reply::RPL_ENDOFSTATS { letter: 'u' },
+                "219",
+                "u :End of STATS report",
+            ),
+            (
+                Reply::RPL_UMODEIS {
+                    modes: "+nt".to_owned(),
+                },
+                "221",
+                ":+nt",
+            ),
+            (
+                Reply::RPL_SERVLIST {
+                    name: "Other".to_owned(),
+                    server_name: "irc.example.com".to_owned(),
+                    mask: "*.example.com".to_owned(),
+                    service_type: "bot".to_owned(),
+                    hopcount: 0,
+                    info: "A test server".to_owned(),
+                },
+                "234",
+                "Other irc.example.com *.example.com bot 0 :A test server",
+            ),
+            (
+                Reply::RPL_SERVLISTEND {
+                    mask: "*.example.com".to_owned(),
+                    service_type: "bot".to_owned(),
+                },
+                "235",
+                "*.example.com bot :End of service listing",
+            ),
+            (
+                Reply::RPL_STATSLLINE {
+                    hostmask: "*.example.org".to_owned(),
+                    server_name: "irc.example.org".to_owned(),
+                    max_depth: 2,
+                },
+                "241",
+                "L *.example.org * irc.example.org :2",
+            ),
+            (
+                Reply::RPL_STATSUPTIME { uptime: 600 },
+                "242",
+                ":Server Up 0 days 0:10:00",
+            ),
+            (
+                Reply::RPL_STATSOLINE {
+                    hostmask: "*@127.0.0.1".to_owned(),
+                    name: "Other".to_owned(),
+                },
+                "243",
+                "O *@127.0.0.1 * :Other",
+            ),
+            (
+                Reply::RPL_STATSHLINE {
+                    hostmask: "*.example.org".to_owned(),
+                    server_name: "irc.example.org".to_owned(),
+                },
+                "244",
+                "H *.example.org * :irc.example.org",
+            ),
+            (
+                Reply::RPL_LUSERCLIENT {
+                    users: 2,
+                    services: 0,
+                    servers: 1,
+                },
+                "251",
+                ":There are 2 users and 0 services on 1 servers",
+            ),
+            (
+                Reply::RPL_LUSEROP { count: 3 },
+                "252",
+                "3 :operator(s) online",
+            ),
+            (
+                Reply::RPL_LUSERUNKNOWN { count: 3 },
+                "253",
+                "3 :unknown connection(s)",
+            ),
+            (
+                Reply::RPL_LUSERCHANNELS { count: 3 },
+                "254",
+                "3 :channels formed",
+            ),
+            (
+                Reply::RPL_LUSERME {
+                    clients: 2,
+                    servers: 1,
+                },
+                "255",
+                ":I have 2 clients and 1 servers",
+            ),
+            (
+                Reply::RPL_ADMINME {
+                    server_name: "irc.example.com".to_owned(),
+                },
+                "256",
+                "irc.example.com :Administrative info",
+            ),
+            (
+                Reply::RPL_ADMINLOC1 {
+                    info: "A test server".to_owned(),
+                },
+                "257",
+                ":A test server",
+            ),
+            (
+                Reply::RPL_ADMINLOC2 {
+                    info: "A test server".to_owned(),
+                },
+                "258",
+                ":A test server",
+            ),
+            (
+                Reply::RPL_ADMINEMAIL {
+                    info: "A test server".to_owned(),
+                },
+                "259",
+                ":A test server",
+            ),
+            (
+                Reply::RPL_TRACELOG {
+                    file: "config.toml".to_owned(),
+                    debug_level: 0,
+                },
+                "261",
+                "File config.toml :0",
+            ),
+            (
+                Reply::RPL_TRACEEND {
+                    server_name: "irc.example.com".to_owned(),
+                    version: "0.1.0".to_owned(),
+                },
+                "262",
+                "irc.example.com 0.1.0 :End of TRACE",
+            ),
+            (
+                Reply::RPL_TRYAGAIN {
+                    command: "FOO".to_owned(),
+                },
+                "263",
+                "FOO :Please wait a while and try again.",
+            ),
+            (
+                Reply::RPL_WHOISCERTFP {
+                    target: "Other".to_owned(),
+                    certfp: "0123abcd".to_owned(),
+                },
+                "276",
+                "Other :has client certificate fingerprint 0123abcd",
+            ),
+            (
+                Reply::RPL_AWAY {
+                    target: "Other".to_owned(),
+                    message: "Gone to lunch".to_owned(),
+                },
+                "301",
+                "Other :Gone to lunch",
+            ),
+            (
+                Reply::RPL_USERHOST {
+                    replies: vec!["Other=+other@127.0.0.1".to_owned()],
+                },
+                "302",
+                ":Other=+other@127.0.0.1",
+            ),
+            (
+                Reply::RPL_ISON {
+                    nicks: vec!["Other".to_owned(), "Nobody".to_owned()],
+                },
+                "303",
+                ":Other Nobody",
+            ),
+            (
+                Reply::RPL_UNAWAY,
+                "305",
+                ":You are no longer marked as being away",
+            ),
+            (
+                Reply::RPL_NOWAWAY,
+                "306",
+                ":You have been marked as being away",
+            ),
+            (
+                Reply::RPL_WHOISUSER {
+                    target: "Other".to_owned(),
+                    user: "other".to_owned(),
+                    host: "127.0.0.1".to_owned(),
+                    realname: "Other Person".to_owned(),
+                },
+                "311",
+                "Other other 127.0.0.1 * :Other Person",
+            ),
+            (
+                Reply::RPL_WHOISSERVER {
+                    target: "Other".to_owned(),
+                    server_name: "irc.example.com".to_owned(),
+                    server_info: "A test server".to_owned(),
+                },
+                "312",
+                "Other irc.example.com :A test server",
+            ),
+            (
+                Reply::RPL_WHOISOPERATOR {
+                    target: "Other".to_owned(),
+                },
+                "313",
+                "Other :is an IRC operator",
+            ),
+            (
+                Reply::RPL_WHOWASUSER {
+                    target: "Other".to_owned(),
+                    user: "other".to_owned(),
+                    host: "127.0.0.1".to_owned(),
+                    realname: "Other Person".to_owned(),
+                },
+                "314",
+                "Other other 127.0.0.1 * :Other Person",
+            ),
+            (
+                Reply::RPL_ENDOFWHO {
+                    mask: "*.example.com".to_owned(),
+                },
+                "315",
+                "*.example.com :End of /WHO list",
+            ),
+            (
+                Reply::RPL_WHOISIDLE {
+                    target: "Other".to_owned(),
+                    idle: 30,
+                    signon: 1579537620,
+                },
+                "317",
+                "Other 30 1579537620 :seconds idle, signon time",
+            ),
+            (
+                Reply::RPL_ENDOFWHOIS {
+                    target: "Other".to_owned(),
+                },
+                "318",
+                "Other :End of /WHOIS list",
+            ),
+            (
+                Reply::RPL_WHOISCHANNELS {
+                    target: "Other".to_owned(),
+                    channels: vec!["@#abc".to_owned(), "#test".to_owned()],
+                },
+                "319",
+                "Other :@#abc #test",
+            ),
+            (Reply::RPL_LISTSTART, "321", "Channel :Users  Name"),
+            (
+                Reply::RPL_LIST {
+                    channel: "#test".to_owned(),
+                    visible: 2,
+                    topic: "Welcome".to_owned(),
+                },
+                "322",
+                "#test 2 :Welcome",
+            ),
+            (Reply::RPL_LISTEND, "323", ":End of /LIST"),
+            (
+                Reply::RPL_CHANNELMODEIS {
+                    channel: "#test".to_owned(),
+                    modes: vec!["+n".to_owned(), "+t".to_owned()],
+                },
+                "324",
+                "#test +n :+t",
+            ),
+            (
+                Reply::RPL_UNIQOPIS {
+                    channel: "#test".to_owned(),
+                    target: "Other".to_owned(),
+                },
+                "325",
+                "#test :Other",
+            ),
+            (
+                Reply::RPL_CREATIONTIME {
+                    channel: "#test".to_owned(),
+                    created_at: 1579537620,
+                },
+                "329",
+                "#test :1579537620",
+            ),
+            (
+                Reply::RPL_WHOISACCOUNT {
+                    target: "Other".to_owned(),
+                    account: "Cardinal".to_owned(),
+                },
+                "330",
+                "Other Cardinal :is logged in as",
+            ),
+            (
+                Reply::RPL_NOTOPIC {
+                    channel: "#test".to_owned(),
+                },
+                "331",
+                "#test :No topic is set",
+            ),
+            (
+                Reply::RPL_TOPIC {
+                    channel: "#test".to_owned(),
+                    topic: "Welcome".to_owned(),
+                },
+                "332",
+                "#test :Welcome",
+            ),
+            (
+                Reply::RPL_TOPICWHOTIME {
+                    channel: "#test".to_owned(),
+                    set_by: "Cardinal!cardinal@127.0.0.1".to_owned(),
+                    set_at: 1579537620,
+                },
+                "333",
+                "#test Cardinal!cardinal@127.0.0.1 :1579537620",
+            ),
+            (
+                Reply::RPL_INVITING {
+                    target: "Other".to_owned(),
+                    channel: "#test".to_owned(),
+                },
+                "341",
+                "Other :#test",
+            ),
+            (
+                Reply::RPL_SUMMONING {
+                    user: "other".to_owned(),
+                },
+                "342",
+                "other :Summoning user to IRC",
+            ),
+            (
+                Reply::RPL_INVITELIST {
+                    channel: "#test".to_owned(),
+                    mask: "*.example.com".to_owned(),
+                },
+                "346",
+                "#test :*.example.com",
+            ),
+            (
+                Reply::RPL_ENDOFINVITELIST {
+                    channel: "#test".to_owned(),
+                },
+                "347",
+                "#test :End of channel invite list",
+            ),
+            (
+                Reply::RPL_EXCEPTLIST {
+                    channel: "#test".to_owned(),
+                    mask: "*.example.com".to_owned(),
+                },
+                "348",
+                "#test :*.example.com",
+            ),
+            (
+                Reply::RPL_ENDOFEXCEPTLIST {
+                    channel: "#test".to_owned(),
+                },
+                "349",
+                "#test :End of channel exception list",
+            ),
+            (
+                Reply::RPL_VERSION {
+                    version: "0.1.0".to_owned(),
+                    server_name: "irc.example.com".to_owned(),
+                    comments: "debug".to_owned(),
+                },
+                "351",
+                "0.1.0 irc.example.com :debug",
+            ),
+            (
+                Reply::RPL_WHOREPLY {
+                    channel: "#test".to_owned(),
+                    user: "other".to_owned(),
+                    host: "127.0.0.1".to_owned(),
+                    server_name: "irc.example.com".to_owned(),
+                    target: "Other".to_owned(),
+                    flags: "H@".to_owned(),
+                    hopcount: 0,
+                    realname: "Other Person".to_owned(),
+                },
+                "352",
+                "#test other 127.0.0.1 irc.example.com Other H@ :0 Other Person",
+            ),
+            (
+                Reply::RPL_NAMREPLY {
+                    channel: "#test".to_owned(),
+                    names: vec!["@Cardinal".to_owned(), "Other".to_owned()],
+                },
+                "353",
+                "= #test :@Cardinal Other",
+            ),
+            (
+                Reply::RPL_LINKS {
+                    mask: "*.example.com".to_owned(),
+                    server_name: "irc.example.com".to_owned(),
+                    hopcount: 0,
+                    server_info: "A test server".to_owned(),
+                },
+                "364",
+                "*.example.com irc.example.com :0 A test server",
+            ),
+            (
+                Reply::RPL_ENDOFLINKS {
+                    mask: "*.example.com".to_owned(),
+                },
+                "365",
+                "*.example.com :End of /LINKS list",
+            ),
+            (
+                Reply::RPL_ENDOFNAMES {
+                    channel: "#test".to_owned(),
+                },
+                "366",
+                "#test :End of /NAMES list",
+            ),
+            (
+                Reply::RPL_BANLIST {
+                    channel: "#test".to_owned(),
+                    mask: "*.example.com".to_owned(),
+                },
+                "367",
+                "#test :*.example.com",
+            ),
+            (
+                Reply::RPL_ENDOFBANLIST {
+                    channel: "#test".to_owned(),
+                },
+                "368",
+                "#test :End of channel ban list",
+            ),
+            (
+                Reply::RPL_ENDOFWHOWAS {
+                    target: "Other".to_owned(),
+                },
+                "369",
+                "Other :End of WHOWAS",
+            ),
+            (
+                Reply::RPL_INFO {
+                    line: "Be nice".to_owned(),
+                },
+                "371",
+                ":Be nice",
+            ),
+            (
+                Reply::RPL_MOTD {
+                    line: "Be nice".to_owned(),
+                },
+                "372",
+                ":- Be nice",
+            ),
+            (Reply::RPL_ENDOFINFO, "374", ":End of /INFO list"),
+            (
+                Reply::RPL_MOTDSTART {
+                    server_name: "irc.example.com".to_owned(),
+                },
+                "375",
+                ":- irc.example.com Message of the day - ",
+            ),
+            (Reply::RPL_ENDOFMOTD, "376", ":End of /MOTD command"),
+            (Reply::RPL_YOUREOPER, "381", ":You are now an IRC operator"),
+            (
+                Reply::RPL_REHASHING {
+                    file: "config.toml".to_owned(),
+                },
+                "382",
+                "config.toml :Rehashing",
+            ),
+            (
+                Reply::RPL_YOURESERVICE {
+                    name: "Other".to_owned(),
+                },
+                "383",
+                ":You are service Other",
+            ),
+            (
+                Reply::RPL_TIME {
+                    server_name: "irc.example.com".to_owned(),
+                    time: "Monday January 20 2020 -- 12:27 -04:00".to_owned(),
+                },
+                "391",
+                "irc.example.com :Monday January 20 2020 -- 12:27 -04:00",
+            ),
+            (Reply::RPL_USERSSTART, "392", ":UserID   Terminal  Host"),
+            (
+                Reply::RPL_USERS {
+                    user: "other".to_owned(),
+                    tty: "pts/0".to_owned(),
+                    host: "127.0.0.1".to_owned(),
+                },
+                "393",
+                ":other pts/0 127.0.0.1",
+            ),
+            (Reply::RPL_ENDOFUSERS, "394", ":End of users"),
+            (Reply::RPL_NOUSERS, "395", ":Nobody logged in"),
+            (
+                Reply::RPL_WHOISSECURE {
+                    target: "Other".to_owned(),
+                },
+                "671",
+                "Other :is using a secure connection",
+            ),
+            (
+                Reply::ERR_UNKNOWNERROR {
+                    command: "FOO".to_owned(),
+                    message: "Gone to lunch".to_owned(),
+                },
+                "400",
+                "FOO :Gone to lunch",
+            ),
+            (
+                Reply::ERR_NOSUCHNICK {
+                    target: "Other".to_owned(),
+                },
+                "401",
+                "Other :No such nick/channel",
+            ),
+            (
+                Reply::ERR_NOSUCHSERVER {
+                    server_name: "irc.example.com".to_owned(),
+                },
+                "402",
+                "irc.example.com :No such server",
+            ),
+            (
+                Reply::ERR_NOSUCHCHANNEL {
+                    channel: "#test".to_owned(),
+                },
+                "403",
+                "#test :No such channel",
+            ),
+            (
+                Reply::ERR_CANNOTSENDTOCHAN {
+                    channel: "#test".to_owned(),
+                },
+                "404",
+                "#test :Cannot send to channel",
+            ),
+            (
+                Reply::ERR_TOOMANYCHANNELS {
+                    channel: "#test".to_owned(),
+                },
+                "405",
+                "#test :You have joined too many channels",
+            ),
+            (
+                Reply::ERR_WASNOSUCHNICK {
+                    target: "Other".to_owned(),
+                },
+                "406",
+                "Other :There was no such nickname",
+            ),
+            (
+                Reply::ERR_TOOMANYTARGETS {
+                    target: "Other".to_owned(),
+                    error_code: "0".to_owned(),
+                    message: "Gone to lunch".to_owned(),
+                },
+                "407",
+                "Other :0 recipients. Gone to lunch",
+            ),
+            (
+                Reply::ERR_NOSUCHSERVICE {
+                    name: "Other".to_owned(),
+                },
+                "408",
+                "Other :No such service",
+            ),
+            (Reply::ERR_NOORIGIN, "409", ":No origin specified"),
+            (
+                Reply::ERR_INVALIDCAPCMD {
+                    subcommand: "FOO".to_owned(),
+                },
+                "410",
+                "FOO :Invalid CAP command",
+            ),
+            (
+                Reply::ERR_NORECIPIENT {
+                    command: "FOO".to_owned(),
+                },
+                "411",
+                ":No recipient given (FOO)",
+            ),
+            (Reply::ERR_NOTEXTTOSEND, "412", ":No text to send"),
+            (
+                Reply::ERR_NOTOPLEVEL {
+                    mask: "*.example.com".to_owned(),
+                },
+                "413",
+                "*.example.com :No toplevel domain specified",
+            ),
+            (
+                Reply::ERR_WILDTOPLEVEL {
+                    mask: "*.example.com".to_owned(),
+                },
+                "414",
+                "*.example.com :Wildcard in toplevel domain",
+            ),
+            (
+                Reply::ERR_BADMASK {
+                    mask: "*.example.com".to_owned(),
+                },
+                "415",
+                "*.example.com :Bad Server/host mask",
+            ),
+            (Reply::ERR_INPUTTOOLONG, "417", ":Input line was too long"),
+            (
+                Reply::ERR_UNKNOWNCOMMAND {
+                    command: "FOO".to_owned(),
+                },
+                "421",
+                "FOO :Unknown command",
+            ),
+            (Reply::ERR_NOMOTD, "422", ":MOTD File is missing"),
+            (
+                Reply::ERR_NOADMININFO {
+                    server_name: "irc.example.com".to_owned(),
+                },
+                "423",
+                "irc.example.com :No administrative info available",
+            ),
+            (
+                Reply::ERR_FILEERROR {
+                    operation: "FOO".to_owned(),
+                    file: "config.toml".to_owned(),
+                },
+                "424",
+                ":File error doing FOO on config.toml",
+            ),
+            (Reply::ERR_NONICKNAMEGIVEN, "431", ":No nickname given"),
+            (
+                Reply::ERR_ERRONEUSNICKNAME {
+                    new_nick: "Other".to_owned(),
+                },
+                "432",
+                "Other :Erroneous nickname",
+            ),
+            (
+                Reply::ERR_NICKNAMEINUSE {
+                    new_nick: "Other".to_owned(),
+                },
+                "433",
+                "Other :Nickname is already in use",
+            ),
+            (
+                Reply::ERR_NICKCOLLISION {
+                    new_nick: "Other".to_owned(),
+                    user: "other".to_owned(),
+                    host: "127.0.0.1".to_owned(),
+                },
+                "436",
+                "Other :Nickname collision KILL from other@127.0.0.1",
+            ),
+            (
+                Reply::ERR_UNAVAILRESOURCE {
+                    name: "Other".to_owned(),
+                },
+                "437",
+                "Other :Nick/channel is temporarily unavailable",
+            ),
+            (
+                Reply::ERR_USERNOTINCHANNEL {
+                    target: "Other".to_owned(),
+                    channel: "#test".to_owned(),
+                },
+                "441",
+                "Other #test :They aren't on that channel",
+            ),
+            (
+                Reply::ERR_NOTONCHANNEL {
+                    channel: "#test".to_owned(),
+                },
+                "442",
+                "#test :You're not on that channel",
+            ),
+            (
+                Reply::ERR_USERONCHANNEL {
+                    target: "Other".to_owned(),
+                    channel: "#test".to_owned(),
+                },
+                "443",
+                "Other #test :is already on channel",
+            ),
+            (
+                Reply::ERR_NOLOGIN {
+                    user: "other".to_owned(),
+                },
+                "444",
+                "other :User not logged in",
+            ),
+            (
+                Reply::ERR_SUMMONDISABLED,
+                "445",
+                ":SUMMON has been disabled",
+            ),
+            (Reply::ERR_USERSDISABLED, "446", ":USERS has been disabled"),
+            (Reply::ERR_NOTREGISTERED, "451", ":You have not registered"),
+            (
+                Reply::ERR_NEEDMOREPARAMS {
+                    command: "FOO".to_owned(),
+                },
+                "461",
+                "FOO :Not enough parameters",
+            ),
+            (
+                Reply::ERR_ALREADYREGISTRED,
+                "462",
+                ":Unauthorized command (already registered)",
+            ),
+            (
+                Reply::ERR_NOPERMFORHOST,
+                "463",
+                ":Your host isn't among the privileged",
+            ),
+            (Reply::ERR_PASSWDMISMATCH, "464", ":Password incorrect"),
+            (
+                Reply::ERR_YOUREBANNEDCREEP {
+                    reason: "Spamming".to_owned(),
+                },
+                "465",
+                ":You are banned from this server: Spamming",
+            ),
+            (
+                Reply::ERR_YOUWILLBEBANNED,
+                "466",
+                ":You will be banned from this server",
+            ),
+            (
+                Reply::ERR_KEYSET {
+                    channel: "#test".to_owned(),
+                },
+                "467",
+                "#test :Channel key already set",
+            ),
+            (
+                Reply::ERR_CHANNELISFULL {
+                    channel: "#test".to_owned(),
+                },
+                "471",
+                "#test :Cannot join channel (+l)",
+            ),
+            (
+                Reply::ERR_UNKNOWNMODE {
+                    mode: 'o',
+                    channel: "#test".to_owned(),
+                },
+                "472",
+                "o :is unknown mode char to me for #test",
+            ),
+            (
+                Reply::ERR_INVITEONLYCHAN {
+                    channel: "#test".to_owned(),
+                },
+                "473",
+                "#test :Cannot join channel (+i)",
+            ),
+            (
+                Reply::ERR_BANNEDFROMCHAN {
+                    channel: "#test".to_owned(),
+                },
+                "474",
+                "#test :Cannot join channel (+b)",
+            ),
+            (
+                Reply::ERR_BADCHANNELKEY {
+                    channel: "#test".to_owned(),
+                },
+                "475",
+                "#test :Cannot join channel (+k)",
+            ),
+            (
+                Reply::ERR_BADCHANMASK {
+                    channel: "#test".to_owned(),
+                },
+                "476",
+                "#test :Bad Channel Mask",
+            ),
+            (
+                Reply::ERR_NOCHANMODES {
+                    channel: "#test".to_owned(),
+                },
+                "477",
+                "#test :Channel doesn't support modes",
+            ),
+            (
+                Reply::ERR_BANLISTFULL {
+                    channel: "#test".to_owned(),
+                    mode: 'o',
+                },
+                "478",
+                "#test o :Channel list is full",
+            ),
+            (
+                Reply::ERR_NOPRIVILEGES,
+                "481",
+                ":Permission Denied- You're not an IRC operator",
+            ),
+            (
+                Reply::ERR_CHANOPRIVSNEEDED {
+                    channel: "#test".to_owned(),
+                },
+                "482",
+                "#test :You're not channel operator",
+            ),
+            (
+                Reply::ERR_CANTKILLSERVER,
+                "483",
+                ":You can't kill a server!",
+            ),
+            (
+                Reply::ERR_RESTRICTED,
+                "484",
+                ":Your connection is restricted!",
+            ),
+            (
+                Reply::ERR_UNIQOPPRIVSNEEDED,
+                "485",
+                ":You're not the original channel operator",
+            ),
+            (Reply::ERR_NOOPERHOST, "491", ":No O-lines for your host"),
+            (Reply::ERR_UMODEUNKNOWNFLAG, "501", ":Unknown MODE flag"),
+            (
+                Reply::ERR_USERSDONTMATCH,
+                "502",
+                ":Cannot change mode for other users",
+            ),
+            (
+                Reply::RPL_LOGGEDIN {
+                    prefix: "Cardinal!cardinal@127.0.0.1".to_owned(),
+                    account: "Cardinal".to_owned(),
+                },
+                "900",
+                "Cardinal!cardinal@127.0.0.1 Cardinal :You are now logged in as Cardinal",
+            ),
+            (
+                Reply::RPL_LOGGEDOUT {
+                    prefix: "Cardinal!cardinal@127.0.0.1".to_owned(),
+                },
+                "901",
+                "Cardinal!cardinal@127.0.0.1 :You are now logged out",
+            ),
+            (
+                Reply::ERR_NICKLOCKED,
+                "902",
+                ":You must use a nick assigned to you",
+            ),
+            (
+                Reply::RPL_SASLSUCCESS,
+                "903",
+                ":SASL authentication successful",
+            ),
+            (Reply::ERR_SASLFAIL, "904", ":SASL authentication failed"),
+            (Reply::ERR_SASLTOOLONG, "905", ":SASL message too long"),
+            (
+                Reply::ERR_SASLABORTED,
+                "906",
+                ":SASL authentication aborted",
+            ),
+            (
+                Reply::ERR_SASLALREADY,
+                "907",
+                ":You have already authenticated using SASL",
+            ),
+            (
+                Reply::RPL_SASLMECHS {
+                    mechanisms: "PLAIN,EXTERNAL".to_owned(),
+                },
+                "908",
+                "PLAIN,EXTERNAL :are available SASL mechanisms",
+            ),
+        ];
+
+        for (reply, code, parameters) in cases {
+            assert_eq!(reply.code(), code);
+            assert_eq!(
+                reply
+                    .to_message("irc.example.com", "Cardinal")
+                    .unwrap()
+                    .as_str(),
+                format!(":irc.example.com {} Cardinal {}\r\n", code, parameters)
+            );
+        }
+    }
+}
@@ -0,0 +1,629 @@
+//! SASL authentication over AUTHENTICATE.
+//!
+//! See <https://ircv3.net/specs/extensions/sasl-3.1>.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use crate::accounts::{AccountStore, PasswordCheck};
+use crate::caps::Capability;
+use crate::client::{Client, ClientId};
+use crate::message::Message;
+use crate::reply::Reply;
+use crate::scram;
+use crate::server::{Server, State};
+
+/// AUTHENTICATE payloads are base64 encoded and split into chunks of this many bytes. A chunk of
+/// exactly this length means more are to follow.
+pub const CHUNK_LEN: usize = 400;
+
+/// The most base64 a client may send for a single SASL message.
+pub const MAX_MESSAGE_LEN: usize = 8192;
+
+/// How many times a client may fail to authenticate before it is disconnected.
+pub const MAX_FAILURES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mechanism {
+    ScramSha256,
+    Plain,
+    External,
+}
+
+impl Mechanism {
+    /// In order of preference, since clients tend to pick the first they support.
+    pub const ALL: &'static [Mechanism] = &[
+        Mechanism::ScramSha256,
+        Mechanism::Plain,
+        Mechanism::External,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mechanism::ScramSha256 => "SCRAM-SHA-256",
+            Mechanism::Plain => "PLAIN",
+            Mechanism::External => "EXTERNAL",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Mechanism::ALL
+            .iter()
+            .copied()
+            .find(|mechanism| mechanism.name().eq_ignore_ascii_case(name))
+    }
+
+    /// The mechanisms as listed in the `sasl` capability value and RPL_SASLMECHS.
+    pub fn list() -> String {
+        let names: Vec<&str> = Mechanism::ALL.iter().map(|m| m.name()).collect();
+        names.join(",")
+    }
+}
+
+/// The outcome of feeding a complete client message to a mechanism.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Step {
+    /// Send this to the client and wait for its response.
+    Challenge(Vec<u8>),
+    /// The outcome depends on a password, which is too slow to check with the state locked.
+    CheckPassword(PlainCredentials),
+    /// The client is logged in to the named account.
+    Success(String),
+    Failure,
+}
+
+/// A chunk of client data, after reassembly.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Chunk {
+    /// More chunks are needed before the message is complete.
+    Incomplete,
+    Complete(Vec<u8>),
+    TooLong,
+    Invalid,
+}
+
+/// An authentication exchange in progress.
+pub struct Session {
+    mechanism: Mechanism,
+    buffer: String,
+    /// Only SCRAM takes more than one step, so only it needs to keep state between them.
+    scram: scram::Exchange,
+}
+
+impl Session {
+    pub fn new(mechanism: Mechanism) -> Self {
+        Session {
+            mechanism,
+            buffer: String::new(),
+            scram: scram::Exchange::new(),
+        }
+    }
+
+    /// Adds a chunk from the client, returning the decoded message once it is complete.
+    pub fn push(&mut self, chunk: &str) -> Chunk {
+        if chunk.len() > CHUNK_LEN {
+            return Chunk::TooLong;
+        }
+        // "+" on its own is an empty chunk
+        if chunk != "+" {
+            self.buffer.push_str(chunk);
+        }
+        if self.buffer.len() > MAX_MESSAGE_LEN {
+            return Chunk::TooLong;
+        }
+        if chunk.len() == CHUNK_LEN {
+            return Chunk::Incomplete;
+        }
+
+        match STANDARD.decode(std::mem::take(&mut self.buffer)) {
+            Ok(message) => Chunk::Complete(message),
+            Err(_) => Chunk::Invalid,
+        }
+    }
+
+    /// Feeds a complete message from the client to the mechanism.
+    pub fn step(
+        &mut self,
+        accounts: &dyn AccountStore,
+        certfp: Option<&str>,
+        message: &[u8],
+    ) -> Step {
+        match self.mechanism {
+            Mechanism::ScramSha256 => self.scram.step(accounts, message),
+            Mechanism::Plain => plain(message),
+            Mechanism::External => external(accounts, certfp, message),
+        }
+    }
+}
+
+/// RFC 4616: `[authzid] NUL authcid NUL passwd`.
+fn plain(message: &[u8]) -> Step {
+    let message = match std::str::from_utf8(message) {
+        Ok(message) => message,
+        Err(_) => return Step::Failure,
+    };
+    let parts: Vec<&str> = message.split('\0').collect();
+    match parts.as_slice() {
+        [authzid, authcid, password] => Step::CheckPassword(PlainCredentials {
+            authzid: (*authzid).to_owned(),
+            authcid: (*authcid).to_owned(),
+            password: (*password).to_owned(),
+        }),
+        _ => Step::Failure,
+    }
+}
+
+/// The credentials from a PLAIN message, waiting to be checked.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PlainCredentials {
+    authzid: String,
+    authcid: String,
+    password: String,
+}
+
+impl PlainCredentials {
+    pub fn verify(&self, accounts: &dyn AccountStore) -> Step {
+        match accounts.verify_password(&self.authcid, &self.password) {
+            // logging in as someone else isn't supported
+            Some(account)
+                if self.authzid.is_empty() || self.authzid.eq_ignore_ascii_case(&account) =>
+            {
+                Step::Success(account)
+            }
+            _ => Step::Failure,
+        }
+    }
+}
+
+/// RFC 4422 appendix A: the client's identity comes from its TLS certificate, and the message is
+/// an optional authzid.
+fn external(accounts: &dyn AccountStore, certfp: Option<&str>, message: &[u8]) -> Step {
+    let account = match certfp.and_then(|certfp| accounts.find_by_certfp(certfp)) {
+        Some(account) => account,
+        None => return Step::Failure,
+    };
+
+    match std::str::from_utf8(message) {
+        Ok(authzid) if authzid.is_empty() || authzid.eq_ignore_ascii_case(&account) => {
+            Step::Success(account)
+        }
+        _ => Step::Failure,
+    }
+}
+
+/// Encodes server data as AUTHENTICATE arguments, ending with `+` when the last chunk is full.
+///
+/// Examples
+///
+/// ```
+/// use ircd::sasl::encode_chunks;
+///
+/// assert_eq!(encode_chunks(b""), vec!["+"]);
+/// assert_eq!(encode_chunks(b"hello"), vec!["aGVsbG8="]);
+/// assert_eq!(encode_chunks(&[0; 300]).len(), 2);
+/// ```
+pub fn encode_chunks(data: &[u8]) -> Vec<String> {
+    let encoded = STANDARD.encode(data);
+    let mut chunks: Vec<String> = encoded
+        .as_bytes()
+        .chunks(CHUNK_LEN)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect();
+    if encoded.len().is_multiple_of(CHUNK_LEN) {
+        chunks.push("+".to_owned());
+    }
+
+    chunks
+}
+
+impl Server {
+    pub(crate) fn handle_authenticate(
+        &self,
+        state: &mut State,
+        id: ClientId,
+        argument: &str,
+    ) -> Vec<Reply> {
+        let client = state.client_mut(id);
+        if !client.caps.contains(Capability::Sasl) {
+            return vec![Reply::ERR_SASLFAIL];
+        }
+
+        if argument == "*" {
+            client.sasl = None;
+            return vec![Reply::ERR_SASLABORTED];
+        }
+
+        let mut session = match client.sasl.take() {
+            Some(session) => session,
+            None => {
+                if client.account.is_some() {
+                    return vec![Reply::ERR_SASLALREADY];
+                }
+                return match Mechanism::from_name(argument) {
+                    Some(mechanism) => {
+                        client.sasl = Some(Session::new(mechanism));
+                        send_authenticate(client, "+");
+                        vec![]
+                    }
+                    None => vec![
+                        Reply::RPL_SASLMECHS {
+                            mechanisms: Mechanism::list(),
+                        },
+                        Reply::ERR_SASLFAIL,
+                    ],
+                };
+            }
+        };
+
+        let message = match session.push(argument) {
+            Chunk::Incomplete => {
+                client.sasl = Some(session);
+                return vec![];
+            }
+            Chunk::Complete(message) => message,
+            Chunk::TooLong => return vec![Reply::ERR_SASLTOOLONG],
+            Chunk::Invalid => return vec![Reply::ERR_SASLFAIL],
+        };
+
+        let step = match self.accounts() {
+            Some(accounts) => session.step(&*accounts, client.certfp.as_deref(), &message),
+            None => Step::Failure,
+        };
+        match step {
+            Step::Challenge(challenge) => {
+                for chunk in encode_chunks(&challenge) {
+                    send_authenticate(client, &chunk);
+                }
+                client.sasl = Some(session);
+                vec![]
+            }
+            step => finish(client, step),
+        }
+    }
+}
+
+/// Replies to the last step of an exchange, which may have to wait for a password to be checked.
+/// A client that fails too many times is disconnected.
+pub(crate) fn finish(client: &mut Client, step: Step) -> Vec<Reply> {
+    match step {
+        Step::CheckPassword(credentials) => {
+            client.password_check = Some(PasswordCheck::Sasl(credentials));
+            vec![]
+        }
+        Step::Success(account) => {
+            client.account = Some(account.clone());
+            vec![
+                Reply::RPL_LOGGEDIN {
+                    prefix: client.prefix(),
+                    account,
+                },
+                Reply::RPL_SASLSUCCESS,
+            ]
+        }
+        // checking a password never leads to a challenge
+        Step::Challenge(_) | Step::Failure => {
+            client.sasl_failures += 1;
+            if client.sasl_failures >= MAX_FAILURES {
+                client.closing = Some("Too many failed SASL attempts".to_owned());
+            }
+            vec![Reply::ERR_SASLFAIL]
+        }
+    }
+}
+
+fn send_authenticate(client: &Client, argument: &str) {
+    client.send_message(Message::new("AUTHENTICATE").param(argument));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    use super::*;
+    use crate::server::Peer;
+    use crate::testing::{connect, received, send};
+
+    /// Knows a single account, Cardinal, with password "hunter2" and certificate "abc123".
+    struct TestAccounts;
+
+    impl AccountStore for TestAccounts {
+        fn verify_password(&self, account: &str, password: &str) -> Option<String> {
+            if account.eq_ignore_ascii_case("cardinal") && password == "hunter2" {
+                Some("Cardinal".to_owned())
+            } else {
+                None
+            }
+        }
+
+        fn find_by_certfp(&self, certfp: &str) -> Option<String> {
+            if certfp == "abc123" {
+                Some("Cardinal".to_owned())
+            } else {
+                None
+            }
+        }
+
+        fn scram_credentials(&self, account: &str) -> Option<(String, scram::ScramCredentials)> {
+            if account.eq_ignore_ascii_case("cardinal") {
+                let credentials = scram::ScramCredentials::derive("hunter2", b"salt", 4096);
+                Some(("Cardinal".to_owned(), credentials))
+            } else {
+                None
+            }
+        }
+    }
+
+    fn server() -> Server {
+        crate::testing::server().with_account_store(TestAccounts)
+    }
+
+    #[test]
+    fn plain_success() {
+        let server = server();
+        let (id, mut receiver) = connect(&server);
+
+        send(
+            &server,
+            id,
+            &[
+                "CAP LS 302",
+                "CAP REQ sasl",
+                "NICK Cardinal",
+                "USER c 0 * :C",
+            ],
+        );
+        received(&mut receiver);
+
+        send(&server, id, &["AUTHENTICATE plain"]);
+        assert_eq!(received(&mut receiver), vec!["AUTHENTICATE +\r\n"]);
+
+        let payload = STANDARD.encode("\0cardinal\0hunter2");
+        send(&server, id, &[&format!("AUTHENTICATE {}", payload)]);
+        assert_eq!(
+            received(&mut receiver),
+            vec![
+                ":irc.example.com 900 Cardinal Cardinal!c@127.0.0.1 Cardinal :You are now logged in as Cardinal\r\n",
+                ":irc.example.com 903 Cardinal :SASL authentication successful\r\n",
+            ]
+        );
+        assert_eq!(
+            server.state().client(id).account.as_deref(),
+            Some("Cardinal")
+        );
+
+        send(&server, id, &["AUTHENTICATE PLAIN", "CAP END"]);
+        let lines = received(&mut receiver);
+        assert_eq!(
+            lines[0],
+            ":irc.example.com 907 Cardinal :You have already authenticated using SASL\r\n"
+        );
+        assert!(lines[1].starts_with(":irc.example.com 001 Cardinal "));
+    }
+
+    #[test]
+    fn plain_failure() {
+        let server = server();
+        let (id, mut receiver) = connect(&server);
+        send(&server, id, &["CAP REQ sasl", "AUTHENTICATE PLAIN"]);
+        received(&mut receiver);
+
+        let payload = STANDARD.encode("other\0cardinal\0hunter2");
+        send(&server, id, &[&format!("AUTHENTICATE {}", payload)]);
+        let payload = STANDARD.encode("\0cardinal\0hunter3");
+        send(&server, id, &["AUTHENTICATE PLAIN"]);
+        send(&server, id, &[&format!("AUTHENTICATE {}", payload)]);
+        send(&server, id, &["AUTHENTICATE PLAIN", "AUTHENTICATE !!!"]);
+
+        assert_eq!(
+            received(&mut receiver),
+            vec![
+                ":irc.example.com 904 * :SASL authentication failed\r\n",
+                "AUTHENTICATE +\r\n",
+                ":irc.example.com 904 * :SASL authentication failed\r\n",
+                "AUTHENTICATE +\r\n",
+                ":irc.example.com 904 * :SASL authentication failed\r\n",
+            ]
+        );
+        assert_eq!(server.state().client(id).account, None);
+    }
+
+    #[test]
+    fn too_many_failures() {
+        let server = server();
+        let (id, mut receiver) = connect(&server);
+        send(&server, id, &["CAP REQ sasl"]);
+        received(&mut receiver);
+
+        let payload = STANDARD.encode("\0cardinal\0hunter3");
+        for _ in 0..MAX_FAILURES {
+            send(
+                &server,
+                id,
+                &["AUTHENTICATE PLAIN", &format!("AUTHENTICATE {}", payload)],
+            );
+        }
+
+        let lines = received(&mut receiver);
+        assert_eq!(
+            lines[lines.len() - 2..],
+            [
+                ":irc.example.com 904 * :SASL authentication failed\r\n",
+                "ERROR :Closing Link (Too many failed SASL attempts)\r\n",
+            ]
+        );
+        assert!(!server.state().clients.contains_key(&id));
+    }
+
+    /// Holds every password check until told to go ahead.
+    struct SlowAccounts {
+        checking: tokio::sync::mpsc::UnboundedSender<()>,
+        go_ahead: Mutex<std::sync::mpsc::Receiver<()>>,
+    }
+
+    impl AccountStore for SlowAccounts {
+        fn verify_password(&self, account: &str, password: &str) -> Option<String> {
+            let _ = self.checking.send(());
+            self.go_ahead.lock().unwrap().recv().unwrap();
+            TestAccounts.verify_password(account, password)
+        }
+
+        fn find_by_certfp(&self, _certfp: &str) -> Option<String> {
+            None
+        }
+    }
+
+    #[tokio::test]
+    async fn password_checked_without_state_locked() {
+        let (checking, mut checks) = tokio::sync::mpsc::unbounded_channel();
+        let (go_ahead, waiting) = std::sync::mpsc::channel();
+        let server = Arc::new(crate::testing::server().with_account_store(SlowAccounts {
+            checking,
+            go_ahead: Mutex::new(waiting),
+        }));
+
+        let (client, connection) = tokio::io::duplex(4096);
+        tokio::spawn({
+            let server = Arc::clone(&server);
+            async move {
+                server
+                    .serve(
+                        connection,
+                        Peer::plaintext(Some("127.0.0.1:50000".parse().unwrap())),
+                    )
+                    .await
+            }
+        });
+        let (read_half, mut write_half) = tokio::io::split(client);
+        let mut lines = BufReader::new(read_half).lines();
+        let payload = STANDARD.encode("\0cardinal\0hunter2");
+        write_half
+            .write_all(
+                format!(
+                    "CAP REQ sasl\r\nAUTHENTICATE PLAIN\r\nAUTHENTICATE {}\r\n",
+                    payload
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap();
+        checks.recv().await.unwrap();
+
+        // other clients are served while the password is being checked
+        let (other, mut receiver) = connect(&server);
+        send(&server, other, &["PING :x"]);
+        assert_eq!(
+            received(&mut receiver),
+            vec![":irc.example.com PONG irc.example.com :x\r\n"]
+        );
+
+        go_ahead.send(()).unwrap();
+        let mut replies = vec![];
+        while replies.len() < 4 {
+            replies.push(lines.next_line().await.unwrap().unwrap());
+        }
+        assert_eq!(
+            replies[2..],
+            [
+                ":irc.example.com 900 * *!*@127.0.0.1 Cardinal :You are now logged in as Cardinal",
+                ":irc.example.com 903 * :SASL authentication successful",
+            ]
+        );
+    }
+
+    #[test]
+    fn external() {
+        let server = server();
+        let (id, mut receiver) = connect(&server);
+        send(
+            &server,
+            id,
+            &["CAP REQ sasl", "AUTHENTICATE EXTERNAL", "AUTHENTICATE +"],
+        );
+        assert_eq!(
+            received(&mut receiver)[2],
+            ":irc.example.com 904 * :SASL authentication failed\r\n"
+        );
+
+        server.state().client_mut(id).certfp = Some("abc123".to_owned());
+        send(&server, id, &["AUTHENTICATE EXTERNAL", "AUTHENTICATE +"]);
+        assert_eq!(
+            received(&mut receiver)[2],
+            ":irc.example.com 903 * :SASL authentication successful\r\n"
+        );
+    }
+
+    #[test]
+    fn unknown_mechanism_abort_and_missing_cap() {
+        let server = server();
+        let (id, mut receiver) = connect(&server);
+
+        send(&server, id, &["AUTHENTICATE PLAIN"]);
+        send(&server, id, &["CAP REQ sasl", "AUTHENTICATE DIGEST-MD5"]);
+        send(&server, id, &["AUTHENTICATE PLAIN", "AUTHENTICATE *"]);
+        send(&server, id, &["AUTHENTICATE PLAIN", "CAP END"]);
+        assert_eq!(
+            received(&mut receiver)[..8],
+            [
+                ":irc.example.com 904 * :SASL authentication failed\r\n",
+                ":irc.example.com CAP * ACK :sasl\r\n",
+                ":irc.example.com 908 * SCRAM-SHA-256,PLAIN,EXTERNAL :are available SASL mechanisms\r\n",
+                ":irc.example.com 904 * :SASL authentication failed\r\n",
+                "AUTHENTICATE +\r\n",
+                ":irc.example.com 906 * :SASL authentication aborted\r\n",
+                "AUTHENTICATE +\r\n",
+                ":irc.example.com 906 * :SASL authentication aborted\r\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn scram_challenge() {
+        let server = server();
+        let (id, mut receiver) = connect(&server);
+        send(&server, id, &["CAP REQ sasl", "AUTHENTICATE SCRAM-SHA-256"]);
+        received(&mut receiver);
+
+        let payload = STANDARD.encode("n,,n=cardinal,r=abcdef");
+        send(&server, id, &[&format!("AUTHENTICATE {}", payload)]);
+        let lines = received(&mut receiver);
+        let challenge = lines[0]
+            .strip_prefix("AUTHENTICATE ")
+            .and_then(|line| line.strip_suffix("\r\n"))
+            .unwrap();
+        let challenge = String::from_utf8(STANDARD.decode(challenge).unwrap()).unwrap();
+        assert!(challenge.starts_with("r=abcdef"));
+        assert!(challenge.ends_with(",s=c2FsdA==,i=4096"));
+        assert!(server.state().client(id).sasl.is_some());
+
+        send(&server, id, &["AUTHENTICATE Yz1iaXdz"]);
+        assert_eq!(
+            received(&mut receiver),
+            vec![":irc.example.com 904 * :SASL authentication failed\r\n"]
+        );
+    }
+
+    #[test]
+    fn chunked_messages() {
+        let mut session = Session::new(Mechanism::Plain);
+        let message = vec![b'a'; 300];
+        let encoded = STANDARD.encode(&message);
+        assert_eq!(encoded.len(), 400);
+
+        assert_eq!(session.push(&encoded), Chunk::Incomplete);
+        assert_eq!(session.push("+"), Chunk::Complete(message.clone()));
+
+        let message = vec![b'a'; 301];
+        let encoded = STANDARD.encode(&message);
+        assert_eq!(session.push(&encoded[..400]), Chunk::Incomplete);
+        assert_eq!(session.push(&encoded[400..]), Chunk::Complete(message));
+
+        assert_eq!(session.push(&"a".repeat(401)), Chunk::TooLong);
+        let mut session = Session::new(Mechanism::Plain);
+        for _ in 0..MAX_MESSAGE_LEN / CHUNK_LEN {
+            assert_eq!(session.push(&"A".repeat(400)), Chunk::Incomplete);
+        }
+        assert_eq!(session.push(&"A".repeat(400)), Chunk::TooLong);
+    }
+}
@@ -0,0 +1,469 @@
+//! The server side of SCRAM-SHA-256.
+//!
+//! See <https://tools.ietf.org/html/rfc5802> and <https://tools.ietf.org/html/rfc7677>. Channel
+//! binding isn't supported, and usernames and passwords are used as given rather than being
+//! normalised with SASLprep.
+
+use std::fmt;
+use std::sync::OnceLock;
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::accounts::AccountStore;
+use crate::sasl::Step;
+
+/// The iteration count used for new credentials, the minimum RFC 7677 recommends.
+pub const DEFAULT_ITERATIONS: u32 = 4096;
+
+/// What the server stores for an account instead of its password.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScramCredentials {
+    pub salt: Vec<u8>,
+    pub iterations: u32,
+    pub stored_key: Vec<u8>,
+    pub server_key: Vec<u8>,
+}
+
+impl ScramCredentials {
+    /// Derives credentials from a password, salt and iteration count.
+    pub fn derive(password: &str, salt: &[u8], iterations: u32) -> Self {
+        let mut salted_password = [0; 32];
+        pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, iterations, &mut salted_password);
+        let client_key = hmac(&salted_password, b"Client Key");
+
+        ScramCredentials {
+            salt: salt.to_vec(),
+            iterations,
+            stored_key: Sha256::digest(client_key).to_vec(),
+            server_key: hmac(&salted_password, b"Server Key"),
+        }
+    }
+
+    /// Derives credentials from a password with a random salt, for storing in configuration.
+    pub fn generate(password: &str) -> Self {
+        let mut salt = [0; 16];
+        OsRng.fill_bytes(&mut salt);
+        ScramCredentials::derive(password, &salt, DEFAULT_ITERATIONS)
+    }
+
+    /// Reads credentials in the RFC 5803 format that `Display` produces:
+    /// `SCRAM-SHA-256$<iterations>:<salt>$<stored key>:<server key>`.
+    ///
+    /// Examples
+    ///
+    /// ```
+    /// use ircd::scram::ScramCredentials;
+    ///
+    /// let credentials = ScramCredentials::derive("pencil", b"salt", 4096);
+    /// let parsed = ScramCredentials::parse(&credentials.to_string()).unwrap();
+    ///
+    /// assert_eq!(parsed, credentials);
+    /// assert_eq!(ScramCredentials::parse("SCRAM-SHA-1$4096:c2FsdA==$AA==:AA=="), None);
+    /// ```
+    pub fn parse(credentials: &str) -> Option<Self> {
+        let mut parts = credentials.split('$');
+        if parts.next()? != "SCRAM-SHA-256" {
+            return None;
+        }
+        let (iterations, salt) = parts.next()?.split_once(':')?;
+        let (stored_key, server_key) = parts.next()?.split_once(':')?;
+        if parts.next().is_some() {
+            return None;
+        }
+
+        let credentials = ScramCredentials {
+            salt: STANDARD.decode(salt).ok()?,
+            iterations: iterations.parse().ok()?,
+            stored_key: STANDARD.decode(stored_key).ok()?,
+            server_key: STANDARD.decode(server_key).ok()?,
+        };
+        if credentials.iterations == 0
+            || credentials.stored_key.len() != 32
+            || credentials.server_key.len() != 32
+        {
+            return None;
+        }
+
+        Some(credentials)
+    }
+}
+
+impl fmt::Display for ScramCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "SCRAM-SHA-256${}:{}${}:{}",
+            self.iterations,
+            STANDARD.encode(&self.salt),
+            STANDARD.encode(&self.stored_key),
+            STANDARD.encode(&self.server_key),
+        )
+    }
+}
+
+/// How far a SCRAM exchange has got.
+enum State {
+    /// Waiting for the client-first-message.
+    Start,
+    /// The server-first-message has been sent, and the client-final-message is next.
+    ServerFirst {
+        /// None when there's no such account, in which case the exchange fails at the
+        /// client-final-message.
+        account: Option<String>,
+        credentials: ScramCredentials,
+        gs2_header: String,
+        nonce: String,
+        /// client-first-message-bare "," server-first-message, the start of the AuthMessage.
+        auth_message: String,
+    },
+    /// The server-final-message has been sent, and the client only has to acknowledge it.
+    ServerFinal {
+        account: String,
+    },
+    Done,
+}
+
+/// One SCRAM-SHA-256 exchange with a client.
+pub struct Exchange {
+    server_nonce: String,
+    state: State,
+}
+
+impl Exchange {
+    pub fn new() -> Self {
+        let mut nonce = [0; 18];
+        OsRng.fill_bytes(&mut nonce);
+        Exchange::with_nonce(&STANDARD.encode(nonce))
+    }
+
+    /// Starts an exchange with a chosen server nonce, which must be printable ASCII without
+    /// commas.
+    pub fn with_nonce(server_nonce: &str) -> Self {
+        Exchange {
+            server_nonce: server_nonce.to_owned(),
+            state: State::Start,
+        }
+    }
+
+    /// Feeds the next message from the client to the exchange.
+    pub fn step(&mut self, accounts: &dyn AccountStore, message: &[u8]) -> Step {
+        let message = match std::str::from_utf8(message) {
+            Ok(message) => message,
+            Err(_) => {
+                self.state = State::Done;
+                return Step::Failure;
+            }
+        };
+
+        let step = match std::mem::replace(&mut self.state, State::Done) {
+            State::Start => self.client_first(accounts, message),
+            State::ServerFirst {
+                account,
+                credentials,
+                gs2_header,
+                nonce,
+                auth_message,
+            } => self.client_final(
+                account,
+                &credentials,
+                &gs2_header,
+                &nonce,
+                auth_message,
+                message,
+            ),
+            // the client has nothing to say in reply to the server signature
+            State::ServerFinal { account } if message.is_empty() => Some(Step::Success(account)),
+            State::ServerFinal { .. } | State::Done => None,
+        };
+
+        step.unwrap_or(Step::Failure)
+    }
+
+    /// `gs2-header client-first-message-bare`, where the header is `n,[a=authzid],` or
+    /// `y,[a=authzid],` since channel binding isn't offered.
+    fn client_first(&mut self, accounts: &dyn AccountStore, message: &str) -> Option<Step> {
+        let mut parts = message.splitn(3, ',');
+        let binding = parts.next()?;
+        let authzid = parts.next()?;
+        let bare = parts.next()?;
+        if binding != "n" && binding != "y" {
+            return None;
+        }
+        let gs2_header = &message[..binding.len() + authzid.len() + 2];
+
+        let mut attributes = bare.split(',');
+        let username = decode_name(attributes.next()?.strip_prefix("n=")?)?;
+        let client_nonce = attributes.next()?.strip_prefix("r=")?;
+        // mandatory extensions aren't supported
+        if client_nonce.is_empty() || attributes.any(|attribute| attribute.starts_with("m=")) {
+            return None;
+        }
+
+        let authzid = match authzid {
+            "" => None,
+            authzid => Some(decode_name(authzid.strip_prefix("a=")?)?),
+        };
+        // logging in as someone else isn't supported
+        let found = accounts
+            .scram_credentials(&username)
+            .filter(|(account, _)| {
+                authzid.is_none_or(|authzid| authzid.eq_ignore_ascii_case(account))
+            });
+        // an unknown user carries on as far as a known one would, so that failing here doesn't
+        // reveal which accounts exist (RFC 5802 section 9)
+        let (account, credentials) = match found {
+            Some((account, credentials)) => (Some(account), credentials),
+            None => (None, fake_credentials(&username)),
+        };
+
+        let nonce = format!("{}{}", client_nonce, self.server_nonce);
+        let server_first = format!(
+            "r={},s={},i={}",
+            nonce,
+            STANDARD.encode(&credentials.salt),
+            credentials.iterations
+        );
+        let auth_message = format!("{},{}", bare, server_first);
+        self.state = State::ServerFirst {
+            account,
+            credentials,
+            gs2_header: gs2_header.to_owned(),
+            nonce,
+            auth_message,
+        };
+
+        Some(Step::Challenge(server_first.into_bytes()))
+    }
+
+    /// `c=<base64 gs2-header>,r=<nonce>[,extensions],p=<proof>`
+    fn client_final(
+        &mut self,
+        account: Option<String>,
+        credentials: &ScramCredentials,
+        gs2_header: &str,
+        nonce: &str,
+        auth_message: String,
+        message: &str,
+    ) -> Option<Step> {
+        let (without_proof, proof) = message.rsplit_once(",p=")?;
+        let mut attributes = without_proof.split(',');
+        let binding = STANDARD
+            .decode(attributes.next()?.strip_prefix("c=")?)
+            .ok()?;
+        let client_nonce = attributes.next()?.strip_prefix("r=")?;
+        if binding != gs2_header.as_bytes() || client_nonce != nonce {
+            return None;
+        }
+        let account = account?;
+        let proof = STANDARD.decode(proof).ok()?;
+        if proof.len() != credentials.stored_key.len() {
+            return None;
+        }
+
+        let auth_message = format!("{},{}", auth_message, without_proof);
+        let client_signature = hmac(&credentials.stored_key, auth_message.as_bytes());
+        let client_key: Vec<u8> = proof
+            .iter()
+            .zip(&client_signature)
+            .map(|(proof, signature)| proof ^ signature)
+            .collect();
+        if !constant_time_eq(&Sha256::digest(client_key), &credentials.stored_key) {
+            return None;
+        }
+
+        let server_signature = hmac(&credentials.server_key, auth_message.as_bytes());
+        self.state = State::ServerFinal { account };
+        Some(Step::Challenge(
+            format!("v={}", STANDARD.encode(server_signature)).into_bytes(),
+        ))
+    }
+}
+
+impl Default for Exchange {
+    fn default() -> Self {
+        Exchange::new()
+    }
+}
+
+/// Stands in for the credentials of an account that doesn't exist. The salt is derived from the
+/// username under a secret picked when the server starts, so it's the same every time that user
+/// is tried, as a real account's would be.
+fn fake_credentials(username: &str) -> ScramCredentials {
+    static SECRET: OnceLock<[u8; 32]> = OnceLock::new();
+    let secret = SECRET.get_or_init(|| {
+        let mut secret = [0; 32];
+        OsRng.fill_bytes(&mut secret);
+        secret
+    });
+
+    ScramCredentials {
+        salt: hmac(secret, username.as_bytes())[..16].to_vec(),
+        iterations: DEFAULT_ITERATIONS,
+        stored_key: vec![],
+        server_key: vec![],
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Compares without returning early, so the time taken doesn't reveal how much of a key matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Usernames escape `,` as `=2C` and `=` as `=3D`; any other use of `=` is invalid.
+fn decode_name(name: &str) -> Option<String> {
+    let mut decoded = String::new();
+    let mut rest = name;
+    while let Some(index) = rest.find('=') {
+        decoded.push_str(&rest[..index]);
+        match rest.get(index..index + 3) {
+            Some("=2C") => decoded.push(','),
+            Some("=3D") => decoded.push('='),
+            _ => return None,
+        }
+        rest = &rest[index + 3..];
+    }
+    decoded.push_str(rest);
+
+    Some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The account from RFC 7677 section 3.
+    struct TestAccounts;
+
+    impl AccountStore for TestAccounts {
+        fn verify_password(&self, _account: &str, _password: &str) -> Option<String> {
+            None
+        }
+
+        fn find_by_certfp(&self, _certfp: &str) -> Option<String> {
+            None
+        }
+
+        fn scram_credentials(&self, account: &str) -> Option<(String, ScramCredentials)> {
+            if account != "user" {
+                return None;
+            }
+            let salt = STANDARD.decode("W22ZaJ0SNY7soEsUEjb6gQ==").unwrap();
+            Some((
+                "user".to_owned(),
+                ScramCredentials::derive("pencil", &salt, 4096),
+            ))
+        }
+    }
+
+    const SERVER_NONCE: &str = "%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0";
+    const SERVER_FIRST: &str =
+        "r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096";
+    const CLIENT_FINAL: &str = "c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ=";
+
+    fn challenge(text: &str) -> Step {
+        Step::Challenge(text.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn rfc7677_test_vectors() {
+        let mut exchange = Exchange::with_nonce(SERVER_NONCE);
+
+        assert_eq!(
+            exchange.step(&TestAccounts, b"n,,n=user,r=rOprNGfwEbeRWgbNEkqO"),
+            challenge(SERVER_FIRST)
+        );
+        assert_eq!(
+            exchange.step(&TestAccounts, CLIENT_FINAL.as_bytes()),
+            challenge("v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=")
+        );
+        assert_eq!(
+            exchange.step(&TestAccounts, b""),
+            Step::Success("user".to_owned())
+        );
+    }
+
+    #[test]
+    fn wrong_proof() {
+        let mut exchange = Exchange::with_nonce(SERVER_NONCE);
+        exchange.step(&TestAccounts, b"n,,n=user,r=rOprNGfwEbeRWgbNEkqO");
+
+        let client_final = CLIENT_FINAL.replace("p=dHzb", "p=dHzc");
+        assert_eq!(
+            exchange.step(&TestAccounts, client_final.as_bytes()),
+            Step::Failure
+        );
+        assert_eq!(exchange.step(&TestAccounts, b""), Step::Failure);
+    }
+
+    #[test]
+    fn rejected_client_first_messages() {
+        for message in [
+            "p=tls-unique,,n=user,r=rOprNGfwEbeRWgbNEkqO",
+            "n,,m=ext,n=user,r=rOprNGfwEbeRWgbNEkqO",
+            "n,,n=us=er,r=rOprNGfwEbeRWgbNEkqO",
+            "n,,n=user,r=",
+            "n,,n=user",
+        ] {
+            let mut exchange = Exchange::with_nonce(SERVER_NONCE);
+            assert_eq!(
+                exchange.step(&TestAccounts, message.as_bytes()),
+                Step::Failure,
+                "{}",
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_users_fail_at_client_final() {
+        fn server_first(message: &str) -> String {
+            let mut exchange = Exchange::with_nonce(SERVER_NONCE);
+            let step = exchange.step(&TestAccounts, message.as_bytes());
+            assert_eq!(
+                exchange.step(&TestAccounts, CLIENT_FINAL.as_bytes()),
+                Step::Failure
+            );
+            match step {
+                Step::Challenge(server_first) => String::from_utf8(server_first).unwrap(),
+                step => panic!("{:?}", step),
+            }
+        }
+
+        // the salt is made up, but the same each time, as a real account's would be
+        let nobody = server_first("n,,n=nobody,r=rOprNGfwEbeRWgbNEkqO");
+        assert!(nobody.starts_with("r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,s="));
+        assert!(nobody.ends_with(",i=4096"));
+        assert_eq!(server_first("n,,n=nobody,r=rOprNGfwEbeRWgbNEkqO"), nobody);
+        assert_ne!(server_first("n,,n=somebody,r=rOprNGfwEbeRWgbNEkqO"), nobody);
+
+        // as is logging in as someone else
+        assert_ne!(
+            server_first("n,a=other,n=user,r=rOprNGfwEbeRWgbNEkqO"),
+            SERVER_FIRST
+        );
+    }
+
+    #[test]
+    fn credentials_round_trip() {
+        let credentials = TestAccounts.scram_credentials("user").unwrap().1;
+        assert_eq!(
+            credentials.to_string(),
+            "SCRAM-SHA-256$4096:W22ZaJ0SNY7soEsUEjb6gQ==$WG5d8oPm3OtcPnkdi4Uo7BkeZkBFzpcXkuLmtbsT4qY=:wfPLwcE6nTWhTAmQ7tl2KeoiWGPlZqQxSrmfPwDl2dU="
+        );
+        assert_eq!(
+            ScramCredentials::parse(&credentials.to_string()),
+            Some(credentials)
+        );
+        assert_eq!(decode_name("a=2Cb=3Dc"), Some("a,b=c".to_owned()));
+    }
+}
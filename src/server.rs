@@ -0,0 +1,1066 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+
+use bytes::Bytes;
+use log::{error, info, warn};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio::task::{self, AbortHandle};
+use tokio::time::{self, Duration};
+
+use crate::accounts::{AccountStore, TomlAccountStore};
+use crate::caps::Capability;
+use crate::casemap::casefold;
+use crate::channel::Channel;
+use crate::client::{Client, ClientId};
+use crate::config::{self, Config};
+use crate::framing::{Frame, LineReader};
+use crate::listener::{Listener, Settings, Socket};
+use crate::message::Message;
+use crate::proxy;
+use crate::reply::Reply;
+use crate::tls;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// How long a listener waits before accepting again after an error, doubling for each error in a
+/// row up to the maximum.
+const MIN_ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(10);
+const MAX_ACCEPT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// State shared between every connection, guarded by `Server::state`.
+///
+/// Command handlers lock it for the duration of a single command, so it must never be held across
+/// an `.await`.
+#[derive(Default)]
+pub struct State {
+    pub clients: HashMap<ClientId, Client>,
+    /// Casefolded nick to the client holding it, including nicks claimed before registration.
+    pub nicks: HashMap<String, ClientId>,
+    pub channels: HashMap<String, Channel>,
+}
+
+impl State {
+    /// Looks up the client a command came from. Commands are only dispatched for clients that are
+    /// still connected, so the client is always present.
+    pub fn client(&self, id: ClientId) -> &Client {
+        &self.clients[&id]
+    }
+
+    pub fn client_mut(&mut self, id: ClientId) -> &mut Client {
+        self.clients
+            .get_mut(&id)
+            .expect("commands are only handled for connected clients")
+    }
+
+    pub fn find_nick(&self, nick: &str) -> Option<ClientId> {
+        self.nicks.get(&casefold(nick)).copied()
+    }
+
+    /// Every client sharing at least one channel with `id`, excluding `id` itself.
+    pub fn neighbours(&self, id: ClientId) -> HashSet<ClientId> {
+        self.channels
+            .values()
+            .filter(|channel| channel.members.contains(&id))
+            .flat_map(|channel| channel.members.iter().copied())
+            .filter(|member| *member != id)
+            .collect()
+    }
+
+    pub fn find_channel(&self, name: &str) -> Option<&Channel> {
+        self.channels.get(&casefold(name))
+    }
+
+    /// Sends a message to every member of a channel, optionally skipping one of them.
+    pub fn send_to_channel(&self, name: &str, message: &Message, except: Option<ClientId>) {
+        if let Some(channel) = self.find_channel(name) {
+            for member in &channel.members {
+                if Some(*member) != except {
+                    self.client(*member).send(message);
+                }
+            }
+        }
+    }
+
+    /// Removes a member from a channel, dropping the channel once it is empty.
+    pub fn leave_channel(&mut self, id: ClientId, name: &str) {
+        let key = casefold(name);
+        if let Some(channel) = self.channels.get_mut(&key) {
+            channel.part(id);
+            if channel.members.is_empty() {
+                self.channels.remove(&key);
+            }
+        }
+    }
+
+    /// Announces a client's departure once to everyone sharing a channel with it, sends it a final
+    /// ERROR and forgets about it. Quitting a client that is already gone does nothing.
+    pub fn quit(&mut self, id: ClientId, reason: &str) {
+        let client = match self.clients.get(&id) {
+            Some(client) => client,
+            None => return,
+        };
+
+        if client.registered {
+            let message = Message::new("QUIT")
+                .source(client.prefix())
+                .trailing(reason)
+                .build();
+            match message {
+                Ok(message) => {
+                    for neighbour in self.neighbours(id) {
+                        self.client(neighbour).send(&message);
+                    }
+                }
+                Err(e) => warn!("Not announcing that client {} quit: {}", id, e),
+            }
+        }
+        client.send_error(&format!("Closing Link ({})", reason));
+
+        self.remove_client(id);
+    }
+
+    fn remove_client(&mut self, id: ClientId) {
+        if let Some(client) = self.clients.remove(&id) {
+            if let Some(nick) = client.nick {
+                self.nicks.remove(&casefold(&nick));
+            }
+        }
+
+        for channel in self.channels.values_mut() {
+            channel.part(id);
+        }
+        self.channels
+            .retain(|_, channel| !channel.members.is_empty());
+    }
+}
+
+/// Where a connection came from, and what was learned about it before it reached `serve`.
+pub struct Peer {
+    pub addr: Option<SocketAddr>,
+    pub secure: bool,
+    pub certfp: Option<String>,
+    pub class: Option<String>,
+}
+
+impl Peer {
+    pub fn plaintext(addr: Option<SocketAddr>) -> Self {
+        Peer {
+            addr,
+            secure: false,
+            certfp: None,
+            class: None,
+        }
+    }
+}
+
+/// A listener being served on its own task.
+struct Running {
+    settings: Settings,
+    task: AbortHandle,
+}
+
+/// Who asked for the configuration to be reloaded: an oper, or a signal when `None`.
+type RehashRequest = Option<ClientId>;
+
+pub struct Server {
+    /// Replaced as a whole by a rehash, so anything read from one configuration stays consistent
+    /// for as long as the `Arc` is held.
+    config: RwLock<Arc<Config>>,
+    /// Where the configuration was loaded from, for rehashing.
+    config_path: Option<String>,
+    /// What SASL authenticates against. SASL is only offered while there is a store, and a
+    /// rehash reloads it.
+    accounts: RwLock<Option<Arc<dyn AccountStore>>>,
+    state: Mutex<State>,
+    next_client_id: AtomicU64,
+    /// The listeners being served, by name.
+    listeners: Mutex<HashMap<String, Running>>,
+    rehash_sender: mpsc::UnboundedSender<RehashRequest>,
+    rehash_receiver: Mutex<Option<mpsc::UnboundedReceiver<RehashRequest>>>,
+}
+
+impl Server {
+    pub fn new(config: Config) -> Self {
+        let (rehash_sender, rehash_receiver) = mpsc::unbounded_channel();
+        Server {
+            config: RwLock::new(Arc::new(config)),
+            config_path: None,
+            accounts: RwLock::new(None),
+            state: Mutex::new(State::default()),
+            next_client_id: AtomicU64::new(1),
+            listeners: Mutex::new(HashMap::new()),
+            rehash_sender,
+            rehash_receiver: Mutex::new(Some(rehash_receiver)),
+        }
+    }
+
+    /// Sets the file the configuration is reloaded from on a rehash.
+    pub fn with_config_path(mut self, path: impl Into<String>) -> Self {
+        self.config_path = Some(path.into());
+        self
+    }
+
+    /// Sets the accounts SASL authenticates against. Without any, SASL isn't offered.
+    pub fn with_account_store(mut self, accounts: impl AccountStore + 'static) -> Self {
+        self.accounts = RwLock::new(Some(Arc::new(accounts)));
+        self
+    }
+
+    /// The current account store, if SASL is offered.
+    pub fn accounts(&self) -> Option<Arc<dyn AccountStore>> {
+        self.accounts
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// The current configuration. A rehash swaps in a new one without affecting copies already
+    /// handed out.
+    pub fn config(&self) -> Arc<Config> {
+        Arc::clone(&self.config.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    pub fn config_path(&self) -> Option<&str> {
+        self.config_path.as_deref()
+    }
+
+    fn listeners(&self) -> MutexGuard<'_, HashMap<String, Running>> {
+        self.listeners.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, State> {
+        // a handler panicking part way through a command should not take every other connection
+        // down with it
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Registers a new, unregistered client whose outgoing lines are delivered to `sender`.
+    pub fn connect(
+        &self,
+        addr: Option<SocketAddr>,
+        sender: mpsc::UnboundedSender<Bytes>,
+    ) -> ClientId {
+        let id = self.next_client_id.fetch_add(1, Ordering::Relaxed);
+        self.state()
+            .clients
+            .insert(id, Client::new(id, addr, sender));
+
+        id
+    }
+
+    pub fn quit(&self, id: ClientId, reason: &str) {
+        self.state().quit(id, reason);
+    }
+
+    /// Serves a bound listener on its own task, until a rehash drops its `[[listen]]` block.
+    pub fn spawn_listener(self: &Arc<Self>, listener: Listener) {
+        let Listener {
+            name,
+            socket,
+            settings,
+        } = listener;
+
+        // the task looks its settings up on every connection, so they're in place before it starts
+        let mut listeners = self.listeners();
+        let task = tokio::spawn(Arc::clone(self).run(name.clone(), socket));
+        listeners.insert(
+            name,
+            Running {
+                settings,
+                task: task.abort_handle(),
+            },
+        );
+    }
+
+    /// Accepts connections for as long as the listener is configured, serving each one on its own
+    /// task.
+    async fn run(self: Arc<Self>, name: String, socket: Socket) {
+        let mut delay = Duration::ZERO;
+        loop {
+            let accepted = match &socket {
+                Socket::Tcp(socket) => socket
+                    .accept()
+                    .await
+                    .map(|(stream, addr)| self.spawn_connection(&name, stream, Some(addr))),
+                Socket::Unix(socket) => socket
+                    .accept()
+                    .await
+                    .map(|(stream, _)| self.spawn_connection(&name, stream, None)),
+            };
+            // errors such as running out of file descriptors clear up once other connections
+            // close, so they're no reason to stop listening
+            match accepted {
+                Ok(()) => delay = Duration::ZERO,
+                Err(e) => {
+                    error!("Error accepting a connection on {}: {}", name, e);
+                    delay = accept_retry_delay(&e, delay);
+                    time::sleep(delay).await;
+                }
+            }
+        }
+    }
+
+    fn spawn_connection<S>(self: &Arc<Self>, name: &str, stream: S, addr: Option<SocketAddr>)
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        info!("Connection from {:?} on {}", addr, name);
+
+        let settings = match self.listeners().get(name) {
+            Some(running) => running.settings.clone(),
+            // removed by a rehash while this connection was being accepted
+            None => return,
+        };
+        let server = Arc::clone(self);
+        tokio::spawn(async move {
+            if let Err(e) = server.accept(&settings, stream, addr).await {
+                info!("Connection from {:?} closed with error: {}", addr, e);
+            }
+        });
+    }
+
+    /// Asks for the configuration to be reloaded. The outcome is reported to the requesting
+    /// client, if any, once `handle_rehashes` gets to it.
+    pub fn request_rehash(&self, requester: RehashRequest) {
+        // the receiver lives as long as the server, so this can't fail
+        let _ = self.rehash_sender.send(requester);
+    }
+
+    /// Carries out requested rehashes one at a time, for as long as the server runs.
+    pub async fn handle_rehashes(self: Arc<Self>) {
+        let receiver = self
+            .rehash_receiver
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        let mut receiver = match receiver {
+            Some(receiver) => receiver,
+            None => return,
+        };
+
+        while let Some(requester) = receiver.recv().await {
+            let result = self.rehash().await;
+            let message = match &result {
+                Ok(()) => {
+                    info!("Reloaded the configuration");
+                    "Reloaded the configuration".to_owned()
+                }
+                Err(e) => {
+                    warn!("Rehash failed: {}", e);
+                    format!("Rehash failed, keeping the old configuration: {}", e)
+                }
+            };
+
+            let id = match requester {
+                Some(id) => id,
+                None => continue,
+            };
+            let config = self.config();
+            if let Some(client) = self.state().clients.get(&id) {
+                client.send_message(
+                    Message::new("NOTICE")
+                        .source(&config.irc.hostname)
+                        .param(client.display_nick())
+                        .trailing(message),
+                );
+            }
+        }
+    }
+
+    /// Reloads the configuration file and swaps it in for the current one. It is only applied
+    /// once it is known to be valid and every new listener is bound, so a failed rehash leaves
+    /// the server running as it was.
+    ///
+    /// Listeners keep their socket when their address or path is unchanged, and connected
+    /// clients stay online. New limits and bans apply to clients as they next connect or
+    /// register. The `[sasl] accounts` file is read again, and clients with cap-notify are told
+    /// when that brings SASL in or takes it away.
+    pub async fn rehash(self: &Arc<Self>) -> Result<(), String> {
+        let path = self
+            .config_path
+            .as_deref()
+            .ok_or("There is no configuration file to reload")?;
+        let config = config::get_config(path).map_err(|e| e.to_string())?;
+        let accounts = match &config.sasl.accounts {
+            Some(path) => {
+                let accounts: Arc<dyn AccountStore> = Arc::new(TomlAccountStore::load(path)?);
+                Some(accounts)
+            }
+            None => None,
+        };
+
+        let running: HashSet<String> = self.listeners().keys().cloned().collect();
+        let mut settings = HashMap::new();
+        let mut bound = vec![];
+        let mut errors = vec![];
+        for listen in &config.listen {
+            let result = match Listener::name(listen) {
+                Ok(name) if running.contains(&name) => {
+                    Settings::new(listen, &name).map(|new_settings| {
+                        settings.insert(name, new_settings);
+                    })
+                }
+                Ok(_) => Listener::bind(listen)
+                    .await
+                    .map(|listener| bound.push(listener)),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                errors.push(e);
+            }
+        }
+        // dropping the listeners that were bound closes them again
+        if !errors.is_empty() {
+            return Err(errors.join(", "));
+        }
+
+        *self.config.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(config);
+        let offered = self.offered_caps();
+        *self.accounts.write().unwrap_or_else(|e| e.into_inner()) = accounts;
+        let now_offered = self.offered_caps();
+        let new: Vec<Capability> = now_offered
+            .iter()
+            .filter(|cap| !offered.contains(cap))
+            .copied()
+            .collect();
+        let del: Vec<Capability> = offered
+            .iter()
+            .filter(|cap| !now_offered.contains(cap))
+            .copied()
+            .collect();
+        if !new.is_empty() || !del.is_empty() {
+            self.notify_cap_change(&mut self.state(), &new, &del);
+        }
+        self.listeners()
+            .retain(|name, running| match settings.remove(name) {
+                Some(settings) => {
+                    running.settings = settings;
+                    true
+                }
+                None => {
+                    info!("No longer listening on {}", name);
+                    running.task.abort();
+                    false
+                }
+            });
+        for listener in bound {
+            info!("Listening on {}", listener.name);
+            self.spawn_listener(listener);
+        }
+
+        Ok(())
+    }
+
+    /// Reads the PROXY header and completes the TLS handshake for listeners that want them, then
+    /// serves the connection.
+    pub async fn accept<S>(
+        &self,
+        settings: &Settings,
+        mut stream: S,
+        mut addr: Option<SocketAddr>,
+    ) -> io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        // a client that never finishes setting up is treated like one that stopped answering
+        // PINGs
+        let timeout = Duration::from_secs(self.config().limits.ping_timeout);
+
+        if settings.proxy {
+            let source = time::timeout(timeout, proxy::read_header(&mut stream))
+                .await
+                .map_err(|_| timed_out("PROXY header"))??;
+            if source.is_some() {
+                addr = source;
+            }
+        }
+
+        let acceptor = match &settings.acceptor {
+            Some(acceptor) => acceptor,
+            None => {
+                let peer = Peer {
+                    class: settings.class.clone(),
+                    ..Peer::plaintext(addr)
+                };
+                return self.serve(stream, peer).await;
+            }
+        };
+        let stream = time::timeout(timeout, acceptor.accept(stream))
+            .await
+            .map_err(|_| timed_out("TLS handshake"))??;
+
+        let peer = Peer {
+            addr,
+            secure: true,
+            certfp: tls::certfp(stream.get_ref().1),
+            class: settings.class.clone(),
+        };
+        self.serve(stream, peer).await
+    }
+
+    /// Serves a single connection until the client goes away or times out.
+    pub async fn serve<S>(&self, stream: S, peer: Peer) -> io::Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (read_stream, mut write_stream) = tokio::io::split(stream);
+
+        // other connections write to this client through the channel, and the writer task is the
+        // only thing that touches the socket's write half
+        let (sender, mut receiver) = mpsc::unbounded_channel::<Bytes>();
+        let writer = tokio::spawn(async move {
+            while let Some(line) = receiver.recv().await {
+                write_stream.write_all(&line).await?;
+            }
+            write_stream.shutdown().await
+        });
+
+        let id = self.connect(peer.addr, sender);
+        let refused = {
+            let mut state = self.state();
+            let client = state.client_mut(id);
+            client.secure = peer.secure;
+            client.certfp = peer.certfp;
+            client.class = peer.class;
+            self.class_full(&state, id)
+        };
+        let result = match refused {
+            Some(reason) => Ok(reason),
+            None => self.read_loop(id, read_stream).await,
+        };
+
+        // a client that sent QUIT is already gone, but one whose connection dropped still needs
+        // cleaning up. dropping the client drops its sender, which lets the writer flush and exit
+        let reason = match &result {
+            Ok(reason) => (*reason).to_owned(),
+            Err(e) => e.to_string(),
+        };
+        self.quit(id, &reason);
+
+        // the peer may have closed its end already, so failing to flush the final ERROR is
+        // expected rather than worth reporting
+        let _ = writer.await;
+
+        result.map(|_| ())
+    }
+
+    /// Why a newly connected client can't be let in, if its class is already at one of its limits.
+    fn class_full(&self, state: &State, id: ClientId) -> Option<&'static str> {
+        let client = state.client(id);
+        let config = self.config();
+        let class = config.class(client.class.as_deref())?;
+        let members: Vec<&Client> = state
+            .clients
+            .values()
+            .filter(|other| other.class == client.class)
+            .collect();
+
+        // the new client is already counted among the members
+        if class.max_clients.is_some_and(|max| members.len() > max) {
+            return Some("Too many connections");
+        }
+        let from_host = members
+            .iter()
+            .filter(|other| other.host == client.host)
+            .count();
+        if class.max_per_host.is_some_and(|max| from_host > max) {
+            return Some("Too many connections from your host");
+        }
+
+        None
+    }
+
+    /// How long the client may be idle before being sent a PING, and then how long it has to
+    /// answer, from its class or the server-wide limits.
+    fn ping_times(&self, id: ClientId) -> (Duration, Duration) {
+        let config = self.config();
+        let state = self.state();
+        let class = state
+            .clients
+            .get(&id)
+            .and_then(|client| config.class(client.class.as_deref()));
+        let ping_interval = class
+            .and_then(|class| class.ping_interval)
+            .unwrap_or(config.limits.ping_interval);
+        let ping_timeout = class
+            .and_then(|class| class.ping_timeout)
+            .unwrap_or(config.limits.ping_timeout);
+
+        (
+            Duration::from_secs(ping_interval),
+            Duration::from_secs(ping_timeout),
+        )
+    }
+
+    /// Handles lines from the client until the connection should close, returning the reason.
+    async fn read_loop<R>(&self, id: ClientId, read_stream: R) -> io::Result<&'static str>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut reader = LineReader::new(read_stream);
+
+        // any line counts as activity; a PING is only sent once the connection has been idle for
+        // the ping interval, and the client then has the ping timeout to respond
+        let mut awaiting_pong = false;
+        loop {
+            // looked up each time around, to pick up changes from a rehash
+            let (ping_interval, ping_timeout) = self.ping_times(id);
+            let wait = if awaiting_pong {
+                ping_timeout
+            } else {
+                ping_interval
+            };
+
+            // a timeout leaves any partially read line buffered, to be completed on the next pass
+            match time::timeout(wait, reader.next_frame()).await {
+                Ok(frame) => {
+                    awaiting_pong = false;
+                    match frame? {
+                        Some(Frame::Line(line)) => {
+                            // clients aren't required to send UTF-8, and one that doesn't
+                            // shouldn't be disconnected for it
+                            let line = String::from_utf8_lossy(&line);
+                            let mut check = self.handle_line(id, &line);
+                            while let Some(pending) = check {
+                                // checking the hash would hold up every other connection if it
+                                // were done on this thread
+                                let accounts = self.accounts();
+                                let verified = task::spawn_blocking(move || {
+                                    pending.verify(accounts.as_deref())
+                                })
+                                .await
+                                .map_err(io::Error::other)?;
+                                check = self.finish_password_check(id, verified);
+                            }
+                        }
+                        Some(Frame::TooLong) => {
+                            if let Some(client) = self.state().clients.get(&id) {
+                                client.reply(&self.config().irc.hostname, Reply::ERR_INPUTTOOLONG);
+                            }
+                        }
+                        None => return Ok("Connection closed"),
+                    }
+
+                    // QUIT removes the client while handling the line
+                    if !self.state().clients.contains_key(&id) {
+                        return Ok("Quit");
+                    }
+                }
+                Err(_) if awaiting_pong => return Ok("Ping timeout"),
+                Err(_) => {
+                    if let Some(client) = self.state().clients.get(&id) {
+                        client.send_message(
+                            Message::new("PING").trailing(&self.config().irc.hostname),
+                        );
+                    }
+                    awaiting_pong = true;
+                }
+            }
+        }
+    }
+}
+
+/// How long to wait before accepting again after an error, given the previous wait. An error that
+/// only concerns the connection being accepted doesn't need one.
+fn accept_retry_delay(error: &io::Error, previous: Duration) -> Duration {
+    match error.kind() {
+        io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::ConnectionRefused
+        | io::ErrorKind::ConnectionReset => Duration::ZERO,
+        _ => (previous * 2).clamp(MIN_ACCEPT_RETRY_DELAY, MAX_ACCEPT_RETRY_DELAY),
+    }
+}
+
+fn timed_out(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, format!("{} timed out", what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::time::Instant;
+
+    fn server() -> Arc<Server> {
+        Arc::new(testing::server())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ping_timeout() {
+        let server = server();
+        let (client, connection) = tokio::io::duplex(4096);
+        let task = tokio::spawn(async move {
+            server
+                .serve(
+                    connection,
+                    Peer::plaintext(Some("127.0.0.1:50000".parse().unwrap())),
+                )
+                .await
+        });
+        let (read_half, mut write_half) = tokio::io::split(client);
+        let mut lines = BufReader::new(read_half).lines();
+        let start = Instant::now();
+
+        let line = lines.next_line().await.unwrap();
+        assert_eq!(line.as_deref(), Some("PING :irc.example.com"));
+        assert_eq!(start.elapsed().as_secs(), 120);
+
+        // answering the PING starts the idle interval over again
+        write_half
+            .write_all(b"PONG :irc.example.com\r\n")
+            .await
+            .unwrap();
+        let line = lines.next_line().await.unwrap();
+        assert_eq!(line.as_deref(), Some("PING :irc.example.com"));
+        assert_eq!(start.elapsed().as_secs(), 240);
+
+        let line = lines.next_line().await.unwrap();
+        assert_eq!(line.as_deref(), Some("ERROR :Closing Link (Ping timeout)"));
+        assert_eq!(start.elapsed().as_secs(), 300);
+        assert_eq!(lines.next_line().await.unwrap(), None);
+
+        task.await.unwrap().unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn class_settings() {
+        let server = Arc::new(testing::server_with(|config| {
+            config.classes.push(crate::config::Class {
+                name: "users".to_owned(),
+                ping_interval: Some(30),
+                ping_timeout: None,
+                max_clients: None,
+                max_per_host: Some(1),
+            });
+        }));
+        let peer = || Peer {
+            class: Some("users".to_owned()),
+            ..Peer::plaintext(Some("127.0.0.1:50000".parse().unwrap()))
+        };
+
+        let (first, connection) = tokio::io::duplex(4096);
+        tokio::spawn({
+            let server = Arc::clone(&server);
+            let peer = peer();
+            async move { server.serve(connection, peer).await }
+        });
+        let (read_half, mut write_half) = tokio::io::split(first);
+        let mut first = BufReader::new(read_half).lines();
+        // wait for the first connection to be served before making another
+        write_half.write_all(b"PING :ready\r\n").await.unwrap();
+        first.next_line().await.unwrap();
+
+        let (second, connection) = tokio::io::duplex(4096);
+        server.serve(connection, peer()).await.unwrap();
+        let mut second = BufReader::new(second).lines();
+        assert_eq!(
+            second.next_line().await.unwrap().as_deref(),
+            Some("ERROR :Closing Link (Too many connections from your host)")
+        );
+
+        let start = Instant::now();
+        let line = first.next_line().await.unwrap();
+        assert_eq!(line.as_deref(), Some("PING :irc.example.com"));
+        assert_eq!(start.elapsed().as_secs(), 30);
+    }
+
+    #[tokio::test]
+    async fn rehash() {
+        let directory = std::env::temp_dir().join(format!("ircd-rehash-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("config.toml");
+        let path = path.to_str().unwrap();
+        let socket = |name: &str| directory.join(name).to_str().unwrap().to_owned();
+        let config = |description: &str, sockets: &[&str]| {
+            let mut config = format!(
+                "[irc]\nhostname = \"irc.example.com\"\ncreated_at = 2020-01-20T12:27:00Z\n\
+                 description = \"{}\"\n",
+                description
+            );
+            for name in sockets {
+                config.push_str(&format!("[[listen]]\npath = \"{}\"\n", socket(name)));
+            }
+            std::fs::write(path, config).unwrap();
+        };
+
+        config("old", &["a.sock", "b.sock"]);
+        let server = Server::new(config::get_config(path).unwrap()).with_config_path(path);
+        let server = Arc::new(server);
+        for listen in &server.config().listen {
+            server.spawn_listener(Listener::bind(listen).await.unwrap());
+        }
+        tokio::spawn(Arc::clone(&server).handle_rehashes());
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let id = server.connect(None, sender);
+        server.handle_line(id, "NICK Cardinal");
+        server.handle_line(id, "USER cardinal 0 * :Cardinal");
+        while receiver.try_recv().is_ok() {}
+
+        // the old configuration stays in place when the new one is invalid
+        std::fs::write(path, "[irc]\nhostname = \"irc.example.com\"\n").unwrap();
+        server.request_rehash(Some(id));
+        assert_eq!(
+            receiver.recv().await.unwrap(),
+            ":irc.example.com NOTICE Cardinal :Rehash failed, keeping the old configuration: \
+             Error in config on line 1 at irc: missing field `created_at`\r\n"
+        );
+        assert_eq!(server.config().irc.description, "old");
+
+        config("new", &["b.sock", "c.sock"]);
+        server.request_rehash(Some(id));
+        assert_eq!(
+            receiver.recv().await.unwrap(),
+            ":irc.example.com NOTICE Cardinal :Reloaded the configuration\r\n"
+        );
+        assert_eq!(server.config().irc.description, "new");
+        let mut listening: Vec<String> = server.listeners().keys().cloned().collect();
+        listening.sort();
+        assert_eq!(listening, vec![socket("b.sock"), socket("c.sock")]);
+        // still connected
+        assert!(server.state().clients.contains_key(&id));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn rehash_notifies_cap_changes() {
+        let directory =
+            std::env::temp_dir().join(format!("ircd-rehash-caps-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("config.toml");
+        let path = path.to_str().unwrap();
+        let accounts = directory.join("accounts.toml");
+        std::fs::write(&accounts, "").unwrap();
+        let config = |sasl: bool| {
+            let mut config = "[irc]\nhostname = \"irc.example.com\"\n\
+                              created_at = 2020-01-20T12:27:00Z\n"
+                .to_owned();
+            if sasl {
+                config.push_str(&format!("[sasl]\naccounts = {:?}\n", accounts));
+            }
+            std::fs::write(path, config).unwrap();
+        };
+
+        config(false);
+        let server = Server::new(config::get_config(path).unwrap()).with_config_path(path);
+        let server = Arc::new(server);
+        tokio::spawn(Arc::clone(&server).handle_rehashes());
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let id = server.connect(None, sender);
+        for line in [
+            "CAP LS 302",
+            "NICK Cardinal",
+            "USER cardinal 0 * :Cardinal",
+            "CAP END",
+        ] {
+            server.handle_line(id, line);
+        }
+        assert_eq!(
+            receiver.recv().await.unwrap(),
+            ":irc.example.com CAP * LS :cap-notify\r\n"
+        );
+        while receiver.try_recv().is_ok() {}
+
+        config(true);
+        server.request_rehash(Some(id));
+        assert_eq!(
+            receiver.recv().await.unwrap(),
+            ":irc.example.com CAP Cardinal NEW :sasl=SCRAM-SHA-256,PLAIN,EXTERNAL\r\n"
+        );
+        assert_eq!(
+            receiver.recv().await.unwrap(),
+            ":irc.example.com NOTICE Cardinal :Reloaded the configuration\r\n"
+        );
+
+        server.handle_line(id, "CAP REQ sasl");
+        assert_eq!(
+            receiver.recv().await.unwrap(),
+            ":irc.example.com CAP Cardinal ACK :sasl\r\n"
+        );
+
+        config(false);
+        server.request_rehash(Some(id));
+        assert_eq!(
+            receiver.recv().await.unwrap(),
+            ":irc.example.com CAP Cardinal DEL :sasl\r\n"
+        );
+        assert_eq!(
+            receiver.recv().await.unwrap(),
+            ":irc.example.com NOTICE Cardinal :Reloaded the configuration\r\n"
+        );
+        assert!(!server.state().client(id).caps.contains(Capability::Sasl));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn dropped_connection_quits() {
+        let server = server();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let other = server.connect(Some("127.0.0.1:50001".parse().unwrap()), sender);
+        server.handle_line(other, "NICK Other");
+        server.handle_line(other, "USER other 0 * :Other");
+        server.handle_line(other, "JOIN #test");
+
+        let (mut client, connection) = tokio::io::duplex(4096);
+        let task = {
+            let server = Arc::clone(&server);
+            tokio::spawn(async move {
+                server
+                    .serve(
+                        connection,
+                        Peer::plaintext(Some("127.0.0.1:50000".parse().unwrap())),
+                    )
+                    .await
+            })
+        };
+        client
+            .write_all(b"NICK Cardinal\r\nUSER cardinal 0 * :Cardinal\r\nJOIN #test\r\n")
+            .await
+            .unwrap();
+        drop(client);
+        task.await.unwrap().unwrap();
+
+        let mut lines = vec![];
+        while let Ok(line) = receiver.try_recv() {
+            lines.push(line);
+        }
+        assert_eq!(
+            lines.last().unwrap(),
+            ":Cardinal!cardinal@127.0.0.1 QUIT :Connection closed\r\n"
+        );
+        assert_eq!(server.state().find_nick("Cardinal"), None);
+    }
+
+    #[tokio::test]
+    async fn bad_input_does_not_close_connection() {
+        let server = server();
+        let (client, connection) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            server
+                .serve(
+                    connection,
+                    Peer::plaintext(Some("127.0.0.1:50000".parse().unwrap())),
+                )
+                .await
+        });
+        let (read_half, mut write_half) = tokio::io::split(client);
+        let mut lines = BufReader::new(read_half).lines();
+
+        write_half
+            .write_all(b"\r\n\n:\r\nPRIVMSG \xff\xfe :\xc3\r\nPING :still here\r\n")
+            .await
+            .unwrap();
+        assert_eq!(
+            lines.next_line().await.unwrap().as_deref(),
+            Some(":irc.example.com 451 * :You have not registered")
+        );
+        assert_eq!(
+            lines.next_line().await.unwrap().as_deref(),
+            Some(":irc.example.com PONG irc.example.com :still here")
+        );
+    }
+
+    #[test]
+    fn accept_retry_delays() {
+        let out_of_files = io::Error::from_raw_os_error(24);
+        let mut delay = Duration::ZERO;
+        let mut delays = vec![];
+        for _ in 0..9 {
+            delay = accept_retry_delay(&out_of_files, delay);
+            delays.push(delay.as_millis());
+        }
+        assert_eq!(delays, vec![10, 20, 40, 80, 160, 320, 640, 1000, 1000]);
+
+        let aborted = io::Error::from(io::ErrorKind::ConnectionAborted);
+        assert_eq!(accept_retry_delay(&aborted, delay), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn tls_records_certfp() {
+        use std::convert::TryFrom;
+
+        use rcgen::{generate_simple_self_signed, CertifiedKey};
+        use tokio_rustls::rustls::crypto::ring;
+        use tokio_rustls::rustls::pki_types::{PrivateKeyDer, ServerName};
+        use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+        use tokio_rustls::TlsConnector;
+
+        use crate::config::Tls;
+
+        let CertifiedKey { cert, key_pair } =
+            generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+        let directory = std::env::temp_dir().join(format!("ircd-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let certificate = directory.join("cert.pem");
+        let key = directory.join("key.pem");
+        std::fs::write(&certificate, cert.pem()).unwrap();
+        std::fs::write(&key, key_pair.serialize_pem()).unwrap();
+        let acceptor = tls::acceptor(&Tls {
+            certificate: certificate.to_string_lossy().into_owned(),
+            key: key.to_string_lossy().into_owned(),
+        })
+        .unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let client_certificate = generate_simple_self_signed(vec!["client".to_owned()]).unwrap();
+        let mut roots = RootCertStore::empty();
+        roots.add(cert.der().clone()).unwrap();
+        let config = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_client_auth_cert(
+                vec![client_certificate.cert.der().clone()],
+                PrivateKeyDer::Pkcs8(client_certificate.key_pair.serialize_der().into()),
+            )
+            .unwrap();
+
+        let server = server();
+        let (client, connection) = tokio::io::duplex(16384);
+        tokio::spawn(async move {
+            server
+                .accept(
+                    &Settings {
+                        acceptor: Some(acceptor),
+                        ..Settings::default()
+                    },
+                    connection,
+                    Some("127.0.0.1:50000".parse().unwrap()),
+                )
+                .await
+        });
+        let client = TlsConnector::from(Arc::new(config))
+            .connect(ServerName::try_from("localhost").unwrap(), client)
+            .await
+            .unwrap();
+        let (read_half, mut write_half) = tokio::io::split(client);
+        let mut lines = BufReader::new(read_half).lines();
+
+        write_half
+            .write_all(b"NICK Cardinal\r\nUSER cardinal 0 * :Cardinal\r\nWHOIS Cardinal\r\n")
+            .await
+            .unwrap();
+        let mut whois = vec![];
+        while let Some(line) = lines.next_line().await.unwrap() {
+            if line.contains(" 318 ") {
+                break;
+            }
+            whois.push(line);
+        }
+
+        let certfp = tls::fingerprint(client_certificate.cert.der());
+        assert!(whois.contains(
+            &":irc.example.com 671 Cardinal Cardinal :is using a secure connection".to_owned()
+        ));
+        assert!(whois.contains(&format!(
+            ":irc.example.com 276 Cardinal Cardinal :has client certificate fingerprint {}",
+            certfp
+        )));
+    }
+}
@@ -1,5 +1,10 @@
+use std::borrow::Cow;
 use std::convert::TryFrom;
 use std::fmt;
+use std::ops::Range;
+
+use crate::framing::{MAX_MESSAGE_LEN, MAX_TAGS_LEN};
+use crate::message::Layout;
 
 type Result<T> = std::result::Result<T, ParseError>;
 
@@ -13,6 +18,11 @@ pub enum ParseError {
         parameter: String,
         index: usize,
     },
+    InvalidCommandParameterError {
+        command: String,
+        parameter: String,
+        index: usize,
+    },
 }
 
 impl std::error::Error for ParseError {}
@@ -26,25 +36,189 @@ impl fmt::Display for ParseError {
                 parameter,
                 index: _,
             } => format!("Command {} missing parameter: {}", command, parameter,),
+            ParseError::InvalidCommandParameterError {
+                command,
+                parameter,
+                index: _,
+            } => format!(
+                "Command {} has an invalid parameter: {}",
+                command, parameter
+            ),
         };
         write!(f, "{}", message)
     }
 }
 
+/// Why a message couldn't be serialized. CR, LF and NUL can't appear anywhere, since they would
+/// end the line early or be mistaken for the end of it.
 #[derive(Debug, PartialEq, Eq)]
+pub enum SerializeError {
+    InvalidTag {
+        key: String,
+    },
+    /// Prefixes can't be empty or contain spaces.
+    InvalidPrefix,
+    /// Commands can't be empty, contain spaces or start with a colon.
+    InvalidCommand,
+    /// Only the last parameter can be empty, contain spaces or start with a colon.
+    InvalidParameter {
+        index: usize,
+    },
+    /// The line is too long, and its last parameter couldn't be shortened to fit.
+    TooLong,
+}
+
+impl std::error::Error for SerializeError {}
+
+impl fmt::Display for SerializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerializeError::InvalidTag { key } => write!(f, "Invalid tag: {}", key),
+            SerializeError::InvalidPrefix => write!(f, "Invalid prefix"),
+            SerializeError::InvalidCommand => write!(f, "Invalid command"),
+            SerializeError::InvalidParameter { index } => {
+                write!(f, "Invalid parameter at index {}", index)
+            }
+            SerializeError::TooLong => write!(f, "Message is too long"),
+        }
+    }
+}
+
+/// When to put the last parameter of a message behind a colon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trailing {
+    /// Only when it is empty, contains spaces or starts with a colon.
+    IfNeeded,
+    /// Always, as is conventional for free text such as messages and the descriptions that end
+    /// numeric replies.
+    Always,
+}
+
+/// Whether a parameter can be sent without a colon, which is also what prefixes and commands
+/// must look like.
+fn is_middle_param(param: &str) -> bool {
+    !param.is_empty() && !param.starts_with(':') && !param.contains(&[' ', '\r', '\n', '\0'][..])
+}
+
+/// An IRCv3 message tag. Keys starting with `+` are client-only tags, which the server relays
+/// without interpreting.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Tag<'a> {
+    pub key: &'a str,
+    pub value: Option<Cow<'a, str>>,
+}
+
+impl<'a> Tag<'a> {
+    pub fn new(key: &'a str, value: Option<&'a str>) -> Self {
+        Tag {
+            key,
+            value: value.map(Cow::Borrowed),
+        }
+    }
+
+    pub fn is_client_only(&self) -> bool {
+        self.key.starts_with('+')
+    }
+}
+
+/// Returns the index of the first character at or after `start` that isn't a space.
+fn skip_spaces(s: &str, start: usize) -> usize {
+    start + s[start..].len() - s[start..].trim_start_matches(' ').len()
+}
+
+/// Parses the tags section of a message, without the leading `@`.
+///
+/// An empty value is the same as no value, and when a key is repeated the last value wins while
+/// the key keeps its original position.
+pub(crate) fn parse_tags(s: &str) -> Vec<Tag<'_>> {
+    let mut tags: Vec<Tag> = vec![];
+    for tag in s.split(';').filter(|tag| !tag.is_empty()) {
+        let (key, value) = match tag.find('=') {
+            Some(idx) => (&tag[..idx], Some(&tag[idx + 1..])),
+            None => (tag, None),
+        };
+        let value = value
+            .filter(|value| !value.is_empty())
+            .map(unescape_tag_value);
+
+        match tags.iter_mut().find(|tag| tag.key == key) {
+            Some(tag) => tag.value = value,
+            None => tags.push(Tag { key, value }),
+        }
+    }
+
+    tags
+}
+
+fn unescape_tag_value(value: &str) -> Cow<'_, str> {
+    if !value.contains('\\') {
+        return Cow::Borrowed(value);
+    }
+
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        // an unknown escape drops the backslash, and a trailing backslash is dropped entirely
+        match chars.next() {
+            Some(':') => unescaped.push(';'),
+            Some('s') => unescaped.push(' '),
+            Some('r') => unescaped.push('\r'),
+            Some('n') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => (),
+        }
+    }
+
+    Cow::Owned(unescaped)
+}
+
+/// Appends `s` to `line`, returning where it went.
+fn push_range(line: &mut String, s: &str) -> Range<usize> {
+    let start = line.len();
+    line.push_str(s);
+    start..line.len()
+}
+
+fn escape_tag_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ';' => escaped.push_str("\\:"),
+            ' ' => escaped.push_str("\\s"),
+            '\\' => escaped.push_str("\\\\"),
+            '\r' => escaped.push_str("\\r"),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct IrcMessage<'a> {
+    pub tags: Vec<Tag<'a>>,
     pub prefix: Option<&'a str>,
     pub command: &'a str,
     pub command_parameters: Vec<&'a str>,
 }
 
-impl IrcMessage<'_> {
+impl<'a> IrcMessage<'a> {
+    pub fn source(&self) -> Option<Source<'a>> {
+        self.prefix.map(Source::parse)
+    }
+
     /// Examples
     ///
     /// ```
     /// use ircd::structs::{Command, IrcMessage};
     ///
     /// let irc_message = IrcMessage{
+    ///     tags: vec![],
     ///     prefix: None,
     ///     command: "USER",
     ///     command_parameters: vec!["Cardinal", "8", "*", "Cardinal"],
@@ -55,8 +229,19 @@ impl IrcMessage<'_> {
     ///
     /// Ok::<(), String>(())
     /// ```
-    pub fn to_command(&self) -> Result<Command> {
-        match self.command {
+    pub fn to_command(&self) -> Result<Command<'a>> {
+        // commands are case-insensitive, but errors should name the command the client sent
+        let command = self.command.to_ascii_uppercase();
+        match command.as_str() {
+            "CAP" => {
+                let subcommand = self.get_command_parameter(0, "subcommand")?;
+                let argument = self.command_parameters.get(1).copied();
+                Ok(Command::CAP(subcommand, argument))
+            }
+            "AUTHENTICATE" => {
+                let argument = self.get_command_parameter(0, "argument")?;
+                Ok(Command::AUTHENTICATE(argument))
+            }
             "PASS" => {
                 let password = self.get_command_parameter(0, "password")?;
                 Ok(Command::PASS(password))
@@ -72,16 +257,222 @@ impl IrcMessage<'_> {
                 let realname = self.get_command_parameter(3, "realname")?;
                 Ok(Command::USER(user, mode, unused, realname))
             }
+            "OPER" => {
+                let name = self.get_command_parameter(0, "name")?;
+                let password = self.get_command_parameter(1, "password")?;
+                Ok(Command::OPER(name, password))
+            }
+            "MODE" => {
+                let target = self.get_command_parameter(0, "target")?;
+                let changes = self
+                    .command_parameters
+                    .get(1)
+                    .map_or_else(Vec::new, |modes| ModeChange::parse(modes));
+                let arguments = self.command_parameters.iter().skip(2).copied().collect();
+                Ok(Command::MODE(target, changes, arguments))
+            }
+            "SERVICE" => {
+                let nick = self.get_command_parameter(0, "nick")?;
+                let distribution = self.get_command_parameter(2, "distribution")?;
+                let service_type = self.get_command_parameter(3, "type")?;
+                let info = self.get_command_parameter(5, "info")?;
+                Ok(Command::SERVICE(nick, distribution, service_type, info))
+            }
+            "QUIT" => {
+                let message = self.command_parameters.first().copied();
+                Ok(Command::QUIT(message))
+            }
+            "SQUIT" => {
+                let server = self.get_command_parameter(0, "server")?;
+                let comment = self.get_command_parameter(1, "comment")?;
+                Ok(Command::SQUIT(server, comment))
+            }
+            "JOIN" => {
+                let channels = self.get_command_parameter(0, "channels")?;
+                let keys = self
+                    .command_parameters
+                    .get(1)
+                    .map_or_else(Vec::new, |k| list(k));
+                Ok(Command::JOIN(list(channels), keys))
+            }
+            "PART" => {
+                let channels = self.get_command_parameter(0, "channels")?;
+                let message = self.command_parameters.get(1).copied();
+                Ok(Command::PART(list(channels), message))
+            }
+            "TOPIC" => {
+                let channel = self.get_command_parameter(0, "channel")?;
+                let topic = self.command_parameters.get(1).copied();
+                Ok(Command::TOPIC(channel, topic))
+            }
+            "NAMES" | "LIST" => {
+                let channels = self
+                    .command_parameters
+                    .first()
+                    .map_or_else(Vec::new, |channels| list(channels));
+                let target = self.command_parameters.get(1).copied();
+                if command == "NAMES" {
+                    Ok(Command::NAMES(channels, target))
+                } else {
+                    Ok(Command::LIST(channels, target))
+                }
+            }
+            "INVITE" => {
+                let nick = self.get_command_parameter(0, "nick")?;
+                let channel = self.get_command_parameter(1, "channel")?;
+                Ok(Command::INVITE(nick, channel))
+            }
+            "KICK" => {
+                let channels = self.get_command_parameter(0, "channels")?;
+                let users = self.get_command_parameter(1, "users")?;
+                let comment = self.command_parameters.get(2).copied();
+                Ok(Command::KICK(list(channels), list(users), comment))
+            }
+            "PRIVMSG" | "NOTICE" => {
+                let targets = list(self.get_command_parameter(0, "targets")?);
+                let text = self.get_command_parameter(1, "text")?;
+                if command == "PRIVMSG" {
+                    Ok(Command::PRIVMSG(targets, text))
+                } else {
+                    Ok(Command::NOTICE(targets, text))
+                }
+            }
+            "MOTD" => {
+                let target = self.command_parameters.first().copied();
+                Ok(Command::MOTD(target))
+            }
+            "LUSERS" => {
+                let mask = self.command_parameters.first().copied();
+                let target = self.command_parameters.get(1).copied();
+                Ok(Command::LUSERS(mask, target))
+            }
+            "VERSION" => Ok(Command::VERSION(self.command_parameters.first().copied())),
+            "STATS" => {
+                let query = self
+                    .command_parameters
+                    .first()
+                    .and_then(|query| query.chars().next());
+                let target = self.command_parameters.get(1).copied();
+                Ok(Command::STATS(query, target))
+            }
+            "LINKS" => {
+                // the mask is last, following an optional server to ask
+                let (server, mask) = match self.command_parameters.as_slice() {
+                    [server, mask, ..] => (Some(*server), Some(*mask)),
+                    [mask] => (None, Some(*mask)),
+                    [] => (None, None),
+                };
+                Ok(Command::LINKS(server, mask))
+            }
+            "TIME" => Ok(Command::TIME(self.command_parameters.first().copied())),
+            "CONNECT" => {
+                let server = self.get_command_parameter(0, "server")?;
+                let port = self.get_command_parameter(1, "port")?;
+                let port = port
+                    .parse()
+                    .map_err(|_| self.invalid_command_parameter(1, "port"))?;
+                let remote = self.command_parameters.get(2).copied();
+                Ok(Command::CONNECT(server, port, remote))
+            }
+            "TRACE" => Ok(Command::TRACE(self.command_parameters.first().copied())),
+            "ADMIN" => Ok(Command::ADMIN(self.command_parameters.first().copied())),
+            "INFO" => Ok(Command::INFO(self.command_parameters.first().copied())),
+            "SERVLIST" => {
+                let mask = self.command_parameters.first().copied();
+                let service_type = self.command_parameters.get(1).copied();
+                Ok(Command::SERVLIST(mask, service_type))
+            }
+            "SQUERY" => {
+                let service = self.get_command_parameter(0, "service")?;
+                let text = self.get_command_parameter(1, "text")?;
+                Ok(Command::SQUERY(service, text))
+            }
+            "WHO" => {
+                let mask = self.command_parameters.first().copied();
+                let operators = self.command_parameters.get(1) == Some(&"o");
+                Ok(Command::WHO(mask, operators))
+            }
+            "WHOIS" => {
+                // the nicks are last, following an optional server to ask
+                let (server, nicks) = match self.command_parameters.as_slice() {
+                    [server, nicks, ..] => (Some(*server), *nicks),
+                    _ => (None, self.get_command_parameter(0, "nick")?),
+                };
+                Ok(Command::WHOIS(server, list(nicks)))
+            }
+            "WHOWAS" => {
+                let nicks = self.get_command_parameter(0, "nick")?;
+                // anything below one asks for every entry
+                let count = match self.command_parameters.get(1) {
+                    Some(count) => {
+                        let count: i64 = count
+                            .parse()
+                            .map_err(|_| self.invalid_command_parameter(1, "count"))?;
+                        usize::try_from(count).ok().filter(|count| *count > 0)
+                    }
+                    None => None,
+                };
+                let target = self.command_parameters.get(2).copied();
+                Ok(Command::WHOWAS(list(nicks), count, target))
+            }
+            "KILL" => {
+                let nick = self.get_command_parameter(0, "nick")?;
+                let comment = self.get_command_parameter(1, "comment")?;
+                Ok(Command::KILL(nick, comment))
+            }
+            "PING" | "PONG" => {
+                let token = self.get_command_parameter(0, "token")?;
+                let server = self.command_parameters.get(1).copied();
+                if command == "PING" {
+                    Ok(Command::PING(token, server))
+                } else {
+                    Ok(Command::PONG(token, server))
+                }
+            }
+            "ERROR" => {
+                let message = self.get_command_parameter(0, "message")?;
+                Ok(Command::ERROR(message))
+            }
+            "AWAY" => Ok(Command::AWAY(self.command_parameters.first().copied())),
+            "REHASH" => Ok(Command::REHASH),
+            "DIE" => Ok(Command::DIE),
+            "RESTART" => Ok(Command::RESTART),
+            "SUMMON" => {
+                let user = self.get_command_parameter(0, "user")?;
+                let target = self.command_parameters.get(1).copied();
+                let channel = self.command_parameters.get(2).copied();
+                Ok(Command::SUMMON(user, target, channel))
+            }
+            "USERS" => Ok(Command::USERS(self.command_parameters.first().copied())),
+            "WALLOPS" => {
+                let text = self.get_command_parameter(0, "text")?;
+                Ok(Command::WALLOPS(text))
+            }
+            "USERHOST" | "ISON" => {
+                self.get_command_parameter(0, "nick")?;
+                // some clients send the nicks as a single trailing parameter
+                let nicks = self
+                    .command_parameters
+                    .iter()
+                    .flat_map(|nicks| nicks.split(' '))
+                    .filter(|nick| !nick.is_empty())
+                    .collect();
+                if command == "USERHOST" {
+                    Ok(Command::USERHOST(nicks))
+                } else {
+                    Ok(Command::ISON(nicks))
+                }
+            }
             _ => Err(ParseError::UnknownCommandError {
                 command: self.command.to_owned(),
             }),
         }
     }
 
-    fn get_command_parameter(&self, idx: usize, name: &str) -> Result<&str> {
+    fn get_command_parameter(&self, idx: usize, name: &str) -> Result<&'a str> {
         let param = self.command_parameters.get(idx).ok_or_else(|| {
             ParseError::MissingCommandParameterError {
-                command: self.command.to_owned(),
+                command: self.command.to_ascii_uppercase(),
                 parameter: name.to_owned(),
                 index: idx,
             }
@@ -90,51 +481,141 @@ impl IrcMessage<'_> {
         Ok(param)
     }
 
+    fn invalid_command_parameter(&self, idx: usize, name: &str) -> ParseError {
+        ParseError::InvalidCommandParameterError {
+            command: self.command.to_ascii_uppercase(),
+            parameter: name.to_owned(),
+            index: idx,
+        }
+    }
+
+    /// Serializes the message, putting the last parameter behind a colon only when it needs one
+    /// or when asked to.
+    ///
+    /// A line longer than `MAX_MESSAGE_LEN` has its last parameter shortened when that parameter is
+    /// free text behind a colon, and can't be serialized otherwise.
+    ///
     /// Examples
     ///
     /// ```
-    /// use ircd::structs::{Command, IrcMessage};
+    /// use ircd::structs::{IrcMessage, Trailing};
     ///
     /// let irc_message = IrcMessage{
+    ///     tags: vec![],
     ///     prefix: Some("localhost"),
     ///     command: "PRIVMSG",
     ///     command_parameters: vec!["Cardinal", "this is an example"],
     /// };
-    /// let s = irc_message.to_line();
+    /// let s = irc_message.to_line(Trailing::IfNeeded)?;
     ///
     /// assert_eq!(s, ":localhost PRIVMSG Cardinal :this is an example\r\n".to_owned());
     ///
-    /// Ok::<(), String>(())
-    /// ```
+    /// let irc_message = IrcMessage{
+    ///     tags: vec![],
+    ///     prefix: None,
+    ///     command: "JOIN",
+    ///     command_parameters: vec!["#test"],
+    /// };
+    /// assert_eq!(irc_message.to_line(Trailing::IfNeeded)?, "JOIN #test\r\n");
+    /// assert_eq!(irc_message.to_line(Trailing::Always)?, "JOIN :#test\r\n");
     ///
-    /// Note: The last parameter will always be prefixed with a colon.
-    pub fn to_line(mut self) -> String {
-        let mut message = "".to_owned();
-        message.push_str(
-            self.prefix
-                .map_or("".to_string(), |s| format!(":{} ", s))
-                .as_str(),
-        );
-        message.push_str(self.command);
-
-        if self.command_parameters.len() > 0 {
-            message.push_str(" ");
-
-            // a little dance to stick the last param behind a colon to ensure that params with
-            // spaces work correctly (e.g. messages)
-            let mut params = self
-                .command_parameters
-                .drain(0..self.command_parameters.len() - 1)
-                .collect::<Vec<&str>>();
-            let last_param = format!(":{}", self.command_parameters.pop().unwrap());
-            params.push(last_param.as_str());
-
-            message.push_str(params.join(" ").as_str());
+    /// Ok::<(), ircd::structs::SerializeError>(())
+    /// ```
+    pub fn to_line(&self, trailing: Trailing) -> std::result::Result<String, SerializeError> {
+        self.serialize(trailing).map(|(line, _)| line)
+    }
+
+    /// Serializes the message, noting where each part of it ends up in the line.
+    pub(crate) fn serialize(
+        &self,
+        trailing: Trailing,
+    ) -> std::result::Result<(String, Layout), SerializeError> {
+        let mut line = String::new();
+        let mut layout = Layout::default();
+
+        if !self.tags.is_empty() {
+            line.push('@');
+            let start = line.len();
+            for (i, tag) in self.tags.iter().enumerate() {
+                if tag.key.is_empty() || tag.key.contains(&[' ', '=', ';', '\r', '\n', '\0'][..]) {
+                    return Err(SerializeError::InvalidTag {
+                        key: tag.key.to_owned(),
+                    });
+                }
+                if i > 0 {
+                    line.push(';');
+                }
+                line.push_str(tag.key);
+                if let Some(value) = &tag.value {
+                    // everything else can be escaped
+                    if value.contains('\0') {
+                        return Err(SerializeError::InvalidTag {
+                            key: tag.key.to_owned(),
+                        });
+                    }
+                    line.push('=');
+                    line.push_str(&escape_tag_value(value));
+                }
+            }
+            layout.tags = Some(start..line.len());
+            line.push(' ');
+            if line.len() > MAX_TAGS_LEN {
+                return Err(SerializeError::TooLong);
+            }
+        }
+        let tags_len = line.len();
+
+        if let Some(prefix) = self.prefix {
+            if !is_middle_param(prefix) {
+                return Err(SerializeError::InvalidPrefix);
+            }
+            line.push(':');
+            layout.prefix = Some(push_range(&mut line, prefix));
+            line.push(' ');
         }
+        if !is_middle_param(self.command) {
+            return Err(SerializeError::InvalidCommand);
+        }
+        layout.command = push_range(&mut line, self.command);
 
-        message.push_str("\r\n");
+        let mut trailer = false;
+        for (index, param) in self.command_parameters.iter().enumerate() {
+            line.push(' ');
+            if index + 1 < self.command_parameters.len() {
+                if !is_middle_param(param) {
+                    return Err(SerializeError::InvalidParameter { index });
+                }
+            } else {
+                if param.contains(&['\r', '\n', '\0'][..]) {
+                    return Err(SerializeError::InvalidParameter { index });
+                }
+                // params with spaces (e.g. messages) must be the last, behind a colon
+                trailer = trailing == Trailing::Always || !is_middle_param(param);
+                if trailer {
+                    line.push(':');
+                }
+            }
+            layout.parameters.push(push_range(&mut line, param));
+        }
+
+        let len = line.len() - tags_len + "\r\n".len();
+        if len > MAX_MESSAGE_LEN {
+            let last = match layout.parameters.last_mut() {
+                Some(last) if trailer && last.len() >= len - MAX_MESSAGE_LEN => last,
+                _ => return Err(SerializeError::TooLong),
+            };
+            // without splitting a character in two
+            let mut end = last.end - (len - MAX_MESSAGE_LEN);
+            while !line.is_char_boundary(end) {
+                end -= 1;
+            }
+            line.truncate(end);
+            last.end = end;
+        }
 
-        message
+        line.push_str("\r\n");
+
+        Ok((line, layout))
     }
 }
 
@@ -151,6 +632,7 @@ impl<'a> TryFrom<&'a str> for IrcMessage<'a> {
     /// let irc_message = IrcMessage::try_from(s)?;
     ///
     /// assert_eq!(irc_message, IrcMessage {
+    ///     tags: vec![],
     ///     prefix: Some("irc.darkscience.net"),
     ///     command: "PRIVMSG",
     ///     command_parameters: vec!["Cardinal", "this is a test"],
@@ -159,15 +641,33 @@ impl<'a> TryFrom<&'a str> for IrcMessage<'a> {
     /// Ok::<(), String>(())
     /// ```
     fn try_from(s: &'a str) -> std::result::Result<Self, Self::Error> {
-        if s == "" {
+        if s.is_empty() {
             return Err(Self::Error::from("IRC message may not be empty"));
         }
 
         let mut start = 0;
 
+        // check for optional tags
+        let tags = match s.strip_prefix('@') {
+            Some(rest) => match rest.find(' ') {
+                // tags must be followed by a command
+                None => {
+                    return Err(Self::Error::from(
+                        "Found tags indication, followed by no command",
+                    ))
+                }
+                Some(tags_end) => {
+                    // skip over the @ and the spaces that follow the tags as well
+                    start = skip_spaces(s, tags_end + 2);
+                    parse_tags(&rest[..tags_end])
+                }
+            },
+            None => vec![],
+        };
+
         // check for optional prefix
         let prefix: Option<&str> = {
-            match s.find(':') {
+            match s[start..].find(':') {
                 Some(0) => {
                     start += 1;
                     match &s[start..].find(' ') {
@@ -179,9 +679,9 @@ impl<'a> TryFrom<&'a str> for IrcMessage<'a> {
                             ))
                         }
                         Some(prefix_end) => {
-                            let prefix = &s[start..*prefix_end + 1];
-                            // skip over the space that follows the prefix as well
-                            start += *prefix_end + 1;
+                            let prefix = &s[start..start + *prefix_end];
+                            // skip over the spaces that follow the prefix as well
+                            start = skip_spaces(s, start + *prefix_end);
                             Some(prefix)
                         }
                     }
@@ -201,6 +701,9 @@ impl<'a> TryFrom<&'a str> for IrcMessage<'a> {
 
             command
         };
+        if command.is_empty() {
+            return Err(Self::Error::from("IRC message is missing a command"));
+        }
 
         // check for optional command parameters
         let command_parameters: Vec<&str> = {
@@ -218,127 +721,253 @@ impl<'a> TryFrom<&'a str> for IrcMessage<'a> {
                 }
             };
 
-            let mut command_parameters: Vec<&str> = if start < end {
-                // skip over the leftover space that follows the command
-                start += 1;
-                s[start..end].split(" ").collect()
-            } else {
-                vec![]
-            };
+            // parameters may be separated by more than one space
+            let mut command_parameters: Vec<&str> = s[start..end]
+                .split(' ')
+                .filter(|parameter| !parameter.is_empty())
+                .collect();
 
             // add trailer if there was one
-            if trailer.is_some() {
-                command_parameters.push(trailer.unwrap());
+            if let Some(trailer) = trailer {
+                command_parameters.push(trailer);
             }
 
             command_parameters
         };
 
         Ok(IrcMessage {
-            prefix: prefix,
-            command: command,
-            command_parameters: command_parameters,
+            tags,
+            prefix,
+            command,
+            command_parameters,
         })
     }
 }
 
-#[allow(non_camel_case_types)]
-pub enum Reply {
-    RPL_WELCOME {
-        nick: String,
-        user: String,
-        host: String,
-    },
-    RPL_YOURHOST {
-        nick: String,
-        server_name: String,
-        version: String,
-    },
-    // RPL_CREATED(String, String, String),
-    // RPL_MYINFO(String, String, String),
-    ERR_UNKNOWNCOMMAND {
-        command: String,
-    },
-    ERR_NEEDMOREPARAMS {
-        command: String,
-    },
+/// A command sent by a client, with its parameters. Comma-separated lists are split, and
+/// parameters the client may leave out are `None` or empty.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command<'a> {
+    CAP(&'a str, Option<&'a str>),
+    AUTHENTICATE(&'a str),
+
+    // Connection registration
+    PASS(&'a str),
+    NICK(&'a str),
+    /// `USER <user> <mode> <unused> <realname>`
+    USER(&'a str, &'a str, &'a str, &'a str),
+    OPER(&'a str, &'a str),
+    /// `MODE <nick or channel> [<modes> [<arguments>...]]`, where no modes is a query.
+    MODE(&'a str, Vec<ModeChange>, Vec<&'a str>),
+    /// `SERVICE <nick> <reserved> <distribution> <type> <reserved> <info>`
+    SERVICE(&'a str, &'a str, &'a str, &'a str),
+    QUIT(Option<&'a str>),
+    SQUIT(&'a str, &'a str),
+
+    // Channel operations
+    /// `JOIN <channels> [<keys>]`, where the channels are just `0` to leave every channel.
+    JOIN(Vec<&'a str>, Vec<&'a str>),
+    PART(Vec<&'a str>, Option<&'a str>),
+    TOPIC(&'a str, Option<&'a str>),
+    NAMES(Vec<&'a str>, Option<&'a str>),
+    LIST(Vec<&'a str>, Option<&'a str>),
+    INVITE(&'a str, &'a str),
+    /// `KICK <channels> <users> [<comment>]`
+    KICK(Vec<&'a str>, Vec<&'a str>, Option<&'a str>),
+
+    // Sending messages
+    PRIVMSG(Vec<&'a str>, &'a str),
+    NOTICE(Vec<&'a str>, &'a str),
+
+    // Server queries and commands
+    MOTD(Option<&'a str>),
+    /// `LUSERS [<mask> [<target>]]`
+    LUSERS(Option<&'a str>, Option<&'a str>),
+    VERSION(Option<&'a str>),
+    /// `STATS [<query letter> [<target>]]`
+    STATS(Option<char>, Option<&'a str>),
+    /// `LINKS [[<server to ask>] <mask>]`
+    LINKS(Option<&'a str>, Option<&'a str>),
+    TIME(Option<&'a str>),
+    /// `CONNECT <server> <port> [<remote server>]`
+    CONNECT(&'a str, u16, Option<&'a str>),
+    TRACE(Option<&'a str>),
+    ADMIN(Option<&'a str>),
+    INFO(Option<&'a str>),
+
+    // Service queries and commands
+    /// `SERVLIST [<mask> [<type>]]`
+    SERVLIST(Option<&'a str>, Option<&'a str>),
+    SQUERY(&'a str, &'a str),
+
+    // User based queries
+    /// `WHO [<mask> ["o"]]`, where `o` asks for operators only.
+    WHO(Option<&'a str>, bool),
+    /// `WHOIS [<server to ask>] <nicks>`
+    WHOIS(Option<&'a str>, Vec<&'a str>),
+    /// `WHOWAS <nicks> [<count> [<target>]]`, where there's no count for every entry.
+    WHOWAS(Vec<&'a str>, Option<usize>, Option<&'a str>),
+
+    // Miscellaneous messages
+    KILL(&'a str, &'a str),
+    /// `PING <token> [<server>]`
+    PING(&'a str, Option<&'a str>),
+    /// `PONG <token> [<server>]`
+    PONG(&'a str, Option<&'a str>),
+    ERROR(&'a str),
+
+    // Optional features
+    AWAY(Option<&'a str>),
+    REHASH,
+    DIE,
+    RESTART,
+    /// `SUMMON <user> [<target> [<channel>]]`
+    SUMMON(&'a str, Option<&'a str>, Option<&'a str>),
+    USERS(Option<&'a str>),
+    WALLOPS(&'a str),
+    USERHOST(Vec<&'a str>),
+    ISON(Vec<&'a str>),
 }
 
-impl Reply {
-    fn as_str(&self) -> &str {
+impl Command<'_> {
+    pub fn name(&self) -> &'static str {
         match self {
-            Reply::RPL_WELCOME {
-                nick: _,
-                user: _,
-                host: _,
-            } => "001",
-            Reply::RPL_YOURHOST {
-                nick: _,
-                server_name: _,
-                version: _,
-            } => "002",
-            // Reply::RPL_CREATED(_, _, _) => "003",
-            // Reply::RPL_MYINFO(_, _, _) => "004",
-            Reply::ERR_UNKNOWNCOMMAND { command: _ } => "421",
-            Reply::ERR_NEEDMOREPARAMS { command: _ } => "461",
+            Command::CAP(..) => "CAP",
+            Command::AUTHENTICATE(..) => "AUTHENTICATE",
+            Command::PASS(..) => "PASS",
+            Command::NICK(..) => "NICK",
+            Command::USER(..) => "USER",
+            Command::OPER(..) => "OPER",
+            Command::MODE(..) => "MODE",
+            Command::SERVICE(..) => "SERVICE",
+            Command::QUIT(..) => "QUIT",
+            Command::SQUIT(..) => "SQUIT",
+            Command::JOIN(..) => "JOIN",
+            Command::PART(..) => "PART",
+            Command::TOPIC(..) => "TOPIC",
+            Command::NAMES(..) => "NAMES",
+            Command::LIST(..) => "LIST",
+            Command::INVITE(..) => "INVITE",
+            Command::KICK(..) => "KICK",
+            Command::PRIVMSG(..) => "PRIVMSG",
+            Command::NOTICE(..) => "NOTICE",
+            Command::MOTD(..) => "MOTD",
+            Command::LUSERS(..) => "LUSERS",
+            Command::VERSION(..) => "VERSION",
+            Command::STATS(..) => "STATS",
+            Command::LINKS(..) => "LINKS",
+            Command::TIME(..) => "TIME",
+            Command::CONNECT(..) => "CONNECT",
+            Command::TRACE(..) => "TRACE",
+            Command::ADMIN(..) => "ADMIN",
+            Command::INFO(..) => "INFO",
+            Command::SERVLIST(..) => "SERVLIST",
+            Command::SQUERY(..) => "SQUERY",
+            Command::WHO(..) => "WHO",
+            Command::WHOIS(..) => "WHOIS",
+            Command::WHOWAS(..) => "WHOWAS",
+            Command::KILL(..) => "KILL",
+            Command::PING(..) => "PING",
+            Command::PONG(..) => "PONG",
+            Command::ERROR(..) => "ERROR",
+            Command::AWAY(..) => "AWAY",
+            Command::REHASH => "REHASH",
+            Command::DIE => "DIE",
+            Command::RESTART => "RESTART",
+            Command::SUMMON(..) => "SUMMON",
+            Command::USERS(..) => "USERS",
+            Command::WALLOPS(..) => "WALLOPS",
+            Command::USERHOST(..) => "USERHOST",
+            Command::ISON(..) => "ISON",
         }
     }
+}
 
-    pub fn as_line(&self) -> String {
-        match self {
-            // Command responses
-            Reply::RPL_WELCOME { nick, user, host } => IrcMessage {
-                prefix: Some("localhost"),
-                command: self.as_str(),
-                command_parameters: vec![
-                    nick,
-                    format!("Welcome to the network {}!{}@{}", nick, user, host).as_str(),
-                ],
-            }
-            .to_line(),
-            Reply::RPL_YOURHOST {
-                nick,
-                server_name,
-                version,
-            } => IrcMessage {
-                prefix: Some("localhost"),
-                command: self.as_str(),
-                command_parameters: vec![
-                    nick,
-                    &format!(
-                        "Your host is {}, running ircd version {}",
-                        server_name, version
-                    ),
-                ],
-            }
-            .to_line(),
+/// One mode being set or unset, from a mode string such as `+o-v`. Which modes take an argument
+/// depends on the server, so arguments are left to the handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModeChange {
+    pub adding: bool,
+    pub mode: char,
+}
 
-            // Error replies
-            Reply::ERR_UNKNOWNCOMMAND { command } => IrcMessage {
-                prefix: Some("localhost"),
-                command: self.as_str(),
-                command_parameters: vec![command, "Unknown command"],
-            }
-            .to_line(),
-            Reply::ERR_NEEDMOREPARAMS { command } => IrcMessage {
-                prefix: Some("localhost"),
-                command: self.as_str(),
-                command_parameters: vec![command, "Not enough parameters"],
+impl ModeChange {
+    /// Modes before the first `+` or `-` are added.
+    ///
+    /// Examples
+    ///
+    /// ```
+    /// use ircd::structs::ModeChange;
+    ///
+    /// assert_eq!(
+    ///     ModeChange::parse("+o-v"),
+    ///     vec![
+    ///         ModeChange { adding: true, mode: 'o' },
+    ///         ModeChange { adding: false, mode: 'v' },
+    ///     ]
+    /// );
+    /// ```
+    pub fn parse(modes: &str) -> Vec<ModeChange> {
+        let mut adding = true;
+        let mut changes = vec![];
+        for c in modes.chars() {
+            match c {
+                '+' => adding = true,
+                '-' => adding = false,
+                mode => changes.push(ModeChange { adding, mode }),
             }
-            .to_line(),
         }
+
+        changes
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub enum Command<'a> {
-    PASS(&'a str),
-    NICK(&'a str),
-    USER(&'a str, &'a str, &'a str, &'a str),
+/// A message prefix split into its parts: `nick[!user][@host]`, or a server name, which is left
+/// in `nick`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Source<'a> {
+    pub nick: &'a str,
+    pub user: Option<&'a str>,
+    pub host: Option<&'a str>,
+}
+
+impl<'a> Source<'a> {
+    /// Examples
+    ///
+    /// ```
+    /// use ircd::structs::Source;
+    ///
+    /// assert_eq!(
+    ///     Source::parse("Cardinal!cardinal@127.0.0.1"),
+    ///     Source { nick: "Cardinal", user: Some("cardinal"), host: Some("127.0.0.1") }
+    /// );
+    /// assert_eq!(
+    ///     Source::parse("irc.example.com"),
+    ///     Source { nick: "irc.example.com", user: None, host: None }
+    /// );
+    /// ```
+    pub fn parse(prefix: &'a str) -> Self {
+        let (rest, host) = match prefix.split_once('@') {
+            Some((rest, host)) => (rest, Some(host)),
+            None => (prefix, None),
+        };
+        let (nick, user) = match rest.split_once('!') {
+            Some((nick, user)) => (nick, Some(user)),
+            None => (rest, None),
+        };
+
+        Source { nick, user, host }
+    }
+}
+
+fn list(s: &str) -> Vec<&str> {
+    s.split(',').collect()
 }
 
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
     use super::*;
 
     #[test]
@@ -349,6 +978,7 @@ mod tests {
         assert_eq!(
             irc_message,
             IrcMessage {
+                tags: vec![],
                 prefix: None,
                 command: "LIST",
                 command_parameters: vec![],
@@ -366,6 +996,7 @@ mod tests {
         assert_eq!(
             irc_message,
             IrcMessage {
+                tags: vec![],
                 prefix: Some("irc.darkscience.net"),
                 command: "LIST",
                 command_parameters: vec![],
@@ -383,6 +1014,7 @@ mod tests {
         assert_eq!(
             irc_message,
             IrcMessage {
+                tags: vec![],
                 prefix: None,
                 command: "PRIVMSG",
                 command_parameters: vec!["Cardinal", "this is a test"],
@@ -400,6 +1032,7 @@ mod tests {
         assert_eq!(
             irc_message,
             IrcMessage {
+                tags: vec![],
                 prefix: None,
                 command: "MODE",
                 command_parameters: vec!["#test", "+v", "Cardinal"],
@@ -417,6 +1050,7 @@ mod tests {
         assert_eq!(
             irc_message,
             IrcMessage {
+                tags: vec![],
                 prefix: None,
                 command: "PONG",
                 command_parameters: vec!["irc.darkscience.net"],
@@ -425,4 +1059,356 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn tags() -> std::result::Result<(), String> {
+        let s = "@id=123;+example.com/draft=a\\sb\\:c\\\\d\\re\\nf;flag :nick!user@host PRIVMSG #test :hi";
+        let irc_message = IrcMessage::try_from(s)?;
+
+        assert_eq!(
+            irc_message,
+            IrcMessage {
+                tags: vec![
+                    Tag::new("id", Some("123")),
+                    Tag::new("+example.com/draft", Some("a b;c\\d\re\nf")),
+                    Tag::new("flag", None),
+                ],
+                prefix: Some("nick!user@host"),
+                command: "PRIVMSG",
+                command_parameters: vec!["#test", "hi"],
+            }
+        );
+        assert!(!irc_message.tags[0].is_client_only());
+        assert!(irc_message.tags[1].is_client_only());
+
+        Ok(())
+    }
+
+    #[test]
+    fn tags_empty_and_repeated() -> std::result::Result<(), String> {
+        let s = "@a=1;b=;c=\\q\\;a=2 PING";
+        let irc_message = IrcMessage::try_from(s)?;
+
+        assert_eq!(
+            irc_message.tags,
+            vec![
+                Tag::new("a", Some("2")),
+                Tag::new("b", None),
+                Tag::new("c", Some("q")),
+            ]
+        );
+        assert_eq!(irc_message.command, "PING");
+
+        Ok(())
+    }
+
+    #[test]
+    fn tags_without_command() {
+        assert!(IrcMessage::try_from("@a=1").is_err());
+    }
+
+    #[test]
+    fn tags_serialized_in_order() {
+        let irc_message = IrcMessage {
+            tags: vec![
+                Tag::new("time", Some("2020-01-20T12:27:00.000Z")),
+                Tag::new("+draft/reply", Some("a b;c\\d\r\n")),
+                Tag::new("flag", None),
+            ],
+            prefix: Some("localhost"),
+            command: "NOTICE",
+            command_parameters: vec!["*", "hi"],
+        };
+
+        assert_eq!(
+            irc_message.to_line(Trailing::Always).unwrap(),
+            "@time=2020-01-20T12:27:00.000Z;+draft/reply=a\\sb\\:c\\\\d\\r\\n;flag :localhost NOTICE * :hi\r\n"
+        );
+    }
+
+    #[test]
+    fn trailing_only_when_needed() {
+        let line = |params: Vec<&str>| {
+            IrcMessage {
+                tags: vec![],
+                prefix: None,
+                command: "TOPIC",
+                command_parameters: params,
+            }
+            .to_line(Trailing::IfNeeded)
+        };
+
+        assert_eq!(line(vec![]), Ok("TOPIC\r\n".to_owned()));
+        assert_eq!(
+            line(vec!["#test", "hi"]),
+            Ok("TOPIC #test hi\r\n".to_owned())
+        );
+        assert_eq!(line(vec!["#test", ""]), Ok("TOPIC #test :\r\n".to_owned()));
+        assert_eq!(
+            line(vec!["#test", ":)"]),
+            Ok("TOPIC #test ::)\r\n".to_owned())
+        );
+        assert_eq!(
+            line(vec!["#test", "a b"]),
+            Ok("TOPIC #test :a b\r\n".to_owned())
+        );
+    }
+
+    #[test]
+    fn invalid_messages() {
+        let message = |prefix, command, params| IrcMessage {
+            tags: vec![],
+            prefix,
+            command,
+            command_parameters: params,
+        };
+        let line = |message: IrcMessage| message.to_line(Trailing::Always);
+
+        // a second command can't be smuggled in
+        assert_eq!(
+            line(message(None, "TOPIC", vec!["#test", "hi\r\nQUIT"])),
+            Err(SerializeError::InvalidParameter { index: 1 })
+        );
+        assert_eq!(
+            line(message(Some("a\nb"), "NICK", vec!["Cardinal"])),
+            Err(SerializeError::InvalidPrefix)
+        );
+        assert_eq!(
+            line(message(None, "TOPIC", vec!["#test", "nul\0"])),
+            Err(SerializeError::InvalidParameter { index: 1 })
+        );
+        for param in &["", "a b", ":a"] {
+            assert_eq!(
+                line(message(None, "KICK", vec!["#test", param, "bye"])),
+                Err(SerializeError::InvalidParameter { index: 1 })
+            );
+        }
+        assert_eq!(
+            line(message(Some(""), "PING", vec![])),
+            Err(SerializeError::InvalidPrefix)
+        );
+        assert_eq!(
+            line(message(None, "", vec![])),
+            Err(SerializeError::InvalidCommand)
+        );
+        assert_eq!(
+            line(IrcMessage {
+                tags: vec![Tag::new("a;b", None)],
+                ..message(None, "PING", vec![])
+            }),
+            Err(SerializeError::InvalidTag {
+                key: "a;b".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn long_lines() {
+        let text = "é".repeat(300);
+        let line = IrcMessage {
+            tags: vec![Tag::new("time", Some("2020-01-20T12:27:00.000Z"))],
+            prefix: Some("irc.example.com"),
+            command: "NOTICE",
+            command_parameters: vec!["*", &text],
+        }
+        .to_line(Trailing::Always)
+        .unwrap();
+
+        // tags don't count, and the text is cut between characters
+        let without_tags = &line["@time=2020-01-20T12:27:00.000Z ".len()..];
+        assert_eq!(without_tags.len(), MAX_MESSAGE_LEN - 1);
+        assert!(without_tags.ends_with("éé\r\n"));
+
+        // only text behind a colon can be shortened
+        let target = "a".repeat(600);
+        assert_eq!(
+            IrcMessage {
+                tags: vec![],
+                prefix: None,
+                command: "NOTICE",
+                command_parameters: vec![&target],
+            }
+            .to_line(Trailing::IfNeeded),
+            Err(SerializeError::TooLong)
+        );
+    }
+
+    #[test]
+    fn unusual_spacing_and_colons() -> std::result::Result<(), String> {
+        let irc_message = IrcMessage::try_from(":nick!user@host  MODE   #test  +o:x   Cardinal  ")?;
+        assert_eq!(irc_message.prefix, Some("nick!user@host"));
+        assert_eq!(irc_message.command, "MODE");
+        assert_eq!(
+            irc_message.command_parameters,
+            vec!["#test", "+o:x", "Cardinal"]
+        );
+
+        let irc_message = IrcMessage::try_from("PRIVMSG #test  ::) ")?;
+        assert_eq!(irc_message.command_parameters, vec!["#test", ":) "]);
+
+        let irc_message = IrcMessage::try_from("AWAY :")?;
+        assert_eq!(irc_message.command_parameters, vec![""]);
+
+        for s in &[":", "@", "@a ", ":nick ", ":nick  ", " LIST", "@a=b :nick"] {
+            assert!(IrcMessage::try_from(*s).is_err(), "{:?} parsed", s);
+        }
+
+        Ok(())
+    }
+
+    fn tag_strategy() -> impl Strategy<Value = Vec<(String, Option<String>)>> {
+        prop::collection::btree_map(
+            "\\+?[a-z0-9/.-]{1,10}",
+            prop::option::of("[^\\x00]{1,10}"),
+            0..4,
+        )
+        .prop_map(|tags| tags.into_iter().collect())
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip(
+            tags in tag_strategy(),
+            prefix in prop::option::of("[^ \r\n\\x00:][^ \r\n\\x00]{0,15}"),
+            command in "[A-Za-z]{1,10}|[0-9]{3}",
+            middle in prop::collection::vec("[^ \r\n\\x00:][^ \r\n\\x00]{0,10}", 0..6),
+            last in prop::option::of("[^\r\n\\x00]{0,20}"),
+            always in any::<bool>(),
+        ) {
+            let mut command_parameters: Vec<&str> = middle.iter().map(String::as_str).collect();
+            command_parameters.extend(last.as_deref());
+            let irc_message = IrcMessage {
+                tags: tags
+                    .iter()
+                    .map(|(key, value)| Tag::new(key, value.as_deref()))
+                    .collect(),
+                prefix: prefix.as_deref(),
+                command: &command,
+                command_parameters,
+            };
+            let trailing = if always { Trailing::Always } else { Trailing::IfNeeded };
+
+            let line = irc_message.to_line(trailing).unwrap();
+            let parsed = IrcMessage::try_from(line.strip_suffix("\r\n").unwrap()).unwrap();
+            prop_assert_eq!(parsed, irc_message);
+        }
+
+        #[test]
+        fn arbitrary_lines(line in "[^\r\n]{0,200}") {
+            // anything that parses and can be written back out parses the same way again
+            if let Ok(irc_message) = IrcMessage::try_from(line.as_str()) {
+                let _ = irc_message.to_command();
+                if let Ok(serialized) = irc_message.to_line(Trailing::IfNeeded) {
+                    let parsed = IrcMessage::try_from(serialized.strip_suffix("\r\n").unwrap());
+                    prop_assert_eq!(parsed, Ok(irc_message));
+                }
+            }
+        }
+
+        #[test]
+        fn long_text_truncated(text in "[^\r\n\\x00]{0,600}") {
+            let irc_message = IrcMessage {
+                tags: vec![],
+                prefix: Some("irc.example.com"),
+                command: "PRIVMSG",
+                command_parameters: vec!["#test", &text],
+            };
+
+            let line = irc_message.to_line(Trailing::Always).unwrap();
+            prop_assert!(line.len() <= MAX_MESSAGE_LEN);
+            let parsed = IrcMessage::try_from(line.strip_suffix("\r\n").unwrap()).unwrap();
+            prop_assert!(text.starts_with(parsed.command_parameters[1]));
+        }
+    }
+
+    fn command(s: &str) -> Result<Command<'_>> {
+        IrcMessage::try_from(s).expect("valid message").to_command()
+    }
+
+    #[test]
+    fn commands_case_insensitive() {
+        assert_eq!(
+            command("privmsg #test,Cardinal :hi there").unwrap(),
+            Command::PRIVMSG(vec!["#test", "Cardinal"], "hi there")
+        );
+        assert_eq!(
+            command("User cardinal 0 * :Cardinal Person").unwrap(),
+            Command::USER("cardinal", "0", "*", "Cardinal Person")
+        );
+        assert!(matches!(
+            command("nick"),
+            Err(ParseError::MissingCommandParameterError { command, .. }) if command == "NICK"
+        ));
+        assert!(matches!(
+            command("foo"),
+            Err(ParseError::UnknownCommandError { command }) if command == "foo"
+        ));
+    }
+
+    #[test]
+    fn typed_parameters() {
+        assert_eq!(
+            command("MODE #test +o-v Cardinal Other").unwrap(),
+            Command::MODE(
+                "#test",
+                vec![
+                    ModeChange {
+                        adding: true,
+                        mode: 'o'
+                    },
+                    ModeChange {
+                        adding: false,
+                        mode: 'v'
+                    },
+                ],
+                vec!["Cardinal", "Other"]
+            )
+        );
+        assert_eq!(
+            command("MODE Cardinal").unwrap(),
+            Command::MODE("Cardinal", vec![], vec![])
+        );
+        assert_eq!(
+            command("JOIN #a,#b key").unwrap(),
+            Command::JOIN(vec!["#a", "#b"], vec!["key"])
+        );
+        assert_eq!(
+            command("KICK #test Cardinal,Other").unwrap(),
+            Command::KICK(vec!["#test"], vec!["Cardinal", "Other"], None)
+        );
+        assert_eq!(command("LIST").unwrap(), Command::LIST(vec![], None));
+        assert_eq!(
+            command("LINKS *.example.com").unwrap(),
+            Command::LINKS(None, Some("*.example.com"))
+        );
+        assert_eq!(command("STATS u").unwrap(), Command::STATS(Some('u'), None));
+        assert_eq!(
+            command("WHO #test o").unwrap(),
+            Command::WHO(Some("#test"), true)
+        );
+        assert_eq!(
+            command("WHOIS irc.example.com Cardinal,Other").unwrap(),
+            Command::WHOIS(Some("irc.example.com"), vec!["Cardinal", "Other"])
+        );
+        assert_eq!(
+            command("WHOWAS Cardinal 3").unwrap(),
+            Command::WHOWAS(vec!["Cardinal"], Some(3), None)
+        );
+        assert_eq!(
+            command("WHOWAS Cardinal -1").unwrap(),
+            Command::WHOWAS(vec!["Cardinal"], None, None)
+        );
+        assert_eq!(
+            command("ISON Cardinal :Other Nobody").unwrap(),
+            Command::ISON(vec!["Cardinal", "Other", "Nobody"])
+        );
+        assert_eq!(
+            command("CONNECT irc.example.org 6667").unwrap(),
+            Command::CONNECT("irc.example.org", 6667, None)
+        );
+        assert!(matches!(
+            command("CONNECT irc.example.org port"),
+            Err(ParseError::InvalidCommandParameterError { index: 1, .. })
+        ));
+    }
 }
@@ -0,0 +1,65 @@
+//! Helpers for driving a `Server` from unit tests without any sockets.
+
+use bytes::Bytes;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+use crate::accounts::TomlAccountStore;
+use crate::client::ClientId;
+use crate::config::Config;
+use crate::server::Server;
+
+pub fn server() -> Server {
+    server_with(|_| ())
+}
+
+/// A server whose configuration has been adjusted after parsing.
+pub fn server_with(configure: impl FnOnce(&mut Config)) -> Server {
+    let mut config = toml::from_str(
+        "[irc]\nhostname = \"irc.example.com\"\ncreated_at = 2020-01-20T12:27:00-04:00\n",
+    )
+    .unwrap();
+    configure(&mut config);
+    // SASL is only offered with an account store, even an empty one
+    Server::new(config).with_account_store(TomlAccountStore::default())
+}
+
+/// Connects a new client, returning the receiving end of everything the server sends it.
+pub fn connect(server: &Server) -> (ClientId, UnboundedReceiver<Bytes>) {
+    let (sender, receiver) = unbounded_channel();
+    let id = server.connect(Some("127.0.0.1:50000".parse().unwrap()), sender);
+    (id, receiver)
+}
+
+/// Connects and registers a client, discarding the welcome burst.
+pub fn register(server: &Server, nick: &str) -> (ClientId, UnboundedReceiver<Bytes>) {
+    let (id, mut receiver) = connect(server);
+    send(
+        server,
+        id,
+        &[
+            &format!("NICK {}", nick),
+            &format!("USER {} 0 * :{}", nick, nick),
+        ],
+    );
+    received(&mut receiver);
+    (id, receiver)
+}
+
+/// Handles each line in turn, checking any password a command waits on right away rather than
+/// on a blocking thread.
+pub fn send(server: &Server, id: ClientId, lines: &[&str]) {
+    for line in lines {
+        let mut check = server.handle_line(id, line);
+        while let Some(pending) = check {
+            check = server.finish_password_check(id, pending.verify(server.accounts().as_deref()));
+        }
+    }
+}
+
+pub fn received(receiver: &mut UnboundedReceiver<Bytes>) -> Vec<String> {
+    let mut lines = vec![];
+    while let Ok(line) = receiver.try_recv() {
+        lines.push(String::from_utf8(line.to_vec()).unwrap());
+    }
+    lines
+}
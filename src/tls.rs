@@ -0,0 +1,127 @@
+//! TLS for client connections, including the client certificates used for CertFP.
+
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+use tokio_rustls::rustls::client::danger::HandshakeSignatureValid;
+use tokio_rustls::rustls::crypto::{
+    ring, verify_tls12_signature, verify_tls13_signature, CryptoProvider,
+};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, UnixTime};
+use tokio_rustls::rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use tokio_rustls::rustls::{
+    DigitallySignedStruct, DistinguishedName, Error, ServerConfig, ServerConnection,
+    SignatureScheme,
+};
+use tokio_rustls::TlsAcceptor;
+
+use crate::config::Tls;
+
+/// Builds an acceptor from the certificate chain and private key named in the configuration.
+///
+/// Clients may present a certificate of their own, which is only used to identify them by its
+/// fingerprint.
+pub fn acceptor(tls: &Tls) -> Result<TlsAcceptor, String> {
+    let certificates = CertificateDer::pem_file_iter(&tls.certificate)
+        .and_then(|certificates| certificates.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("Error reading certificate {}: {}", tls.certificate, e))?;
+    let key = PrivateKeyDer::from_pem_file(&tls.key)
+        .map_err(|e| format!("Error reading private key {}: {}", tls.key, e))?;
+
+    let provider = Arc::new(ring::default_provider());
+    let config = ServerConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| format!("Error configuring TLS: {}", e))?
+        .with_client_cert_verifier(Arc::new(AnyClientCertificate { provider }))
+        .with_single_cert(certificates, key)
+        .map_err(|e| format!("Error configuring TLS: {}", e))?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// The fingerprint of the certificate a client presented during the handshake, if any.
+pub fn certfp(connection: &ServerConnection) -> Option<String> {
+    let certificate = connection.peer_certificates()?.first()?;
+    Some(fingerprint(certificate))
+}
+
+/// The lowercase hex SHA-256 of a DER encoded certificate.
+///
+/// Examples
+///
+/// ```
+/// use ircd::tls::fingerprint;
+///
+/// assert_eq!(
+///     fingerprint(b""),
+///     "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+/// );
+/// ```
+pub fn fingerprint(certificate: &[u8]) -> String {
+    Sha256::digest(certificate)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Client certificates are usually self-signed, so rather than checking them against a CA, any
+/// certificate is accepted as long as the client proves it holds the key. Whether the
+/// fingerprint means anything is up to whatever it is checked against.
+#[derive(Debug)]
+struct AnyClientCertificate {
+    provider: Arc<CryptoProvider>,
+}
+
+impl ClientCertVerifier for AnyClientCertificate {
+    fn client_auth_mandatory(&self) -> bool {
+        false
+    }
+
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _now: UnixTime,
+    ) -> Result<ClientCertVerified, Error> {
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        certificate: &CertificateDer<'_>,
+        signature: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_tls12_signature(
+            message,
+            certificate,
+            signature,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        certificate: &CertificateDer<'_>,
+        signature: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        verify_tls13_signature(
+            message,
+            certificate,
+            signature,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
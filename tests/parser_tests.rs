@@ -0,0 +1,148 @@
+//! Runs the ircdocs parser-tests vectors in `tests/parser-tests` against `IrcMessage`.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use serde::Deserialize;
+use serde_yaml::Mapping;
+
+use ircd::structs::{IrcMessage, Source, Tag, Trailing};
+
+#[derive(Deserialize)]
+struct Suite<T> {
+    tests: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct MessageAtoms {
+    tags: Option<Mapping>,
+    source: Option<String>,
+    verb: String,
+    #[serde(default)]
+    params: Vec<String>,
+}
+
+impl MessageAtoms {
+    /// Tags in the order they're listed, where no value and an empty value are both `None`.
+    fn tags(&self) -> Vec<(&str, Option<&str>)> {
+        self.tags
+            .iter()
+            .flatten()
+            .map(|(key, value)| {
+                let key = key.as_str().expect("tag keys are strings");
+                (key, value.as_str().filter(|value| !value.is_empty()))
+            })
+            .collect()
+    }
+}
+
+#[derive(Deserialize)]
+struct SplitTest {
+    input: String,
+    atoms: MessageAtoms,
+}
+
+#[derive(Deserialize)]
+struct JoinTest {
+    desc: String,
+    atoms: MessageAtoms,
+    matches: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct UserhostTest {
+    source: String,
+    atoms: UserhostAtoms,
+}
+
+#[derive(Deserialize)]
+struct UserhostAtoms {
+    nick: String,
+    user: Option<String>,
+    host: Option<String>,
+}
+
+fn suite<T: for<'de> Deserialize<'de>>(yaml: &str) -> Vec<T> {
+    serde_yaml::from_str::<Suite<T>>(yaml)
+        .expect("valid test vectors")
+        .tests
+}
+
+#[test]
+fn msg_split() {
+    for test in suite::<SplitTest>(include_str!("parser-tests/msg-split.yaml")) {
+        let irc_message = IrcMessage::try_from(test.input.as_str())
+            .unwrap_or_else(|e| panic!("{:?} didn't parse: {}", test.input, e));
+
+        // the order of tags doesn't matter here
+        let tags: BTreeMap<&str, Option<&str>> = irc_message
+            .tags
+            .iter()
+            .map(|tag| (tag.key, tag.value.as_deref()))
+            .collect();
+        let expected_tags: BTreeMap<&str, Option<&str>> = test.atoms.tags().into_iter().collect();
+
+        assert_eq!(tags, expected_tags, "tags of {:?}", test.input);
+        assert_eq!(
+            irc_message.prefix,
+            test.atoms.source.as_deref(),
+            "source of {:?}",
+            test.input
+        );
+        assert_eq!(
+            irc_message.command, test.atoms.verb,
+            "verb of {:?}",
+            test.input
+        );
+        assert_eq!(
+            irc_message.command_parameters, test.atoms.params,
+            "params of {:?}",
+            test.input
+        );
+    }
+}
+
+#[test]
+fn msg_join() {
+    for test in suite::<JoinTest>(include_str!("parser-tests/msg-join.yaml")) {
+        let irc_message = IrcMessage {
+            tags: test
+                .atoms
+                .tags()
+                .into_iter()
+                .map(|(key, value)| Tag::new(key, value))
+                .collect(),
+            prefix: test.atoms.source.as_deref(),
+            command: &test.atoms.verb,
+            command_parameters: test.atoms.params.iter().map(String::as_str).collect(),
+        };
+
+        let line = irc_message
+            .to_line(Trailing::IfNeeded)
+            .unwrap_or_else(|e| panic!("{}: {}", test.desc, e));
+        let line = line.strip_suffix("\r\n").unwrap();
+        assert!(
+            test.matches.iter().any(|m| m == line),
+            "{}: {:?} isn't one of {:?}",
+            test.desc,
+            line,
+            test.matches
+        );
+    }
+}
+
+#[test]
+fn userhost_split() {
+    for test in suite::<UserhostTest>(include_str!("parser-tests/userhost-split.yaml")) {
+        assert_eq!(
+            Source::parse(&test.source),
+            Source {
+                nick: &test.atoms.nick,
+                user: test.atoms.user.as_deref(),
+                host: test.atoms.host.as_deref(),
+            },
+            "{:?}",
+            test.source
+        );
+    }
+}